@@ -7,7 +7,7 @@ use tokio::task;
 
 use tokio::sync::mpsc;
 use waku::{
-    waku_new, Encoding, WakuEvent, LibwakuResponse, WakuContentTopic,
+    waku_new, Encoding, Fleet, WakuEvent, LibwakuResponse, WakuContentTopic,
     WakuMessage, WakuNodeConfig, WakuNodeHandle, Initialized, Running,
     general::pubsubtopic::PubsubTopic,
 };
@@ -101,7 +101,7 @@ impl TicTacToeApp<Initialized> {
 
         // let ctopic = WakuContentTopic::new("waku", "2", "tictactoegame", Encoding::Proto);
         // let content_topics = vec![ctopic];
-        // waku.filter_subscribe(&self.game_topic, content_topics).await.expect("waku should subscribe");
+        // waku.filter_subscribe(&self.game_topic, content_topics, None).await.expect("waku should subscribe");
 
         // End filter example ----------------------------------------
 
@@ -347,14 +347,14 @@ async fn main() -> eframe::Result<()> {
         shards: vec![1, 32, 64, 128, 256],
         // node_key: Some(SecretKey::from_str("2fc0515879e52b7b73297cfd6ab3abf7c344ef84b7a90ff6f4cc19e05a198027").unwrap()),
         max_message_size: Some("1024KiB".to_string()),
-        relay_topics: vec![String::from(&game_topic)],
+        subscribe_shards: vec![32], // game_topic
         log_level: Some("FATAL"), // Supported: TRACE, DEBUG, INFO, NOTICE, WARN, ERROR or FATAL
 
         keep_alive: Some(true),
 
         // Discovery
         dns_discovery: Some(true),
-        dns_discovery_url: Some("enrtree://AMOJVZX4V6EXP7NTJPMAYJYST2QP6AJXYW76IU6VGJS7UVSNDYZG4@boot.prod.status.nodes.status.im"),
+        dns_discovery_url: Some(Fleet::StatusProd.dns_discovery_url()),
         // discv5_discovery: Some(true),
         // discv5_udp_port: Some(9001),
         // discv5_enr_auto_update: Some(false),
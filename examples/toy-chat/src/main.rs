@@ -8,7 +8,6 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use prost::Message;
-use chrono::Utc;
 use std::io::Write;
 use std::sync::{Arc, RwLock};
 use std::{error::Error, io};
@@ -23,8 +22,8 @@ use tui::{
 };
 use unicode_width::UnicodeWidthStr;
 use waku::{
-    general::pubsubtopic::PubsubTopic, general::Result, waku_new, Initialized, LibwakuResponse, Running, WakuEvent,
-    WakuMessage, WakuNodeConfig, WakuNodeHandle,
+    general::pubsubtopic::PubsubTopic, general::Result, waku_new, Fleet, Initialized, LibwakuResponse, Running, Timestamp,
+    WakuEvent, WakuMessage, WakuNodeConfig, WakuNodeHandle,
 };
 
 enum InputMode {
@@ -64,7 +63,7 @@ impl App<Initialized> {
     
             // Discovery
             dns_discovery: Some(true),
-            dns_discovery_url: Some("enrtree://AMOJVZX4V6EXP7NTJPMAYJYST2QP6AJXYW76IU6VGJS7UVSNDYZG4@boot.prod.status.nodes.status.im"),
+            dns_discovery_url: Some(Fleet::StatusProd.dns_discovery_url()),
             // discv5_discovery: Some(true),
             // discv5_udp_port: Some(9001),
             // discv5_enr_auto_update: Some(false),
@@ -139,9 +138,7 @@ impl App<Running> {
 
     async fn retrieve_history(&mut self) {
         let one_day_in_secs = 60 * 60 * 24;
-        let time_start = (Duration::from_secs(Utc::now().timestamp() as u64)
-            - Duration::from_secs(one_day_in_secs))
-            .as_nanos() as u64;
+        let time_start = (Timestamp::now() - Duration::from_secs(one_day_in_secs)).as_nanos();
 
         let include_data = true;
 
@@ -1,21 +1,28 @@
 // std
+use crate::general::pubsubtopic::PubsubTopic;
 use crate::general::waku_decode::WakuDecode;
-use crate::general::Result;
+use crate::general::{Result, WakuError};
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
 use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
 
 use sscanf::{scanf, RegexRepresentation};
 
 /// WakuMessage encoding scheme
-#[derive(Clone, Debug, Eq, PartialEq, Default)]
+/// [RFC 23](https://rfc.vac.dev/spec/23/) only recommends an encoding segment, so a content
+/// topic with an empty one (`/app/1/name/`) is valid and parsed as [`Encoding::None`].
+/// The standard encodings are parsed regardless of their case, others are kept verbatim
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Default)]
 pub enum Encoding {
     #[default]
     Proto,
     Rlp,
     Rfc26,
+    /// No encoding specified, represented by an empty segment
+    None,
     Unknown(String),
 }
 
@@ -25,6 +32,7 @@ impl Display for Encoding {
             Encoding::Proto => "proto",
             Encoding::Rlp => "rlp",
             Encoding::Rfc26 => "rfc26",
+            Encoding::None => "",
             Encoding::Unknown(value) => value,
         };
         f.write_str(s)
@@ -39,7 +47,8 @@ impl FromStr for Encoding {
             "proto" => Ok(Self::Proto),
             "rlp" => Ok(Self::Rlp),
             "rfc26" => Ok(Self::Rfc26),
-            encoding => Ok(Self::Unknown(encoding.to_string())),
+            "" => Ok(Self::None),
+            _ => Ok(Self::Unknown(s.to_string())),
         }
     }
 }
@@ -48,8 +57,15 @@ impl RegexRepresentation for Encoding {
     const REGEX: &'static str = r"\w";
 }
 
+/// Cluster of [The Waku Network](https://rfc.vac.dev/spec/64/)
+pub const WAKU_NETWORK_CLUSTER_ID: u16 = 1;
+/// Autosharding generation of The Waku Network
+pub const WAKU_NETWORK_GENERATION: u32 = 0;
+/// Number of shards of autosharding generation 0, the one of The Waku Network
+pub const WAKU_NETWORK_SHARD_COUNT: u16 = 8;
+
 /// A waku content topic `/{application_name}/{version}/{content_topic_name}/{encdoing}`
-#[derive(Clone, Debug, Eq, PartialEq, Default)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Default)]
 pub struct WakuContentTopic {
     pub application_name: Cow<'static, str>,
     pub version: Cow<'static, str>,
@@ -72,6 +88,27 @@ impl WakuContentTopic {
         }
     }
 
+    /// Content topic with a numeric `version`, as the struct fields of earlier releases had it
+    pub fn from_parts(app: &str, version: u32, name: &str, encoding: Encoding) -> Self {
+        Self {
+            application_name: Cow::Owned(app.to_string()),
+            version: Cow::Owned(version.to_string()),
+            content_topic_name: Cow::Owned(name.to_string()),
+            encoding,
+        }
+    }
+
+    /// Content topic as other Waku implementations (nwaku, go-waku, js-waku) expect it,
+    /// `/{application_name}/{version}/{content_topic_name}/{encoding}` as per
+    /// [RFC 23](https://rfc.vac.dev/spec/23/). The standard encodings are written lowercase,
+    /// whatever case they were parsed from, while unknown encodings and the other segments are
+    /// kept verbatim, as the other implementations compare content topics byte for byte.
+    /// [`Encoding::None`] leaves the last segment empty, which some implementations reject.
+    /// An alias of the [`Display`] format, which is already the interop one
+    pub fn to_interop_string(&self) -> String {
+        self.to_string()
+    }
+
     pub fn join_content_topics(topics: Vec<WakuContentTopic>) -> String {
         topics
             .iter()
@@ -79,11 +116,92 @@ impl WakuContentTopic {
             .collect::<Vec<_>>()
             .join(",")
     }
+
+    /// `/waku/2/rs/{cluster_id}/{shard}` pubsub topic of the shard this content topic maps to
+    /// under generation `0` of [RFC 51](https://rfc.vac.dev/spec/51/) autosharding, in a
+    /// cluster of `shard_count` shards. The shard only depends on the application name and
+    /// version of the content topic. Panics if `shard_count` is `0`
+    pub fn pubsub_topic(&self, cluster_id: u16, shard_count: u16) -> PubsubTopic {
+        PubsubTopic::from_shard(cluster_id, autoshard_index(self, shard_count))
+    }
+
+    /// Shard index each content topic maps to, see [`Self::pubsub_topic`]
+    #[deprecated(
+        note = "use `WakuContentTopic::pubsub_topic`, the shard doesn't depend on the cluster"
+    )]
+    pub fn shard_assignments(
+        topics: &[WakuContentTopic],
+        _cluster_id: u16,
+        num_shards: u16,
+    ) -> Vec<(WakuContentTopic, u16)> {
+        topics
+            .iter()
+            .map(|topic| (topic.clone(), autoshard_index(topic, num_shards)))
+            .collect()
+    }
+
+    /// Same as [`Self::pubsub_topic`], erroring for any `generation` but `0` and for a
+    /// `shard_count` of `0`
+    #[deprecated(note = "use `WakuContentTopic::pubsub_topic`, only generation 0 is specified")]
+    pub fn autoshard(
+        &self,
+        cluster_id: u16,
+        generation: u32,
+        shard_count: u16,
+    ) -> Result<PubsubTopic> {
+        if generation != 0 {
+            return Err(WakuError::Other(format!(
+                "autosharding generation {generation} is not supported, only generation 0 is specified"
+            )));
+        }
+        if shard_count == 0 {
+            return Err(WakuError::Other(
+                "autosharding needs at least one shard".to_string(),
+            ));
+        }
+        Ok(self.pubsub_topic(cluster_id, shard_count))
+    }
+
+    /// Same as [`Self::pubsub_topic`]
+    #[deprecated(note = "use `WakuContentTopic::pubsub_topic`")]
+    pub fn auto_shard(&self, cluster_id: u16, num_shards: u16) -> PubsubTopic {
+        self.pubsub_topic(cluster_id, num_shards)
+    }
+
+    /// Shard of the [pubsub topic](Self::pubsub_topic) this content topic maps to
+    #[deprecated(
+        note = "use `WakuContentTopic::pubsub_topic`, the shard doesn't depend on the cluster"
+    )]
+    pub fn shard_index(&self, _cluster_id: u16, shard_count: u16) -> u16 {
+        autoshard_index(self, shard_count)
+    }
+
+    /// Pubsub topic of the shard of The Waku Network this content topic maps to
+    #[deprecated(
+        note = "use `WakuContentTopic::pubsub_topic` with `WAKU_NETWORK_CLUSTER_ID` and `WAKU_NETWORK_SHARD_COUNT`"
+    )]
+    pub fn waku_network_shard(&self) -> PubsubTopic {
+        self.pubsub_topic(WAKU_NETWORK_CLUSTER_ID, WAKU_NETWORK_SHARD_COUNT)
+    }
+}
+
+/// RFC 51 autosharding: the last 8 bytes of `sha256(application_name || version)`,
+/// read as a big endian `u64`, modulo the number of shards in the network
+fn autoshard_index(topic: &WakuContentTopic, num_shards: u16) -> u16 {
+    let hash = Sha256::new()
+        .chain_update(topic.application_name.as_bytes())
+        .chain_update(topic.version.as_bytes())
+        .finalize();
+    let value = u64::from_be_bytes(hash[24..].try_into().expect("sha256 digest is 32 bytes"));
+    value
+        .checked_rem(num_shards as u64)
+        .expect("number of shards should be greater than zero") as u16
 }
 
 impl WakuDecode for WakuContentTopic {
     fn decode(input: &str) -> Result<Self> {
-        Ok(serde_json::from_str(input).expect("could not parse store resp"))
+        serde_json::from_str(input)
+            .map_err(|e| WakuError::Decode(format!("could not parse content topic: {e}")))
     }
 }
 
@@ -92,7 +210,7 @@ impl FromStr for WakuContentTopic {
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         if let Ok((application_name, version, content_topic_name, encoding)) =
-            scanf!(s, "/{}/{}/{}/{:/.+?/}", String, String, String, Encoding)
+            scanf!(s, "/{}/{}/{}/{:/.*?/}", String, String, String, Encoding)
         {
             Ok(WakuContentTopic {
                 application_name: Cow::Owned(application_name),
@@ -140,3 +258,135 @@ impl<'de> Deserialize<'de> for WakuContentTopic {
             .map_err(D::Error::custom)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pubsub_topic_vectors() {
+        // RFC 51 vectors, pinned to the assignments nwaku and js-waku produce, on The Waku
+        // Network, on another cluster and on a network of a single shard
+        for (topic, cluster_id, shard_count, pubsub_topic) in [
+            ("/toychat/2/huilong/proto", 1, 8, "/waku/2/rs/1/3"),
+            ("/myapp/1/latest/proto", 1, 8, "/waku/2/rs/1/0"),
+            ("/waku/2/content/test.js", 1, 8, "/waku/2/rs/1/1"),
+            ("/toychat/2/huilong/proto", 16, 8, "/waku/2/rs/16/3"),
+            ("/toychat/2/huilong/proto", 16, 1, "/waku/2/rs/16/0"),
+        ] {
+            let topic: WakuContentTopic = topic.parse().unwrap();
+            assert_eq!(
+                topic.pubsub_topic(cluster_id, shard_count),
+                PubsubTopic::new(pubsub_topic)
+            );
+        }
+    }
+
+    #[test]
+    fn shard_ignores_name_and_encoding() {
+        let topic: WakuContentTopic = "/toychat/2/huilong/proto".parse().unwrap();
+        let other: WakuContentTopic = "/toychat/2/other/rlp".parse().unwrap();
+        assert_eq!(topic.pubsub_topic(1, 8), other.pubsub_topic(1, 8));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_aliases_match_pubsub_topic() {
+        let topic: WakuContentTopic = "/toychat/2/huilong/proto".parse().unwrap();
+        let pubsub_topic = topic.pubsub_topic(16, 8);
+
+        assert_eq!(topic.autoshard(16, 0, 8).unwrap(), pubsub_topic);
+        assert!(topic.autoshard(16, 1, 8).is_err());
+        assert!(topic.autoshard(16, 0, 0).is_err());
+        assert_eq!(topic.auto_shard(16, 8), pubsub_topic);
+        assert_eq!(Some((16, topic.shard_index(16, 8))), pubsub_topic.shard());
+        assert_eq!(
+            WakuContentTopic::shard_assignments(std::slice::from_ref(&topic), 16, 8),
+            vec![(topic.clone(), 3)]
+        );
+        assert_eq!(
+            topic.waku_network_shard(),
+            topic.pubsub_topic(WAKU_NETWORK_CLUSTER_ID, WAKU_NETWORK_SHARD_COUNT)
+        );
+    }
+
+    #[test]
+    fn from_parts_matches_parsed_topic() {
+        let topic = WakuContentTopic::from_parts("toychat", 2, "huilong", Encoding::Proto);
+        assert_eq!(
+            topic,
+            "/toychat/2/huilong/proto"
+                .parse::<WakuContentTopic>()
+                .unwrap()
+        );
+        assert_eq!(topic.to_string(), "/toychat/2/huilong/proto");
+    }
+
+    #[test]
+    fn empty_encoding_is_none() {
+        let topic: WakuContentTopic = "/app/1/name/".parse().unwrap();
+        assert_eq!(topic.application_name, "app");
+        assert_eq!(topic.version, "1");
+        assert_eq!(topic.content_topic_name, "name");
+        assert_eq!(topic.encoding, Encoding::None);
+        assert_eq!(topic.to_string(), "/app/1/name/");
+
+        let topic: WakuContentTopic = "/app/1/name/proto".parse().unwrap();
+        assert_eq!(topic.encoding, Encoding::Proto);
+    }
+
+    #[test]
+    fn interop_string_normalizes_standard_encodings_only() {
+        let topic: WakuContentTopic = "/MyApp/1/Chat/PROTO".parse().unwrap();
+        assert_eq!(topic.encoding, Encoding::Proto);
+        assert_eq!(topic.to_interop_string(), "/MyApp/1/Chat/proto");
+
+        let topic: WakuContentTopic = "/app/1/name/Json".parse().unwrap();
+        assert_eq!(topic.encoding, Encoding::Unknown("Json".to_string()));
+        assert_eq!(topic.to_interop_string(), "/app/1/name/Json");
+
+        let topic = WakuContentTopic::new("app", "1", "name", Encoding::None);
+        assert_eq!(topic.to_interop_string(), "/app/1/name/");
+    }
+
+    #[test]
+    fn interop_string_round_trips() {
+        const CHARS: &[u8] = b"abcXYZ019.-_";
+        const ENCODINGS: [&str; 8] = ["proto", "Proto", "RLP", "rfc26", "", "json", "JSON", "x.y"];
+        // xorshift64, deterministic so failures reproduce
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        let segment = |next: &mut dyn FnMut() -> usize| -> String {
+            (0..1 + next() % 8)
+                .map(|_| CHARS[next() % CHARS.len()] as char)
+                .collect()
+        };
+
+        for _ in 0..2_000 {
+            let input = format!(
+                "/{}/{}/{}/{}",
+                segment(&mut next),
+                segment(&mut next),
+                segment(&mut next),
+                ENCODINGS[next() % ENCODINGS.len()]
+            );
+            let topic: WakuContentTopic = input.parse().unwrap();
+            let interop = topic.to_interop_string();
+            let reparsed: WakuContentTopic = interop.parse().unwrap();
+
+            assert_eq!(reparsed, topic, "{input}");
+            assert_eq!(reparsed.to_interop_string(), interop, "{input}");
+            assert_eq!(interop.to_lowercase(), input.to_lowercase(), "{input}");
+        }
+    }
+
+    #[test]
+    fn missing_encoding_segment_is_rejected() {
+        assert!("/app/1/name".parse::<WakuContentTopic>().is_err());
+    }
+}
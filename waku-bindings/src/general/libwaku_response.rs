@@ -1,5 +1,5 @@
 use crate::general::waku_decode::WakuDecode;
-use crate::general::Result;
+use crate::general::{ErrorCategory, Result, WakuError};
 use std::convert::TryFrom;
 use std::str;
 use waku_sys::{RET_ERR, RET_MISSING_CALLBACK, RET_OK};
@@ -30,6 +30,22 @@ impl TryFrom<(u32, &str)> for LibwakuResponse {
     }
 }
 
+/// Error for a failure reported by libwaku. Timeouts are only reported as text,
+/// so they are told through [`ErrorCategory::of_message`]
+pub(crate) fn failure_error(msg: String) -> WakuError {
+    match ErrorCategory::of_message(&msg) {
+        ErrorCategory::Timeout => WakuError::Timeout(msg),
+        _ => WakuError::LibwakuFailure(msg),
+    }
+}
+
+/// Error for a callback libwaku never called despite returning `code`
+fn undefined_error(code: i32) -> WakuError {
+    WakuError::LibwakuFailure(format!(
+        "undefined ffi state: code({code}) was returned but callback was not executed"
+    ))
+}
+
 /// Used in cases where the FFI call doesn't return additional information in the
 /// callback. Instead, it returns RET_OK, RET_ERR, etc.
 pub fn handle_no_response(code: i32, result: LibwakuResponse) -> Result<()> {
@@ -40,12 +56,9 @@ pub fn handle_no_response(code: i32, result: LibwakuResponse) -> Result<()> {
 
     match result {
         LibwakuResponse::Success(_) => Ok(()),
-        LibwakuResponse::Failure(v) => Err(v),
-        LibwakuResponse::MissingCallback => panic!("callback is required"),
-        LibwakuResponse::Undefined => panic!(
-            "undefined ffi state: code({}) was returned but callback was not executed",
-            code
-        ),
+        LibwakuResponse::Failure(v) => Err(failure_error(v)),
+        LibwakuResponse::MissingCallback => Err(WakuError::MissingCallback),
+        LibwakuResponse::Undefined => Err(undefined_error(code)),
     }
 }
 
@@ -54,11 +67,62 @@ pub fn handle_no_response(code: i32, result: LibwakuResponse) -> Result<()> {
 pub fn handle_response<F: WakuDecode>(code: i32, result: LibwakuResponse) -> Result<F> {
     match result {
         LibwakuResponse::Success(v) => WakuDecode::decode(&v.unwrap_or_default()),
-        LibwakuResponse::Failure(v) => Err(v),
-        LibwakuResponse::MissingCallback => panic!("callback is required"),
-        LibwakuResponse::Undefined => panic!(
-            "undefined ffi state: code({}) was returned but callback was not executed",
-            code
-        ),
+        LibwakuResponse::Failure(v) => Err(failure_error(v)),
+        LibwakuResponse::MissingCallback => Err(WakuError::MissingCallback),
+        LibwakuResponse::Undefined => Err(undefined_error(code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handle_no_response, handle_response, LibwakuResponse};
+    use crate::general::WakuError;
+    use waku_sys::{RET_ERR, RET_MISSING_CALLBACK, RET_OK};
+
+    #[test]
+    fn timeout_failures_typed() {
+        let failure = |msg: &str| LibwakuResponse::Failure(format!("waku error: {msg}"));
+
+        let err =
+            handle_no_response(RET_ERR as i32, failure("connect peer timed out")).unwrap_err();
+        assert!(matches!(err, WakuError::Timeout(_)));
+        assert!(err.is_retryable());
+
+        let err = handle_response::<String>(
+            RET_ERR as i32,
+            failure("Timeout while waiting for the store response"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, WakuError::Timeout(_)));
+
+        let err = handle_no_response(RET_ERR as i32, failure("invalid multiaddress")).unwrap_err();
+        assert_eq!(
+            err,
+            WakuError::LibwakuFailure("waku error: invalid multiaddress".to_string())
+        );
+        assert!(handle_no_response(RET_OK as i32, LibwakuResponse::Undefined).is_ok());
+    }
+
+    #[test]
+    fn missing_callback_and_undefined_state_are_errors() {
+        assert_eq!(
+            handle_no_response(
+                RET_MISSING_CALLBACK as i32,
+                LibwakuResponse::MissingCallback
+            ),
+            Err(WakuError::MissingCallback)
+        );
+        let err =
+            handle_response::<String>(RET_ERR as i32, LibwakuResponse::Undefined).unwrap_err();
+        assert!(matches!(err, WakuError::LibwakuFailure(_)));
+        assert!(handle_no_response(RET_ERR as i32, LibwakuResponse::Undefined).is_err());
+    }
+
+    #[test]
+    fn undecodable_response() {
+        let err =
+            handle_response::<usize>(RET_OK as i32, LibwakuResponse::Success(Some("x".into())))
+                .unwrap_err();
+        assert!(matches!(err, WakuError::Decode(_)));
     }
 }
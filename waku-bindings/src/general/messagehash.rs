@@ -1,6 +1,9 @@
+use crate::general::pubsubtopic::PubsubTopic;
 use crate::general::waku_decode::WakuDecode;
+use crate::general::WakuMessage;
 use hex::FromHex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::convert::TryInto;
 use std::fmt;
 use std::fmt::Write;
@@ -20,6 +23,32 @@ impl MessageHash {
     }
 }
 
+/// Deterministic hash of `message` published on `pubsub_topic`, as per
+/// [RFC 14](https://rfc.vac.dev/spec/14/#deterministic-message-hashing):
+/// `sha256(pubsub_topic || payload || content_topic || meta || timestamp)`,
+/// with the timestamp as a big endian `u64`
+pub(crate) fn compute_message_hash(
+    message: &WakuMessage,
+    pubsub_topic: &PubsubTopic,
+) -> MessageHash {
+    let digest = Sha256::new()
+        .chain_update(String::from(pubsub_topic).as_bytes())
+        .chain_update(&message.payload)
+        .chain_update(message.content_topic.to_string().as_bytes())
+        .chain_update(&message.meta)
+        .chain_update(message.timestamp.to_be_bytes())
+        .finalize();
+    MessageHash(digest.into())
+}
+
+impl WakuMessage {
+    /// Hash of the message once published on `pubsub_topic`, computed locally as libwaku does,
+    /// see [RFC 14](https://rfc.vac.dev/spec/14/#deterministic-message-hashing)
+    pub fn hash(&self, pubsub_topic: &PubsubTopic) -> MessageHash {
+        compute_message_hash(self, pubsub_topic)
+    }
+}
+
 impl FromStr for MessageHash {
     type Err = String;
 
@@ -39,8 +68,8 @@ impl FromStr for MessageHash {
 }
 
 impl WakuDecode for MessageHash {
-    fn decode(input: &str) -> Result<Self, String> {
-        MessageHash::from_str(input)
+    fn decode(input: &str) -> crate::general::Result<Self> {
+        MessageHash::from_str(input).map_err(crate::general::WakuError::Parse)
     }
 }
 
@@ -50,3 +79,33 @@ impl fmt::Display for MessageHash {
         write!(f, "{}", self.to_hex_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::contenttopic::WakuContentTopic;
+
+    #[test]
+    fn rfc14_test_vector() {
+        let content_topic: WakuContentTopic = "/waku/2/default-content/proto".parse().unwrap();
+        let mut message = WakuMessage::new(
+            hex::decode("010203045445535405060708").unwrap(),
+            content_topic,
+            0,
+            hex::decode("73757065722d736563726574").unwrap(),
+            false,
+        );
+        message.timestamp = 0x175789bfa23f8400;
+
+        let pubsub_topic = PubsubTopic::new("/waku/2/default-waku/proto");
+        let hash = compute_message_hash(&message, &pubsub_topic);
+        assert_eq!(
+            hash,
+            "64cce733fed134e83da02b02c6f689814872b1a0ac97ea56b76095c3c72bfe05"
+                .parse()
+                .unwrap()
+        );
+        assert_eq!(message.hash(&pubsub_topic), hash);
+        assert_ne!(message.hash(&PubsubTopic::new("/waku/2/rs/0/1")), hash);
+    }
+}
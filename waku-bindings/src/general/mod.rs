@@ -3,25 +3,237 @@
 pub mod contenttopic;
 pub mod libwaku_response;
 pub mod messagehash;
+pub mod payload;
 pub mod pubsubtopic;
+pub mod signing;
 pub mod time;
+pub mod util;
 pub mod waku_decode;
 
+// std
+use std::fmt;
 // crates
-use crate::general::time::get_now_in_nanosecs;
+use crate::general::time::Timestamp;
+use crate::node::StoreStatus;
+use base64::Engine;
 use contenttopic::WakuContentTopic;
+use secp256k1::ecdsa::RecoverableSignature;
+use secp256k1::PublicKey;
 use serde::{Deserialize, Serialize};
 use serde_aux::prelude::*;
 
 /// Waku message version
 pub type WakuMessageVersion = usize;
 
-pub type Result<T> = std::result::Result<T, String>;
+pub type Result<T> = std::result::Result<T, WakuError>;
+
+/// Errors returned by the bindings
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WakuError {
+    /// The node was destroyed, so its handle can't be used anymore
+    NodeDestroyed,
+    /// A relay publication found no peer to send the message to, i.e. the mesh isn't formed yet
+    NoPeersToPublish(String),
+    /// The message is larger than the network accepts
+    MessageTooLarge(String),
+    /// The store node couldn't be dialed
+    StorePeerUnreachable(String),
+    /// The store node answered the query with a failure status, classified by
+    /// [`store_status`](WakuError::store_status)
+    StorePeerRejected {
+        /// HTTP-like status code of the store response
+        status_code: u32,
+        /// Description of the failure given by the store node
+        status_desc: String,
+    },
+    /// Subscribing would exceed
+    /// [`max_relay_subscriptions`](crate::WakuNodeConfig::max_relay_subscriptions)
+    SubscriptionLimitReached { limit: usize },
+    /// libwaku gave up on the operation after its timeout
+    Timeout(String),
+    /// libwaku reported the operation as failed
+    LibwakuFailure(String),
+    /// libwaku was called without the callback it requires
+    MissingCallback,
+    /// A libwaku response couldn't be decoded
+    Decode(String),
+    /// The node configuration is inconsistent or unsupported
+    InvalidConfig(String),
+    /// An argument or value couldn't be parsed, e.g. a multiaddress or a message hash
+    Parse(String),
+    /// Any other failure found by the bindings
+    Other(String),
+}
+
+impl fmt::Display for WakuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WakuError::NodeDestroyed => write!(f, "the waku node was destroyed"),
+            WakuError::MissingCallback => write!(f, "libwaku requires a callback"),
+            WakuError::StorePeerRejected {
+                status_code,
+                status_desc,
+            } => write!(
+                f,
+                "store query rejected with status {status_code}: {status_desc}"
+            ),
+            WakuError::SubscriptionLimitReached { limit } => {
+                write!(
+                    f,
+                    "already subscribed to the maximum of {limit} relay topics"
+                )
+            }
+            WakuError::NoPeersToPublish(msg)
+            | WakuError::MessageTooLarge(msg)
+            | WakuError::StorePeerUnreachable(msg)
+            | WakuError::Timeout(msg)
+            | WakuError::LibwakuFailure(msg)
+            | WakuError::Other(msg) => write!(f, "{msg}"),
+            WakuError::Decode(msg) => write!(f, "could not decode libwaku response: {msg}"),
+            WakuError::InvalidConfig(msg) => write!(f, "invalid node configuration: {msg}"),
+            WakuError::Parse(msg) => write!(f, "could not parse: {msg}"),
+        }
+    }
+}
+
+/// Kind of a failure, told from its [`WakuError`] variant or, for the failures libwaku only
+/// reports as text, from the wording of the message
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// The peer was dialed too recently after a failed dial
+    DialBackoff,
+    /// No peer to carry out the operation with
+    NoPeers,
+    /// The peer doesn't speak the protocol
+    ProtocolNotSupported,
+    /// The peer turned the request down because of its rate limit
+    RateLimited,
+    /// The message is larger than the network accepts
+    MessageTooLarge,
+    /// The operation didn't complete in time
+    Timeout,
+    /// The peer couldn't be dialed
+    PeerUnreachable,
+    /// Any other failure, or one whose kind can't be told
+    Other,
+}
+
+/// Wordings of the failures of each category, matched against lowercase messages
+const CATEGORY_PATTERNS: [(ErrorCategory, &[&str]); 7] = [
+    (ErrorCategory::DialBackoff, &["backoff"]),
+    (
+        ErrorCategory::NoPeers,
+        &["nopeerstopublish", "no peer", "no suitable"],
+    ),
+    (
+        ErrorCategory::ProtocolNotSupported,
+        &[
+            "protocol not supported",
+            "protocols not supported",
+            "failed to negotiate",
+        ],
+    ),
+    (
+        ErrorCategory::RateLimited,
+        &[
+            "rate limit",
+            "rate_limit",
+            "too many requests",
+            "too_many_requests",
+        ],
+    ),
+    (
+        ErrorCategory::MessageTooLarge,
+        &["size exceeded", "too large"],
+    ),
+    (
+        ErrorCategory::Timeout,
+        &["timed out", "timeout", "deadline exceeded"],
+    ),
+    (
+        ErrorCategory::PeerUnreachable,
+        &["dial", "connection refused", "unreachable"],
+    ),
+];
+
+impl ErrorCategory {
+    /// Category of a failure libwaku only reports as text, told from its wording
+    pub(crate) fn of_message(msg: &str) -> Self {
+        let msg = msg.to_lowercase();
+        CATEGORY_PATTERNS
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| msg.contains(pattern)))
+            .map_or(ErrorCategory::Other, |(category, _)| *category)
+    }
+}
+
+impl WakuError {
+    /// Best-effort category of the failure, [`ErrorCategory::Other`] when it can't be told.
+    /// The message itself stays available through `Display`
+    pub fn classify(&self) -> ErrorCategory {
+        match self {
+            WakuError::NoPeersToPublish(_) => ErrorCategory::NoPeers,
+            WakuError::MessageTooLarge(_) => ErrorCategory::MessageTooLarge,
+            WakuError::Timeout(_) => ErrorCategory::Timeout,
+            WakuError::StorePeerUnreachable(_) => ErrorCategory::PeerUnreachable,
+            WakuError::StorePeerRejected { status_code, .. } => {
+                match StoreStatus::from(*status_code) {
+                    StoreStatus::TooManyRequests => ErrorCategory::RateLimited,
+                    StoreStatus::ServiceUnavailable => ErrorCategory::NoPeers,
+                    _ => ErrorCategory::Other,
+                }
+            }
+            WakuError::LibwakuFailure(msg) | WakuError::Other(msg) => {
+                ErrorCategory::of_message(msg)
+            }
+            _ => ErrorCategory::Other,
+        }
+    }
+
+    /// Whether the same call may succeed if issued again later
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, WakuError::NoPeersToPublish(_) | WakuError::Timeout(_))
+    }
+
+    /// Category of the status a store peer rejected a query with
+    pub fn store_status(&self) -> Option<StoreStatus> {
+        match self {
+            WakuError::StorePeerRejected { status_code, .. } => Some((*status_code).into()),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for WakuError {}
+
+impl From<String> for WakuError {
+    fn from(msg: String) -> Self {
+        WakuError::Other(msg)
+    }
+}
+
+impl From<&str> for WakuError {
+    fn from(msg: &str) -> Self {
+        WakuError::Other(msg.to_string())
+    }
+}
+
+impl From<WakuError> for String {
+    fn from(err: WakuError) -> Self {
+        err.to_string()
+    }
+}
+
+/// Version of the [`WakuMessage::to_bytes`] format
+const MESSAGE_BYTES_FORMAT: u8 = 1;
 
 // TODO: Properly type and deserialize payload form base64 encoded string
 /// Waku message in JSON format.
 /// as per the [specification](https://rfc.vac.dev/spec/36/#jsonmessage-type)
-#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+/// Only the content topic is required when deserializing, other fields default to empty
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct WakuMessage {
     #[serde(with = "base64_serde", default = "Vec::new")]
@@ -31,27 +243,67 @@ pub struct WakuMessage {
     /// The Waku Message version number
     #[serde(default)]
     pub version: WakuMessageVersion,
-    /// Unix timestamp in nanoseconds
-    #[serde(deserialize_with = "deserialize_number_from_string")]
+    /// Unix timestamp in nanoseconds, see [`Timestamp`]. `0` if missing
+    #[serde(deserialize_with = "deserialize_number_from_string", default)]
     pub timestamp: u64,
     #[serde(with = "base64_serde", default = "Vec::new")]
     pub meta: Vec<u8>,
     #[serde(default)]
     pub ephemeral: bool,
-    // TODO: implement RLN fields
+    /// RLN proof attached by the publisher when RLN relay is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_proof: Option<RateLimitProof>,
     #[serde(flatten)]
-    _extras: serde_json::Value,
+    _extras: serde_json::Map<String, serde_json::Value>,
+}
+
+/// [RLN](https://rfc.vac.dev/spec/17/) proof that the publisher stayed within its message rate,
+/// each field base64 encoded in JSON
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitProof {
+    /// zkSNARK proof
+    #[serde(with = "base64_serde", default = "Vec::new")]
+    pub proof: Vec<u8>,
+    /// Root of the membership merkle tree the proof is made against
+    #[serde(with = "base64_serde", default = "Vec::new")]
+    pub merkle_root: Vec<u8>,
+    /// Epoch the message was published in, the rate being limited per epoch
+    #[serde(with = "base64_serde", default = "Vec::new")]
+    pub epoch: Vec<u8>,
+    /// Hash of the message, the point the secret share is evaluated at
+    #[serde(with = "base64_serde", default = "Vec::new")]
+    pub share_x: Vec<u8>,
+    /// Secret share of the publisher at `share_x`, two shares of the same epoch revealing its
+    /// key if it exceeds its rate
+    #[serde(with = "base64_serde", default = "Vec::new")]
+    pub share_y: Vec<u8>,
+    /// Internal nullifier, the same for the messages of a publisher within an epoch
+    #[serde(with = "base64_serde", default = "Vec::new")]
+    pub nullifier: Vec<u8>,
+    /// Identifier of the application the proof is made for, so it can't be replayed elsewhere
+    #[serde(with = "base64_serde", default = "Vec::new")]
+    pub rln_identifier: Vec<u8>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct WakuStoreRespMessage {
-    // #[serde(with = "base64_serde", default = "Vec::new")]
+    /// Base64 encoded like the one of [`WakuMessage`], a byte array is accepted as well
+    #[serde(
+        serialize_with = "base64_serde::serialize",
+        deserialize_with = "base64_serde::deserialize_str_or_bytes"
+    )]
     pub payload: Vec<u8>,
     /// The content topic to be set on the message
     // #[serde(rename = "contentTopic")]
     pub content_topic: String,
-    // #[serde(with = "base64_serde", default = "Vec::new")]
+    /// Base64 encoded like the one of [`WakuMessage`], a byte array is accepted as well
+    #[serde(
+        serialize_with = "base64_serde::serialize",
+        deserialize_with = "base64_serde::deserialize_str_or_bytes",
+        default = "Vec::new"
+    )]
     pub meta: Vec<u8>,
     /// The Waku Message version number
     #[serde(default)]
@@ -78,16 +330,188 @@ impl WakuMessage {
             payload,
             content_topic,
             version,
-            timestamp: get_now_in_nanosecs(),
+            timestamp: Timestamp::now().as_nanos(),
             meta,
             ephemeral,
+            rate_limit_proof: None,
             _extras: Default::default(),
         }
     }
 
+    /// Replace the timestamp, set to the creation time by the constructors
+    pub fn with_timestamp(mut self, timestamp: Timestamp) -> Self {
+        self.timestamp = timestamp.as_nanos();
+        self
+    }
+
+    /// Build a message from a hex encoded payload, with or without `0x` prefix
+    pub fn from_hex_payload<META: AsRef<[u8]>>(
+        payload: &str,
+        content_topic: WakuContentTopic,
+        version: WakuMessageVersion,
+        meta: META,
+        ephemeral: bool,
+    ) -> Result<Self> {
+        let payload = hex::decode(payload.strip_prefix("0x").unwrap_or(payload))
+            .map_err(|e| format!("could not decode hex payload: {e}"))?;
+        Ok(Self::new(payload, content_topic, version, meta, ephemeral))
+    }
+
+    /// Build a message from a standard base64 encoded payload
+    pub fn from_base64_payload<META: AsRef<[u8]>>(
+        payload: &str,
+        content_topic: WakuContentTopic,
+        version: WakuMessageVersion,
+        meta: META,
+        ephemeral: bool,
+    ) -> Result<Self> {
+        let payload = base64::engine::general_purpose::STANDARD
+            .decode(payload)
+            .map_err(|e| format!("could not decode base64 payload: {e}"))?;
+        Ok(Self::new(payload, content_topic, version, meta, ephemeral))
+    }
+
     pub fn payload(&self) -> &[u8] {
         &self.payload
     }
+
+    /// Payload as a lowercase hex string, without `0x` prefix
+    pub fn payload_hex(&self) -> String {
+        hex::encode(&self.payload)
+    }
+
+    /// Payload as standard base64, the same form used on the wire
+    pub fn payload_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.payload)
+    }
+
+    /// Compact binary form meant for local caches, unrelated to the JSON exchanged with libwaku.
+    /// A format version byte is followed by the payload, content topic and meta, each prefixed
+    /// by its length as a little endian `u32`, then the little endian `u64` version and timestamp,
+    /// an ephemeral byte and a byte telling whether a rate limit proof follows, its fields in
+    /// declaration order being length prefixed too. Unknown JSON fields kept by deserialization
+    /// are left out. Fails with [`WakuError::MessageTooLarge`] if a field is 4GiB or longer
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        fn put_prefixed(bytes: &mut Vec<u8>, field: &[u8]) -> Result<()> {
+            let len = u32::try_from(field.len()).map_err(|_| {
+                WakuError::MessageTooLarge(format!(
+                    "a message field of {} bytes doesn't fit the message bytes format",
+                    field.len()
+                ))
+            })?;
+            bytes.extend(len.to_le_bytes());
+            bytes.extend(field);
+            Ok(())
+        }
+
+        let content_topic = self.content_topic.to_string();
+        let mut bytes =
+            Vec::with_capacity(30 + self.payload.len() + content_topic.len() + self.meta.len());
+        bytes.push(MESSAGE_BYTES_FORMAT);
+        put_prefixed(&mut bytes, &self.payload)?;
+        put_prefixed(&mut bytes, content_topic.as_bytes())?;
+        put_prefixed(&mut bytes, &self.meta)?;
+        bytes.extend((self.version as u64).to_le_bytes());
+        bytes.extend(self.timestamp.to_le_bytes());
+        bytes.push(u8::from(self.ephemeral));
+        bytes.push(u8::from(self.rate_limit_proof.is_some()));
+        if let Some(proof) = &self.rate_limit_proof {
+            for field in [
+                &proof.proof,
+                &proof.merkle_root,
+                &proof.epoch,
+                &proof.share_x,
+                &proof.share_y,
+                &proof.nullifier,
+                &proof.rln_identifier,
+            ] {
+                put_prefixed(&mut bytes, field)?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Read back a message written by [`WakuMessage::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader(bytes);
+
+        let [format] = reader.take_array()?;
+        if format != MESSAGE_BYTES_FORMAT {
+            return Err(format!("unsupported message bytes format {format}").into());
+        }
+        let payload = reader.take_prefixed()?.to_vec();
+        let content_topic = std::str::from_utf8(reader.take_prefixed()?)
+            .map_err(|e| format!("invalid content topic in message bytes: {e}"))?
+            .parse()?;
+        let meta = reader.take_prefixed()?.to_vec();
+        let version = WakuMessageVersion::try_from(u64::from_le_bytes(reader.take_array()?))
+            .map_err(|e| format!("invalid version in message bytes: {e}"))?;
+        let timestamp = u64::from_le_bytes(reader.take_array()?);
+        let ephemeral = match reader.take_array()? {
+            [0] => false,
+            [1] => true,
+            [other] => {
+                return Err(format!("invalid ephemeral flag in message bytes: {other}").into())
+            }
+        };
+        let rate_limit_proof = match reader.take_array()? {
+            [0] => None,
+            [1] => Some(RateLimitProof {
+                proof: reader.take_prefixed()?.to_vec(),
+                merkle_root: reader.take_prefixed()?.to_vec(),
+                epoch: reader.take_prefixed()?.to_vec(),
+                share_x: reader.take_prefixed()?.to_vec(),
+                share_y: reader.take_prefixed()?.to_vec(),
+                nullifier: reader.take_prefixed()?.to_vec(),
+                rln_identifier: reader.take_prefixed()?.to_vec(),
+            }),
+            [other] => {
+                return Err(
+                    format!("invalid rate limit proof flag in message bytes: {other}").into(),
+                )
+            }
+        };
+        if !reader.0.is_empty() {
+            return Err("trailing data after message bytes".into());
+        }
+
+        Ok(Self {
+            payload,
+            content_topic,
+            version,
+            timestamp,
+            meta,
+            ephemeral,
+            rate_limit_proof,
+            _extras: Default::default(),
+        })
+    }
+}
+
+/// Consumes the fields of [`WakuMessage::to_bytes`] one after the other
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err("truncated message bytes".into());
+        }
+        let (field, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(field)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self
+            .take(N)?
+            .try_into()
+            .expect("exactly N bytes were taken"))
+    }
+
+    fn take_prefixed(&mut self) -> Result<&'a [u8]> {
+        let len = u32::from_le_bytes(self.take_array()?);
+        self.take(len as usize)
+    }
 }
 
 impl WakuStoreRespMessage {
@@ -96,6 +520,159 @@ impl WakuStoreRespMessage {
     }
 }
 
+/// Payload decrypted by libwaku, along with the signature data of signed payloads.
+/// As per the [specification](https://rfc.vac.dev/spec/36/#decodedpayload-type)
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct DecodedPayload {
+    /// Public key of the signer, if the payload was signed
+    #[serde(with = "option_hex_serde::public_key", default, rename = "pubkey")]
+    pubkey: Option<PublicKey>,
+    /// Signature of the payload, if it was signed
+    #[serde(with = "option_hex_serde::signature", default)]
+    signature: Option<RecoverableSignature>,
+    /// Decrypted payload
+    #[serde(with = "base64_serde", rename = "data")]
+    payload: Vec<u8>,
+    /// Padding added to the payload before encryption
+    #[serde(with = "base64_serde", default = "Vec::new")]
+    padding: Vec<u8>,
+}
+
+impl DecodedPayload {
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn padding(&self) -> &[u8] {
+        &self.padding
+    }
+
+    pub fn pubkey(&self) -> Option<&PublicKey> {
+        self.pubkey.as_ref()
+    }
+
+    pub fn signature(&self) -> Option<&RecoverableSignature> {
+        self.signature.as_ref()
+    }
+
+    /// Payload decoded as an utf8 string
+    pub fn payload_utf8(&self) -> Result<&str> {
+        std::str::from_utf8(&self.payload)
+            .map_err(|e| format!("payload is not valid utf8: {e}").into())
+    }
+}
+
+impl waku_decode::WakuDecode for DecodedPayload {
+    fn decode(input: &str) -> Result<Self> {
+        serde_json::from_str(input)
+            .map_err(|e| format!("could not parse decoded payload: {e}").into())
+    }
+}
+
+/// Optional secp256k1 values as `0x` prefixed hex strings, as libwaku encodes them
+mod option_hex_serde {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    fn deserialize_bytes<'de, D>(deserializer: D) -> std::result::Result<Option<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let as_string: Option<String> = Option::<String>::deserialize(deserializer)?;
+        as_string
+            .filter(|s| !s.is_empty())
+            .map(|s| hex::decode(s.strip_prefix("0x").unwrap_or(&s)).map_err(D::Error::custom))
+            .transpose()
+    }
+
+    fn serialize_bytes<S>(
+        bytes: Option<Vec<u8>>,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match bytes {
+            Some(bytes) => serializer.serialize_str(&format!("0x{}", hex::encode(bytes))),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub mod public_key {
+        use secp256k1::PublicKey;
+        use serde::de::Error;
+        use serde::{Deserializer, Serializer};
+
+        pub fn serialize<S>(
+            key: &Option<PublicKey>,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            super::serialize_bytes(
+                key.map(|key| key.serialize_uncompressed().to_vec()),
+                serializer,
+            )
+        }
+
+        pub fn deserialize<'de, D>(
+            deserializer: D,
+        ) -> std::result::Result<Option<PublicKey>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize_bytes(deserializer)?
+                .map(|bytes| PublicKey::from_slice(&bytes).map_err(D::Error::custom))
+                .transpose()
+        }
+    }
+
+    /// 65 bytes `r || s || v` signatures, `v` being `0`/`1` or `27`/`28`
+    pub mod signature {
+        use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+        use serde::de::Error;
+        use serde::{Deserializer, Serializer};
+
+        pub fn serialize<S>(
+            signature: &Option<RecoverableSignature>,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let bytes = signature.map(|signature| {
+                let (recovery_id, compact) = signature.serialize_compact();
+                let mut bytes = compact.to_vec();
+                bytes.push(recovery_id.to_i32() as u8);
+                bytes
+            });
+            super::serialize_bytes(bytes, serializer)
+        }
+
+        pub fn deserialize<'de, D>(
+            deserializer: D,
+        ) -> std::result::Result<Option<RecoverableSignature>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize_bytes(deserializer)?
+                .map(|bytes| {
+                    let (compact, v) = match bytes.as_slice() {
+                        [compact @ .., v] if compact.len() == 64 => (compact, *v),
+                        _ => return Err(D::Error::custom("signature should be 65 bytes long")),
+                    };
+                    let recovery_id =
+                        RecoveryId::from_i32(i32::from(if v >= 27 { v - 27 } else { v }))
+                            .map_err(D::Error::custom)?;
+                    RecoverableSignature::from_compact(compact, recovery_id)
+                        .map_err(D::Error::custom)
+                })
+                .transpose()
+        }
+    }
+}
+
 mod base64_serde {
     use base64::Engine;
     use serde::de::Error;
@@ -119,15 +696,292 @@ mod base64_serde {
             .decode(base64_str)
             .map_err(D::Error::custom)
     }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StrOrBytes {
+        Str(String),
+        Bytes(Vec<u8>),
+    }
+
+    /// Either a base64 string or a plain byte array
+    pub fn deserialize_str_or_bytes<'de, D>(
+        deserializer: D,
+    ) -> std::result::Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match StrOrBytes::deserialize(deserializer)? {
+            StrOrBytes::Str(base64_str) => base64::engine::general_purpose::STANDARD
+                .decode(base64_str)
+                .map_err(D::Error::custom),
+            StrOrBytes::Bytes(bytes) => Ok(bytes),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::general::contenttopic::Encoding;
+
+    #[test]
+    fn rln_tagged_message_roundtrip() {
+        let json = r#"{
+            "payload": "SGkgZnJvbSDwn6aAIQ==",
+            "contentTopic": "/toychat/2/huilong/proto",
+            "version": 0,
+            "timestamp": 1719331735409120000,
+            "ephemeral": false,
+            "rateLimitProof": {
+                "proof": "dO16GcVWQ0qHAtquLnV2F1vyOj8k7btDbmr34rHlLfSOH6XNA1sw5dWBiTTbx0kf5E9KsV0ws6vLwB1E7fJfGIDWZyDnUSH+3HOOmEcEhGasjQViZAb+O0OLFpncv6N+JZmyFFMTB4Spdwp8cnmkFWRtSr8aYZTRw0DcxAPTeHE=",
+                "merkleRoot": "VOVMXXQcg7T6PiASUxuEy1vSO3CJ2kFg9GGkqG0pXY4=",
+                "epoch": "8vnx10jx9pX4wR3/2NRPXRgXkUrKs33/ne0qypnkExY=",
+                "shareX": "P5+/D9CIpnlrafsFAmCXAlRKhVHIgpKrp+mnDGjLRU0=",
+                "shareY": "3eoi4MsGr3NGFQUtI9OA+dGgojTEiL++kw/L6NdypS8=",
+                "nullifier": "tSVTcln/YnywXDIomIIRZ/OsLqGnnebXu2CEwlyT/p4=",
+                "rlnIdentifier": "scr3SxkfIMRCWZcjA81QZkQ7hB8jJjVcGYGDuuLS8Wc="
+            },
+            "unknownField": 42
+        }"#;
+
+        let message: WakuMessage = serde_json::from_str(json).unwrap();
+        let proof = message.rate_limit_proof.as_ref().unwrap();
+        assert_eq!(proof.proof.len(), 128);
+        assert_eq!(proof.merkle_root.len(), 32);
+        assert_eq!(proof.nullifier.len(), 32);
+        assert_eq!(&proof.epoch[..3], [0xf2, 0xf9, 0xf1]);
+        assert_eq!(message.payload(), "Hi from 🦀!".as_bytes());
+
+        let reserialized = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            reserialized["rateLimitProof"],
+            serde_json::from_str::<serde_json::Value>(json).unwrap()["rateLimitProof"]
+        );
+        assert_eq!(reserialized["unknownField"], 42);
+        assert_eq!(
+            serde_json::from_value::<WakuMessage>(reserialized).unwrap(),
+            message
+        );
+
+        let untagged = WakuMessage::new("Hi!", message.content_topic.clone(), 0, "", false);
+        assert!(serde_json::to_value(&untagged)
+            .unwrap()
+            .get("rateLimitProof")
+            .is_none());
+    }
+
+    #[test]
+    fn failures_classified() {
+        let failure = |msg: &str| WakuError::LibwakuFailure(msg.to_string());
+        let cases = [
+            (
+                "failed to dial: dial backoff for peer 16Uiu2HAm",
+                ErrorCategory::DialBackoff,
+            ),
+            (
+                "Failed to publish: NoPeersToPublish",
+                ErrorCategory::NoPeers,
+            ),
+            (
+                "lightpush error: no suitable remote peers",
+                ErrorCategory::NoPeers,
+            ),
+            (
+                "failed to negotiate protocol: protocols not supported",
+                ErrorCategory::ProtocolNotSupported,
+            ),
+            ("request rate limit exceeded", ErrorCategory::RateLimited),
+            (
+                "Message size exceeded maximum of 153600 bytes",
+                ErrorCategory::MessageTooLarge,
+            ),
+            ("store query timed out", ErrorCategory::Timeout),
+            (
+                "PEER_DIAL_FAILURE: /ip4/127.0.0.1/tcp/60099",
+                ErrorCategory::PeerUnreachable,
+            ),
+            ("invalid pubsub topic", ErrorCategory::Other),
+        ];
+        for (msg, category) in cases {
+            let err = failure(msg);
+            assert_eq!(err.classify(), category, "{msg}");
+            assert_eq!(err.to_string(), msg);
+        }
+
+        assert_eq!(
+            WakuError::StorePeerRejected {
+                status_code: 429,
+                status_desc: "TOO_MANY_REQUESTS".to_string()
+            }
+            .classify(),
+            ErrorCategory::RateLimited
+        );
+        assert_eq!(
+            WakuError::Timeout("waku error".to_string()).classify(),
+            ErrorCategory::Timeout
+        );
+        assert_eq!(WakuError::NodeDestroyed.classify(), ErrorCategory::Other);
+    }
+
+    #[test]
+    fn deserialize_decoded_payload() {
+        let key = secp256k1::SecretKey::from_slice(&[0x42; 32]).unwrap();
+        let pubkey = PublicKey::from_secret_key(&secp256k1::Secp256k1::new(), &key);
+        let digest = secp256k1::Message::from_slice(&[0x07; 32]).unwrap();
+        let signature = secp256k1::Secp256k1::new().sign_ecdsa_recoverable(&digest, &key);
+        let (recovery_id, compact) = signature.serialize_compact();
+
+        let response = format!(
+            "{{\"pubkey\":\"0x{}\",\"signature\":\"0x{}{:02x}\",\"data\":\"SGkgZnJvbSDwn6aAIQ==\",\"padding\":\"AAAA\"}}",
+            hex::encode(pubkey.serialize_uncompressed()),
+            hex::encode(compact),
+            recovery_id.to_i32() + 27
+        );
+        let decoded: DecodedPayload = serde_json::from_str(&response).unwrap();
+        assert_eq!(decoded.payload_utf8().unwrap(), "Hi from 🦀!");
+        assert_eq!(decoded.padding(), &[0, 0, 0]);
+        assert_eq!(decoded.pubkey(), Some(&pubkey));
+        assert_eq!(decoded.signature(), Some(&signature));
+
+        let roundtrip: DecodedPayload =
+            serde_json::from_str(&serde_json::to_string(&decoded).unwrap()).unwrap();
+        assert_eq!(roundtrip, decoded);
+
+        let unsigned: DecodedPayload = serde_json::from_str(
+            "{\"pubkey\":null,\"data\":\"SGkgZnJvbSDwn6aAIQ==\",\"padding\":\"\"}",
+        )
+        .unwrap();
+        assert!(unsigned.pubkey().is_none());
+        assert!(unsigned.signature().is_none());
+        assert!(unsigned.padding().is_empty());
+    }
 
     #[test]
     fn deserialize_waku_message() {
         let message = "{\"payload\":\"SGkgZnJvbSDwn6aAIQ==\",\"contentTopic\":\"/toychat/2/huilong/proto\",\"timestamp\":1665580926660,\"ephemeral\":true,\"meta\":\"SGkgZnJvbSDwn6aAIQ==\"}";
         let _: WakuMessage = serde_json::from_str(message).unwrap();
     }
+
+    #[test]
+    fn payload_hex_roundtrip() {
+        let bytes = vec![0x00, 0x01, 0xab, 0xff];
+        let content_topic = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        let message = WakuMessage::new(&bytes, content_topic.clone(), 0, Vec::new(), false);
+        assert_eq!(message.payload_hex(), "0001abff");
+
+        let decoded = WakuMessage::from_hex_payload(
+            &message.payload_hex(),
+            content_topic.clone(),
+            0,
+            Vec::new(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(decoded.payload(), bytes.as_slice());
+
+        let prefixed =
+            WakuMessage::from_hex_payload("0x0001abff", content_topic, 0, Vec::new(), false)
+                .unwrap();
+        assert_eq!(prefixed.payload(), bytes.as_slice());
+    }
+
+    #[test]
+    fn payload_base64_roundtrip() {
+        let bytes = "Hi from 🦀!".as_bytes().to_vec();
+        let content_topic = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        let message = WakuMessage::new(&bytes, content_topic.clone(), 0, Vec::new(), false);
+        assert_eq!(message.payload_base64(), "SGkgZnJvbSDwn6aAIQ==");
+
+        let decoded = WakuMessage::from_base64_payload(
+            &message.payload_base64(),
+            content_topic,
+            0,
+            Vec::new(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(decoded.payload(), bytes.as_slice());
+    }
+
+    #[test]
+    fn invalid_encoded_payloads() {
+        let content_topic = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        assert!(
+            WakuMessage::from_hex_payload("zz", content_topic.clone(), 0, Vec::new(), false)
+                .is_err()
+        );
+        assert!(
+            WakuMessage::from_base64_payload("!!", content_topic, 0, Vec::new(), false).is_err()
+        );
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let content_topic = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        let message = WakuMessage::new(
+            "Hi from 🦀!",
+            content_topic,
+            1,
+            [0xde, 0xad, 0xbe, 0xef],
+            true,
+        );
+
+        let bytes = message.to_bytes().unwrap();
+        assert_eq!(WakuMessage::from_bytes(&bytes).unwrap(), message);
+        assert!(bytes.len() < serde_json::to_vec(&message).unwrap().len());
+
+        assert!(WakuMessage::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(WakuMessage::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+
+        let mut unknown_format = bytes.clone();
+        unknown_format[0] = 2;
+        assert!(WakuMessage::from_bytes(&unknown_format).is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip_keeps_rate_limit_proof() {
+        let content_topic = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        let mut message = WakuMessage::new("Hi from 🦀!", content_topic, 0, Vec::new(), false);
+        message.rate_limit_proof = Some(RateLimitProof {
+            proof: vec![1; 128],
+            merkle_root: vec![2; 32],
+            epoch: vec![3; 32],
+            share_x: vec![4; 32],
+            share_y: vec![5; 32],
+            nullifier: vec![6; 32],
+            rln_identifier: vec![7; 32],
+        });
+
+        let bytes = message.to_bytes().unwrap();
+        assert_eq!(WakuMessage::from_bytes(&bytes).unwrap(), message);
+        assert!(WakuMessage::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn store_message_decoded_like_relayed_message() {
+        let relayed: WakuMessage = serde_json::from_str(
+            "{\"payload\":\"SGk=\",\"contentTopic\":\"/toychat/2/huilong/proto\",\"meta\":\"3q2+7w==\"}",
+        )
+        .unwrap();
+        let stored: WakuStoreRespMessage = serde_json::from_str(
+            "{\"payload\":\"SGk=\",\"contentTopic\":\"/toychat/2/huilong/proto\",\"meta\":\"3q2+7w==\",\"timestamp\":0,\"proof\":[]}",
+        )
+        .unwrap();
+        assert_eq!(stored.payload, b"Hi");
+        assert_eq!(stored.payload, relayed.payload);
+        assert_eq!(stored.meta, vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(stored.meta, relayed.meta);
+
+        let stored: WakuStoreRespMessage = serde_json::from_str(
+            "{\"payload\":[72,105],\"contentTopic\":\"/toychat/2/huilong/proto\",\"meta\":[222,173,190,239],\"timestamp\":0,\"proof\":[]}",
+        )
+        .unwrap();
+        assert_eq!(stored.payload, relayed.payload);
+        assert_eq!(stored.meta, relayed.meta);
+
+        let json = serde_json::to_value(&stored).unwrap();
+        assert_eq!(json["payload"], "SGk=");
+        assert_eq!(json["meta"], "3q2+7w==");
+    }
 }
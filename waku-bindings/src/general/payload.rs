@@ -0,0 +1,135 @@
+//! Payload decoding driven by the content topic
+//!
+//! Apps usually know, per content topic, whether payloads are text, json or opaque bytes.
+//! A [`PayloadRegistry`] records it once so received payloads are decoded in one place.
+
+// std
+use std::collections::HashMap;
+// internal
+use crate::general::contenttopic::WakuContentTopic;
+use crate::general::{Result, WakuMessage};
+
+/// How the payloads of a content topic are encoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PayloadKind {
+    /// UTF-8 text
+    Utf8,
+    /// Opaque bytes, kept as they are
+    #[default]
+    Binary,
+    /// UTF-8 encoded json document
+    Json,
+}
+
+/// Payload decoded as per its [`PayloadKind`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypedPayload {
+    Utf8(String),
+    Binary(Vec<u8>),
+    Json(serde_json::Value),
+}
+
+/// [`PayloadKind`] of each known content topic. Payloads of unregistered topics are binary
+#[derive(Clone, Debug, Default)]
+pub struct PayloadRegistry {
+    kinds: HashMap<WakuContentTopic, PayloadKind>,
+}
+
+impl PayloadRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the payload kind of `content_topic`, replacing any previous one
+    pub fn with_kind(mut self, content_topic: WakuContentTopic, kind: PayloadKind) -> Self {
+        self.register(content_topic, kind);
+        self
+    }
+
+    /// Register the payload kind of `content_topic`, replacing any previous one
+    pub fn register(&mut self, content_topic: WakuContentTopic, kind: PayloadKind) {
+        self.kinds.insert(content_topic, kind);
+    }
+
+    /// Payload kind of `content_topic`, [`PayloadKind::Binary`] if unregistered
+    pub fn kind(&self, content_topic: &WakuContentTopic) -> PayloadKind {
+        self.kinds.get(content_topic).copied().unwrap_or_default()
+    }
+
+    /// Decode the payload of `message` as per the kind of its content topic.
+    /// Fails if a text or json payload isn't valid
+    pub fn decode(&self, message: &WakuMessage) -> Result<TypedPayload> {
+        let payload = message.payload();
+        match self.kind(&message.content_topic) {
+            PayloadKind::Binary => Ok(TypedPayload::Binary(payload.to_vec())),
+            PayloadKind::Utf8 => String::from_utf8(payload.to_vec())
+                .map(TypedPayload::Utf8)
+                .map_err(|e| {
+                    format!("invalid utf-8 payload on {}: {e}", message.content_topic).into()
+                }),
+            PayloadKind::Json => serde_json::from_slice(payload)
+                .map(TypedPayload::Json)
+                .map_err(|e| {
+                    format!("invalid json payload on {}: {e}", message.content_topic).into()
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PayloadKind, PayloadRegistry, TypedPayload};
+    use crate::general::contenttopic::{Encoding, WakuContentTopic};
+    use crate::general::WakuMessage;
+
+    const TEXT: WakuContentTopic = WakuContentTopic::new("toychat", "2", "text", Encoding::Proto);
+    const JSON: WakuContentTopic = WakuContentTopic::new("toychat", "2", "json", Encoding::Proto);
+    const BLOB: WakuContentTopic = WakuContentTopic::new("toychat", "2", "blob", Encoding::Proto);
+
+    fn registry() -> PayloadRegistry {
+        PayloadRegistry::new()
+            .with_kind(TEXT, PayloadKind::Utf8)
+            .with_kind(JSON, PayloadKind::Json)
+    }
+
+    fn message(payload: &[u8], content_topic: WakuContentTopic) -> WakuMessage {
+        WakuMessage::new(payload, content_topic, 0, Vec::new(), false)
+    }
+
+    #[test]
+    fn utf8_payload() {
+        let decoded = registry().decode(&message("Hi from 🦀!".as_bytes(), TEXT));
+        assert_eq!(
+            decoded.unwrap(),
+            TypedPayload::Utf8("Hi from 🦀!".to_string())
+        );
+
+        assert!(registry().decode(&message(&[0xff, 0xfe], TEXT)).is_err());
+    }
+
+    #[test]
+    fn json_payload() {
+        let decoded = registry().decode(&message(br#"{"move":[1,2]}"#, JSON));
+        assert_eq!(
+            decoded.unwrap(),
+            TypedPayload::Json(serde_json::json!({"move": [1, 2]}))
+        );
+
+        assert!(registry().decode(&message(b"{\"move\"", JSON)).is_err());
+    }
+
+    #[test]
+    fn binary_payload() {
+        let registry = registry().with_kind(BLOB, PayloadKind::Binary);
+        let decoded = registry.decode(&message(&[0xff, 0xfe], BLOB));
+        assert_eq!(decoded.unwrap(), TypedPayload::Binary(vec![0xff, 0xfe]));
+    }
+
+    #[test]
+    fn unregistered_topic_is_binary() {
+        let other = WakuContentTopic::new("toychat", "2", "other", Encoding::Proto);
+        assert_eq!(registry().kind(&other), PayloadKind::Binary);
+        let decoded = registry().decode(&message(b"Hi!", other));
+        assert_eq!(decoded.unwrap(), TypedPayload::Binary(b"Hi!".to_vec()));
+    }
+}
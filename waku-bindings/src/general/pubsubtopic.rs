@@ -1,4 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Prefix of the pubsub topics used by static sharding, `/waku/2/rs/{cluster_id}/{shard}`
+const STATIC_SHARDING_PREFIX: &str = "/waku/2/rs/";
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -9,6 +13,68 @@ impl PubsubTopic {
     pub fn new(value: &str) -> Self {
         PubsubTopic(value.to_string())
     }
+
+    /// Static sharding topic of `shard` in `cluster_id`, `/waku/2/rs/{cluster_id}/{shard}`
+    pub fn from_shard(cluster_id: u16, shard: u16) -> Self {
+        PubsubTopic(format!("{STATIC_SHARDING_PREFIX}{cluster_id}/{shard}"))
+    }
+
+    /// Named topic `/{namespace}/1/{name}` of an application not using sharding, `1` being the
+    /// version of this naming convention. Both parts are normalized so that every participant
+    /// derives the same topic: surrounding whitespace is trimmed, letters are lowercased and any
+    /// character other than an ASCII letter, a digit, `-`, `_` or `.` becomes `-`.
+    /// i.e. `app_named("MyOrg", "My Chat")` is `/myorg/1/my-chat`.
+    /// Fails if either part is empty once normalized
+    pub fn app_named(namespace: &str, name: &str) -> Result<Self, String> {
+        let normalize = |part: &str, what: &str| {
+            let normalized: String = part
+                .trim()
+                .chars()
+                .map(|c| match c.to_ascii_lowercase() {
+                    c @ ('a'..='z' | '0'..='9' | '-' | '_' | '.') => c,
+                    _ => '-',
+                })
+                .collect();
+            let normalized = normalized.trim_matches('-');
+            if normalized.is_empty() {
+                Err(format!("pubsub topic {what} can't be empty, got `{part}`"))
+            } else {
+                Ok(normalized.to_string())
+            }
+        };
+        Ok(PubsubTopic(format!(
+            "/{}/1/{}",
+            normalize(namespace, "namespace")?,
+            normalize(name, "name")?
+        )))
+    }
+
+    /// `(cluster_id, shard)` if this is a static sharding topic, `None` for named topics
+    pub fn shard(&self) -> Option<(u16, u16)> {
+        let (cluster_id, shard) = self
+            .0
+            .strip_prefix(STATIC_SHARDING_PREFIX)?
+            .split_once('/')?;
+        Some((cluster_id.parse().ok()?, shard.parse().ok()?))
+    }
+}
+
+impl FromStr for PubsubTopic {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("pubsub topic can't be empty".to_string());
+        }
+
+        let topic = PubsubTopic::new(s);
+        if s.starts_with(STATIC_SHARDING_PREFIX) && topic.shard().is_none() {
+            return Err(format!(
+                "Wrong static sharding pubsub topic format. Should be `/waku/2/rs/{{cluster-id}}/{{shard}}`. Got: {s}"
+            ));
+        }
+        Ok(topic)
+    }
 }
 
 // to allow conversion from `PubsubTopic` to `String`
@@ -17,3 +83,48 @@ impl From<&PubsubTopic> for String {
         topic.0.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PubsubTopic;
+
+    #[test]
+    fn parse_pubsub_topics() {
+        let sharded: PubsubTopic = "/waku/2/rs/16/32".parse().unwrap();
+        assert_eq!(sharded.shard(), Some((16, 32)));
+
+        let named: PubsubTopic = "/waku/2/default-waku/proto".parse().unwrap();
+        assert_eq!(named.shard(), None);
+
+        assert!("".parse::<PubsubTopic>().is_err());
+        assert!("/waku/2/rs/16".parse::<PubsubTopic>().is_err());
+        assert!("/waku/2/rs/16/shard".parse::<PubsubTopic>().is_err());
+    }
+
+    #[test]
+    fn app_named_topics() {
+        let topic = PubsubTopic::app_named("myorg", "mychat").unwrap();
+        assert_eq!(topic, PubsubTopic::new("/myorg/1/mychat"));
+        assert_eq!(topic.shard(), None);
+
+        // Two participants spelling the same inputs differently agree on the topic
+        assert_eq!(
+            PubsubTopic::app_named(" MyOrg", "My Chat").unwrap(),
+            PubsubTopic::app_named("myorg ", "my chat").unwrap()
+        );
+        assert_eq!(
+            PubsubTopic::app_named("MyOrg", "My Chat/Lobby").unwrap(),
+            PubsubTopic::new("/myorg/1/my-chat-lobby")
+        );
+
+        assert!(PubsubTopic::app_named("", "mychat").is_err());
+        assert!(PubsubTopic::app_named("myorg", " / ").is_err());
+    }
+
+    #[test]
+    fn pubsub_topic_from_shard() {
+        let topic = PubsubTopic::from_shard(16, 32);
+        assert_eq!(topic, PubsubTopic::new("/waku/2/rs/16/32"));
+        assert_eq!(topic.shard(), Some((16, 32)));
+    }
+}
@@ -0,0 +1,144 @@
+//! Message authorship through secp256k1 signatures
+//!
+//! libwaku doesn't sign messages on publish, so signatures are attached by the bindings.
+//! The payload of a signed message is the original payload followed by a 65 bytes recoverable
+//! signature (`r || s || recovery_id`) over `sha256(content_topic || payload)`.
+//! The message version is left untouched, version `1` being taken by
+//! [26/WAKU-PAYLOAD](https://rfc.vac.dev/spec/26/) encryption, so nothing but a successful
+//! verification tells a signed payload from an unsigned one.
+
+// crates
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256};
+// internal
+use crate::general::{Result, WakuMessage};
+
+/// Length of the signature appended to the payload
+pub const SIGNATURE_LEN: usize = 65;
+
+fn signing_digest(content_topic: &str, payload: &[u8]) -> Message {
+    let digest = Sha256::new()
+        .chain_update(content_topic.as_bytes())
+        .chain_update(payload)
+        .finalize();
+    Message::from_slice(&digest).expect("sha256 digest is 32 bytes")
+}
+
+/// Sign `message` with `key`, returning a copy whose payload carries the signature
+pub fn sign_message(message: &WakuMessage, key: &SecretKey) -> WakuMessage {
+    let digest = signing_digest(&message.content_topic.to_string(), &message.payload);
+    let (recovery_id, signature) = Secp256k1::signing_only()
+        .sign_ecdsa_recoverable(&digest, key)
+        .serialize_compact();
+
+    let mut signed = message.clone();
+    signed.payload.extend_from_slice(&signature);
+    signed.payload.push(recovery_id.to_i32() as u8);
+    signed
+}
+
+/// Split a signed message into its original payload and the public key of its signer.
+/// The key recovered from an unsigned payload is meaningless, so it must be compared to an
+/// expected signer
+pub fn recover_signer(message: &WakuMessage) -> Result<(Vec<u8>, PublicKey)> {
+    let payload_len = message
+        .payload
+        .len()
+        .checked_sub(SIGNATURE_LEN)
+        .ok_or_else(|| "payload too short to contain a signature".to_string())?;
+    let (payload, signature) = message.payload.split_at(payload_len);
+
+    let recovery_id = RecoveryId::from_i32(signature[SIGNATURE_LEN - 1] as i32)
+        .map_err(|e| format!("invalid recovery id: {e}"))?;
+    let signature =
+        RecoverableSignature::from_compact(&signature[..SIGNATURE_LEN - 1], recovery_id)
+            .map_err(|e| format!("invalid signature: {e}"))?;
+
+    let digest = signing_digest(&message.content_topic.to_string(), payload);
+    let public_key = Secp256k1::verification_only()
+        .recover_ecdsa(&digest, &signature)
+        .map_err(|e| format!("could not recover signer: {e}"))?;
+
+    Ok((payload.to_vec(), public_key))
+}
+
+impl WakuMessage {
+    /// Sign the payload in place with `key`, using the envelope described in the
+    /// [module documentation](self). The message version is left untouched
+    pub fn sign_payload(&mut self, key: &SecretKey) {
+        *self = sign_message(self, key);
+    }
+
+    /// Whether the message carries a valid signature by `pubkey` over its payload and content topic
+    pub fn verify_payload(&self, pubkey: &PublicKey) -> bool {
+        recover_signer(self).is_ok_and(|(_, signer)| signer == *pubkey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::contenttopic::{Encoding, WakuContentTopic};
+    use std::str::FromStr;
+
+    fn key() -> SecretKey {
+        SecretKey::from_str("05f381866cc21f6c1e2e80e07fa732008e36d942dce3206ad6dcd6793c98d609")
+            .unwrap()
+    }
+
+    #[test]
+    fn sign_and_recover() {
+        let content_topic = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        let message = WakuMessage::new("Hi from 🦀!", content_topic, 0, Vec::new(), false);
+
+        let signed = sign_message(&message, &key());
+        assert_eq!(signed.version, message.version);
+        assert_eq!(signed.payload.len(), message.payload.len() + SIGNATURE_LEN);
+
+        let (payload, signer) = recover_signer(&signed).unwrap();
+        assert_eq!(payload, message.payload);
+        assert_eq!(signer, key().public_key(&Secp256k1::signing_only()));
+    }
+
+    #[test]
+    fn tampered_message_recovers_other_signer() {
+        let content_topic = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        let message = WakuMessage::new("Hi from 🦀!", content_topic, 0, Vec::new(), false);
+
+        let mut signed = sign_message(&message, &key());
+        signed.payload[0] ^= 0xff;
+
+        let signer = recover_signer(&signed).map(|(_, signer)| signer);
+        assert_ne!(signer, Ok(key().public_key(&Secp256k1::signing_only())));
+    }
+
+    #[test]
+    fn sign_and_verify_payload() {
+        let content_topic = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        let mut message = WakuMessage::new("Hi from 🦀!", content_topic, 0, Vec::new(), false);
+        let pubkey = key().public_key(&Secp256k1::signing_only());
+        assert!(!message.verify_payload(&pubkey));
+
+        message.sign_payload(&key());
+        assert!(message.verify_payload(&pubkey));
+
+        let other_key = SecretKey::from_slice(&[0x42; 32]).unwrap();
+        assert!(!message.verify_payload(&other_key.public_key(&Secp256k1::signing_only())));
+
+        let mut tampered = message.clone();
+        tampered.payload[0] ^= 0xff;
+        assert!(!tampered.verify_payload(&pubkey));
+
+        let mut moved = message.clone();
+        moved.content_topic = WakuContentTopic::new("toychat", "2", "other", Encoding::Proto);
+        assert!(!moved.verify_payload(&pubkey));
+    }
+
+    #[test]
+    fn short_payload_is_rejected() {
+        let content_topic = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        let message = WakuMessage::new("Hi from 🦀!", content_topic, 0, Vec::new(), false);
+        assert!(recover_signer(&message).is_err());
+    }
+}
@@ -1,7 +1,106 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fmt::{Display, Formatter};
+use std::ops::Sub;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
 
 pub fn get_now_in_nanosecs() -> u64 {
-    let now = SystemTime::now();
-    let since_epoch = now.duration_since(UNIX_EPOCH).expect("Time went backwards");
-    since_epoch.as_secs() * 1_000_000_000 + since_epoch.subsec_nanos() as u64
+    Timestamp::now().as_nanos()
+}
+
+/// Unix timestamp, stored in nanoseconds as Waku messages and store queries expect.
+/// Build it with the constructor matching the unit at hand instead of converting by hand
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    /// Current time
+    pub fn now() -> Self {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards");
+        Self::from_duration(since_epoch)
+    }
+
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Self(nanos)
+    }
+
+    /// Saturates at [`u64::MAX`] nanoseconds
+    pub const fn from_millis(millis: u64) -> Self {
+        Self(millis.saturating_mul(1_000_000))
+    }
+
+    /// Saturates at [`u64::MAX`] nanoseconds
+    pub const fn from_secs(secs: u64) -> Self {
+        Self(secs.saturating_mul(1_000_000_000))
+    }
+
+    /// Timestamp `duration` after the unix epoch, saturating at [`u64::MAX`] nanoseconds
+    pub fn from_duration(duration: Duration) -> Self {
+        Self(duration.as_nanos().try_into().unwrap_or(u64::MAX))
+    }
+
+    pub const fn as_nanos(&self) -> u64 {
+        self.0
+    }
+
+    pub const fn as_millis(&self) -> u64 {
+        self.0 / 1_000_000
+    }
+}
+
+impl Sub<Duration> for Timestamp {
+    type Output = Self;
+
+    /// Saturates at the unix epoch
+    fn sub(self, duration: Duration) -> Self {
+        Self(self.0.saturating_sub(Self::from_duration(duration).0))
+    }
+}
+
+impl From<Timestamp> for u64 {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.as_nanos()
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Timestamp;
+    use std::time::Duration;
+
+    #[test]
+    fn units_normalize_to_nanos() {
+        let x = 1_665_580_926_660;
+        assert_eq!(
+            Timestamp::from_millis(x),
+            Timestamp::from_nanos(x * 1_000_000)
+        );
+        assert_eq!(Timestamp::from_secs(2), Timestamp::from_millis(2_000));
+        assert_eq!(
+            Timestamp::from_duration(Duration::from_millis(x)),
+            Timestamp::from_millis(x)
+        );
+        assert_eq!(Timestamp::from_millis(x).as_millis(), x);
+    }
+
+    #[test]
+    fn sub_saturates_at_epoch() {
+        let timestamp = Timestamp::from_secs(10);
+        assert_eq!(timestamp - Duration::from_secs(4), Timestamp::from_secs(6));
+        assert_eq!(
+            timestamp - Duration::from_secs(20),
+            Timestamp::from_nanos(0)
+        );
+    }
 }
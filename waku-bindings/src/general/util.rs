@@ -0,0 +1,74 @@
+//! Helpers to build the values the node API takes
+
+// std
+use std::net::SocketAddr;
+// crates
+use multiaddr::{Multiaddr, Protocol};
+// internal
+use crate::general::{Result, WakuError};
+
+/// Multihash codes of the libp2p peer ids: identity for inlined keys, sha2-256 for hashed ones
+const PEER_ID_MULTIHASH_CODES: [u64; 2] = [0x00, 0x12];
+
+/// Dialable address `/ip4/{ip}/tcp/{port}/p2p/{peer_id}` (or `/ip6/...`) of the peer with id
+/// `peer_id` listening at `addr`, as taken by
+/// [`connect`](crate::WakuNodeHandle::connect).
+/// Fails if `peer_id` isn't a base58 encoded libp2p peer id
+pub fn multiaddr_from_socketaddr(addr: SocketAddr, peer_id: &str) -> Result<Multiaddr> {
+    let invalid = |reason: String| WakuError::Parse(format!("invalid peer id {peer_id}: {reason}"));
+
+    let peer_id = match format!("/p2p/{peer_id}")
+        .parse::<Multiaddr>()
+        .map_err(|e| invalid(e.to_string()))?
+        .pop()
+    {
+        Some(Protocol::P2p(peer_id)) => peer_id,
+        _ => return Err(invalid("not a peer id".to_string())),
+    };
+    if !PEER_ID_MULTIHASH_CODES.contains(&peer_id.code()) {
+        return Err(invalid(format!(
+            "unexpected multihash code {:#x}",
+            peer_id.code()
+        )));
+    }
+
+    Ok(Multiaddr::from(addr.ip())
+        .with(Protocol::Tcp(addr.port()))
+        .with(Protocol::P2p(peer_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::multiaddr_from_socketaddr;
+
+    const PEER_ID: &str = "16Uiu2HAm5hPxT7s3TA2f4b4YjAPzS7yLRSgdj87ysDZ6NAXMiRuY";
+
+    #[test]
+    fn ipv4_socket_address() {
+        let address = multiaddr_from_socketaddr("10.0.0.7:60000".parse().unwrap(), PEER_ID);
+        assert_eq!(
+            address.unwrap().to_string(),
+            format!("/ip4/10.0.0.7/tcp/60000/p2p/{PEER_ID}")
+        );
+    }
+
+    #[test]
+    fn ipv6_socket_address() {
+        let address = multiaddr_from_socketaddr("[::1]:60000".parse().unwrap(), PEER_ID);
+        assert_eq!(
+            address.unwrap().to_string(),
+            format!("/ip6/::1/tcp/60000/p2p/{PEER_ID}")
+        );
+    }
+
+    #[test]
+    fn invalid_peer_ids() {
+        let addr = "10.0.0.7:60000".parse().unwrap();
+        assert!(multiaddr_from_socketaddr(addr, "").is_err());
+        assert!(multiaddr_from_socketaddr(addr, "not-a-peer-id").is_err());
+        assert!(
+            multiaddr_from_socketaddr(addr, "16Uiu2HAm5hPxT7s3TA2f4b4YjAPzS7yLRSgdj87").is_err()
+        );
+        assert!(multiaddr_from_socketaddr(addr, &format!("{PEER_ID}/tcp/1")).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::general::Result;
+use crate::general::{Result, WakuError};
 use multiaddr::Multiaddr;
 // Define the WakuDecode trait
 pub trait WakuDecode: Sized {
@@ -19,8 +19,28 @@ impl WakuDecode for Vec<Multiaddr> {
     fn decode(input: &str) -> Result<Self> {
         input
             .split(',')
-            .map(|s| s.trim().parse::<Multiaddr>().map_err(|err| err.to_string()))
-            .collect::<Result<Vec<Multiaddr>>>() // Collect results into a Vec
-            .map_err(|err| format!("could not parse Multiaddr: {}", err))
+            .map(|s| s.trim().parse::<Multiaddr>())
+            .collect::<std::result::Result<Vec<Multiaddr>, _>>() // Collect results into a Vec
+            .map_err(|err| WakuError::Decode(format!("could not parse Multiaddr: {}", err)))
+    }
+}
+
+impl WakuDecode for Vec<String> {
+    fn decode(input: &str) -> Result<Self> {
+        Ok(input
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+impl WakuDecode for usize {
+    fn decode(input: &str) -> Result<Self> {
+        input
+            .trim()
+            .parse()
+            .map_err(|err| WakuError::Decode(format!("could not parse number {input}: {err}")))
     }
 }
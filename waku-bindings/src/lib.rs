@@ -4,6 +4,8 @@
 pub mod general;
 mod macros;
 pub mod node;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 // Re-export the LibwakuResponse type to make it accessible outside this module
 pub use general::libwaku_response::LibwakuResponse;
@@ -15,9 +17,19 @@ pub use general::libwaku_response::LibwakuResponse;
 use rln;
 
 pub use node::{
-    waku_create_content_topic, waku_new, Initialized, Key, Multiaddr, PublicKey, RLNConfig,
-    Running, SecretKey, WakuEvent, WakuMessageEvent, WakuNodeConfig, WakuNodeHandle,
+    waku_create_content_topic, waku_new, ConnectionChangeEvent, Fleet, Initialized, Key, Multiaddr,
+    NodeHealth, NwakuVersion, PeerConnectionEvent, PublicKey, RLNConfig, RLNConfigBuilder, Running,
+    SecretKey, TopicHealth, WakuEvent, WakuMessageEvent, WakuNodeConfig, WakuNodeHandle,
 };
 
-pub use general::contenttopic::{Encoding, WakuContentTopic};
-pub use general::{messagehash::MessageHash, Result, WakuMessage, WakuMessageVersion};
+pub use general::contenttopic::{
+    Encoding, WakuContentTopic, WAKU_NETWORK_CLUSTER_ID, WAKU_NETWORK_GENERATION,
+    WAKU_NETWORK_SHARD_COUNT,
+};
+pub use general::payload::{PayloadKind, PayloadRegistry, TypedPayload};
+pub use general::time::Timestamp;
+pub use general::util::multiaddr_from_socketaddr;
+pub use general::{
+    messagehash::MessageHash, DecodedPayload, ErrorCategory, RateLimitProof, Result, WakuError,
+    WakuMessage, WakuMessageVersion,
+};
@@ -14,14 +14,18 @@ unsafe extern "C" fn trampoline<F>(
     let closure = &mut *(user_data as *mut F);
 
     let response = if data.is_null() {
-        ""
+        Ok("")
     } else {
         str::from_utf8(slice::from_raw_parts(data as *mut u8, data_len))
-            .expect("could not retrieve response")
     };
 
-    let result = LibwakuResponse::try_from((ret_code as u32, response))
-        .expect("invalid response obtained from libwaku");
+    // A malformed response must not unwind across the FFI boundary, so it is
+    // reported to the closure as a failure instead
+    let result = match response {
+        Ok(response) => LibwakuResponse::try_from((ret_code as u32, response))
+            .unwrap_or_else(LibwakuResponse::Failure),
+        Err(e) => LibwakuResponse::Failure(format!("invalid utf-8 in libwaku response: {e}")),
+    };
 
     closure(result);
 }
@@ -35,13 +39,15 @@ where
 
 #[macro_export]
 macro_rules! handle_ffi_call {
-    // Case: With or without additional arguments
-    ($waku_fn:expr, $resp_hndlr:expr, $ctx:expr $(, $($arg:expr),*)?) => {{
+    // Case: With or without additional arguments.
+    // `$node` is a guard keeping the node pointer valid until the response is received
+    ($waku_fn:expr, $resp_hndlr:expr, $node:expr $(, $($arg:expr),*)?) => {{
         use $crate::macros::get_trampoline;
         use std::sync::Arc;
         use tokio::sync::Notify;
         use libc::*;
 
+        let node = $node;
         let mut result = LibwakuResponse::default();
         let notify = Arc::new(Notify::new());
         let notify_clone = notify.clone();
@@ -56,18 +62,69 @@ macro_rules! handle_ffi_call {
         let code = unsafe {
             let mut closure = result_cb;
             let cb = get_trampoline(&closure);
-            $waku_fn(
-                $ctx,           // Pass the context
+            let ctx_ptr = node.ptr();
+            let code = $waku_fn(
+                ctx_ptr,        // Pass the context
                 $($($arg),*,)?  // Expand the variadic arguments if provided
                 cb,             // Pass the callback trampoline
                 &mut closure as *mut _ as *mut c_void
-            )
+            );
+            code
         };
 
-        // Wait for the callback to notify us
-        notify.notified().await;
+        // Wait for the callback to notify us, under the node's stall watchdog
+        node.await_response(notify.notified()).await;
+
+        // libwaku is done with the pointer once it called back
+        drop(node);
 
         // Handle the response
         $resp_hndlr(code, result)
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::raw::c_void;
+    use waku_sys::RET_OK;
+
+    fn call_trampoline(ret_code: u32, data: &[u8]) -> LibwakuResponse {
+        let mut result = LibwakuResponse::default();
+        let mut closure = |r: LibwakuResponse| result = r;
+        let cb = get_trampoline(&closure).unwrap();
+        unsafe {
+            cb(
+                ret_code as i32,
+                data.as_ptr() as *const _,
+                data.len(),
+                &mut closure as *mut _ as *mut c_void,
+            )
+        };
+        result
+    }
+
+    #[test]
+    fn trampoline_handles_invalid_utf8() {
+        let result = call_trampoline(RET_OK, &[0x66, 0x6f, 0xff, 0xfe]);
+        assert!(matches!(result, LibwakuResponse::Failure(e) if e.contains("invalid utf-8")));
+    }
+
+    #[test]
+    fn trampoline_handles_unknown_return_code() {
+        let result = call_trampoline(42, b"ok");
+        assert_eq!(
+            result,
+            LibwakuResponse::Failure("undefined return code 42".to_string())
+        );
+    }
+
+    #[test]
+    fn trampoline_forwards_valid_response() {
+        let result = call_trampoline(RET_OK, "Hi from 🦀!".as_bytes());
+        assert_eq!(
+            result,
+            LibwakuResponse::Success(Some("Hi from 🦀!".to_string()))
+        );
+    }
+}
@@ -1,22 +1,513 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ffi::c_void;
+use std::future::Future;
+use std::ops::ControlFlow;
 use std::ptr::null_mut;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc as std_mpsc;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
+use multiaddr::Multiaddr;
+use secp256k1::SecretKey;
+use tokio::sync::{watch, Notify};
+
+use crate::general::contenttopic::WakuContentTopic;
 use crate::general::libwaku_response::LibwakuResponse;
+use crate::general::messagehash::MessageHash;
+use crate::general::pubsubtopic::PubsubTopic;
+use crate::general::{Result, WakuError, WakuMessage};
 use crate::macros::get_trampoline;
+use crate::node::discovery::{DiscoveryMechanism, DiscoveryStatus};
+use crate::node::dns::{enr_peer_id, Enr};
+use crate::node::event_stream::EventStream;
+use crate::node::events::{NodeStalledEvent, TopicHealth, WakuEvent};
+use crate::node::peers::{self, PeerGate, Protocol};
+use crate::node::watchdog::Watchdog;
 
 type LibwakuResponseClosure = dyn FnMut(LibwakuResponse) + Send + Sync;
+type EventListener = dyn FnMut(&WakuEvent) -> ControlFlow<()> + Send;
+
+/// Largest number of events kept while the event callback is paused, older ones are dropped
+pub const PAUSED_EVENTS_CAPACITY: usize = 1024;
+
+/// Number of the latest message hashes remembered as seen by the node
+pub const SEEN_MESSAGES_CAPACITY: usize = 10_000;
+
+/// Messages and payload bytes published with a content topic
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TopicPublishStats {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+/// Messages published by the node through relay and lightpush since its creation,
+/// by content topic. Only successful publications are counted
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PublishStats {
+    pub by_content_topic: HashMap<WakuContentTopic, TopicPublishStats>,
+}
+
+impl PublishStats {
+    /// Sum over every content topic
+    pub fn total(&self) -> TopicPublishStats {
+        self.by_content_topic
+            .values()
+            .fold(TopicPublishStats::default(), |total, stats| {
+                TopicPublishStats {
+                    messages: total.messages + stats.messages,
+                    bytes: total.bytes + stats.bytes,
+                }
+            })
+    }
+
+    /// Count `message` as published
+    fn record(&mut self, message: &WakuMessage) {
+        let stats = self
+            .by_content_topic
+            .entry(message.content_topic.clone())
+            .or_default();
+        stats.messages += 1;
+        stats.bytes += message.payload.len() as u64;
+    }
+}
+
+/// Identifier of a listener registered with [`WakuNodeContext::add_event_listener`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ListenerId(u64);
+
+/// Counts the messages of each peer over one second windows
+struct PeerRateLimiter {
+    max_per_sec: u32,
+    /// Start of the current window and messages accepted within it, by peer id
+    windows: HashMap<String, (Instant, u32)>,
+}
+
+impl PeerRateLimiter {
+    fn new(max_per_sec: u32) -> Self {
+        Self {
+            max_per_sec,
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Whether a message received from `peer_id` at `now` is within the rate
+    fn allow(&mut self, peer_id: &str, now: Instant) -> bool {
+        const WINDOW: Duration = Duration::from_secs(1);
+
+        if self.windows.len() > 1024 {
+            self.windows
+                .retain(|_, (start, _)| now.duration_since(*start) < WINDOW);
+        }
+        let (start, count) = self.windows.entry(peer_id.to_string()).or_insert((now, 0));
+        if now.duration_since(*start) >= WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= self.max_per_sec {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+/// Hashes of the latest messages, the oldest ones being forgotten beyond
+/// [`SEEN_MESSAGES_CAPACITY`]
+#[derive(Default)]
+struct SeenMessages {
+    order: VecDeque<MessageHash>,
+    hashes: HashSet<MessageHash>,
+}
+
+impl SeenMessages {
+    fn insert(&mut self, hash: &MessageHash) {
+        if !self.hashes.insert(hash.clone()) {
+            return;
+        }
+        self.order.push_back(hash.clone());
+        if self.order.len() > SEEN_MESSAGES_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+    }
+
+    fn contains(&self, hash: &MessageHash) -> bool {
+        self.hashes.contains(hash)
+    }
+}
+
+/// Hooks run on every event before the user callback.
+/// Shared with the closure registered in libwaku, so they keep working
+/// whenever the user callback is replaced.
+#[derive(Default)]
+struct EventHooks {
+    peer_gate: Mutex<Option<PeerGate>>,
+    listeners: Mutex<Vec<(ListenerId, Box<EventListener>)>>,
+    next_listener_id: AtomicU64,
+    /// Last health reported for each relay topic
+    topic_health: Mutex<HashMap<PubsubTopic, TopicHealth>>,
+    /// Messages with a larger payload are dropped before reaching the listeners and the user callback
+    max_payload: Mutex<Option<usize>>,
+    /// Messages of a peer beyond its rate are dropped before reaching the listeners and the user callback
+    rate_limiter: Mutex<Option<PeerRateLimiter>>,
+    rate_limited: AtomicU64,
+    /// Messages received or published lately
+    seen: Mutex<SeenMessages>,
+    /// User callback, run after the hooks
+    callback: Mutex<Option<Box<LibwakuResponseClosure>>>,
+    /// Events withheld from the user callback while it is paused
+    paused: Mutex<Option<VecDeque<LibwakuResponse>>>,
+    dropped_while_paused: AtomicU64,
+    watchdog: Watchdog,
+    /// Peers rejected by the peer gate, to be disconnected by the [`disconnector`] thread
+    disconnects: Mutex<Option<std_mpsc::Sender<String>>>,
+}
+
+/// Disconnect the peers received through `rejected` until the node is destroyed.
+/// Events are delivered on the libwaku thread, which can't serve a request issued from
+/// itself, so peers are disconnected from this thread instead. It is owned by the context,
+/// which stops it before destroying the node
+fn disconnector(node: Arc<NodePtr>, rejected: std_mpsc::Receiver<String>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        for peer_id in rejected {
+            let Ok(guard) = node.guard() else {
+                // The node is being destroyed
+                break;
+            };
+            if let Err(e) =
+                futures::executor::block_on(peers::waku_disconnect_peer_by_id(guard, &peer_id))
+            {
+                log::warn!("could not disconnect disallowed peer {peer_id}: {e}");
+            }
+        }
+    })
+}
+
+impl EventHooks {
+    /// Run the hooks on `response`, returning whether it should reach the user callback
+    fn dispatch(&self, response: &LibwakuResponse) -> bool {
+        let LibwakuResponse::Success(Some(event)) = response else {
+            return true;
+        };
+
+        let Ok(event) = serde_json::from_str::<WakuEvent>(event) else {
+            return true;
+        };
+
+        if let WakuEvent::WakuMessage(evt) = &event {
+            self.seen
+                .lock()
+                .expect("seen messages lock")
+                .insert(&evt.message_hash);
+        }
+
+        if let (WakuEvent::WakuMessage(evt), Some(max_payload)) =
+            (&event, *self.max_payload.lock().expect("max payload lock"))
+        {
+            let payload_len = evt.waku_message.payload.len();
+            if payload_len > max_payload {
+                log::debug!(
+                    "dropping message {} with a {payload_len} bytes payload, above the {max_payload} bytes limit",
+                    evt.message_hash
+                );
+                return false;
+            }
+        }
+
+        if let WakuEvent::WakuMessage(evt) = &event {
+            if let (Some(peer_id), Some(rate_limiter)) = (
+                &evt.peer_id,
+                self.rate_limiter
+                    .lock()
+                    .expect("rate limiter lock")
+                    .as_mut(),
+            ) {
+                if !rate_limiter.allow(peer_id, Instant::now()) {
+                    log::debug!(
+                        "dropping message {} from {peer_id}, above the rate limit",
+                        evt.message_hash
+                    );
+                    self.rate_limited.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+            }
+        }
+
+        if let WakuEvent::RelayTopicHealthChange(evt) = &event {
+            self.topic_health.lock().expect("topic health lock").insert(
+                PubsubTopic::new(&evt.pubsub_topic),
+                evt.topic_health.clone(),
+            );
+        }
+
+        let peer_gate = self.peer_gate.lock().expect("peer gate lock");
+        let mut listeners = self.listeners.lock().expect("event listeners lock");
+        if let Some(peer_id) = peer_gate.as_ref().and_then(|gate| gate.rejected(&event)) {
+            if let Some(disconnects) = self.disconnects.lock().expect("disconnects lock").as_ref() {
+                let _ = disconnects.send(peer_id.to_string());
+            }
+        }
+        listeners.retain_mut(|(_, listener)| listener(&event).is_continue());
+        true
+    }
+
+    /// Make `closure` the user callback, returning the callback to register in libwaku,
+    /// which runs the hooks on every response before it
+    fn hooked_callback<F: FnMut(LibwakuResponse) + 'static + Sync + Send>(
+        self: Arc<Self>,
+        closure: F,
+    ) -> Box<LibwakuResponseClosure> {
+        *self.lock_callback() = Some(Box::new(closure));
+        Box::new(move |response: LibwakuResponse| {
+            self.watchdog.callback_fired();
+            if self.dispatch(&response) {
+                self.deliver(response)
+            }
+        })
+    }
+
+    /// The user callback may panic, as the default one does, without disabling later ones
+    fn lock_callback(&self) -> std::sync::MutexGuard<'_, Option<Box<LibwakuResponseClosure>>> {
+        self.callback.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Hand `response` to the user callback, or keep it for later while paused
+    fn deliver(&self, response: LibwakuResponse) {
+        {
+            let mut paused = self.paused.lock().expect("paused events lock");
+            if let Some(buffer) = paused.as_mut() {
+                if buffer.len() == PAUSED_EVENTS_CAPACITY {
+                    buffer.pop_front();
+                    self.dropped_while_paused.fetch_add(1, Ordering::Relaxed);
+                }
+                buffer.push_back(response);
+                return;
+            }
+        }
+        if let Some(callback) = self.lock_callback().as_mut() {
+            callback(response)
+        }
+    }
+
+    /// Emit a [`WakuEvent::NodeStalled`] event, as if libwaku had
+    fn report_stall(&self, silence: Duration) {
+        log::warn!("libwaku made no callback for {silence:?}");
+        let event = WakuEvent::NodeStalled(NodeStalledEvent {
+            stalled_for_millis: silence.as_millis() as u64,
+        });
+        let response = LibwakuResponse::Success(Some(
+            serde_json::to_string(&event).expect("events should always be serializable"),
+        ));
+        if self.dispatch(&response) {
+            self.deliver(response)
+        }
+    }
+
+    fn pause(&self) {
+        self.paused
+            .lock()
+            .expect("paused events lock")
+            .get_or_insert_with(VecDeque::new);
+    }
+
+    /// Deliver the events kept while paused, on the calling thread. Events arriving meanwhile
+    /// wait for the user callback, so the delivery order is preserved
+    fn resume(&self) {
+        let mut callback = self.lock_callback();
+        let Some(buffer) = self.paused.lock().expect("paused events lock").take() else {
+            return;
+        };
+        if let Some(callback) = callback.as_mut() {
+            buffer.into_iter().for_each(callback);
+        }
+    }
+}
+
+/// Pointer to the libwaku node, invalidated at once for every user when the node is destroyed.
+/// FFI calls hold a [`NodeGuard`] for as long as libwaku may use the pointer, and the node is
+/// only handed out for destruction once none of them is left
+struct NodePtr {
+    ptr: AtomicPtr<c_void>,
+    /// FFI calls holding the pointer
+    in_flight: AtomicUsize,
+    /// Notified when the last FFI call holding the pointer is done
+    idle: Notify,
+}
+
+impl NodePtr {
+    fn new(ptr: *mut c_void) -> Self {
+        Self {
+            ptr: AtomicPtr::new(ptr),
+            in_flight: AtomicUsize::new(0),
+            idle: Notify::new(),
+        }
+    }
+
+    fn get(&self) -> Result<*mut c_void> {
+        let ptr = self.ptr.load(Ordering::SeqCst);
+        if ptr.is_null() {
+            Err(WakuError::NodeDestroyed)
+        } else {
+            Ok(ptr)
+        }
+    }
+
+    /// Pointer to use for an FFI call, kept valid until the guard is dropped
+    fn guard(&self) -> Result<NodeGuard<'_>> {
+        // Counted before the pointer is loaded: either `take` sees this call in flight,
+        // or this call sees the pointer invalidated
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = NodeGuard {
+            in_flight: Some(self),
+            ptr: self.ptr.load(Ordering::SeqCst) as usize,
+            hooks: None,
+        };
+        if guard.ptr == 0 {
+            return Err(WakuError::NodeDestroyed);
+        }
+        Ok(guard)
+    }
+
+    /// Invalidate the pointer, then wait for the FFI calls still holding it to be done before
+    /// returning it to the only caller allowed to destroy the node
+    async fn take(&self) -> Result<NodeGuard<'_>> {
+        let ptr = self.ptr.swap(null_mut(), Ordering::SeqCst) as usize;
+        if ptr == 0 {
+            return Err(WakuError::NodeDestroyed);
+        }
+        loop {
+            let idle = self.idle.notified();
+            tokio::pin!(idle);
+            idle.as_mut().enable();
+            if self.in_flight.load(Ordering::SeqCst) == 0 {
+                return Ok(NodeGuard {
+                    in_flight: None,
+                    ptr,
+                    hooks: None,
+                });
+            }
+            idle.await;
+        }
+    }
+}
+
+/// Pointer to the libwaku node held for the duration of an FFI call, see [`NodePtr`]
+pub struct NodeGuard<'a> {
+    /// Node the call is counted in flight for, `None` for the call destroying the node
+    in_flight: Option<&'a NodePtr>,
+    /// Kept as an address so the futures holding the guard stay `Send`
+    ptr: usize,
+    /// Hooks of the node, whose watchdog watches the call
+    hooks: Option<&'a EventHooks>,
+}
+
+impl<'a> NodeGuard<'a> {
+    pub fn ptr(&self) -> *mut c_void {
+        self.ptr as *mut c_void
+    }
+
+    fn watched_by(mut self, hooks: &'a EventHooks) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Await the callback of the FFI call, reporting a stall if the node's watchdog is enabled
+    pub async fn await_response<F: Future>(&self, response: F) -> F::Output {
+        match self.hooks {
+            Some(hooks) => {
+                hooks
+                    .watchdog
+                    .watch(response, |silence| hooks.report_stall(silence))
+                    .await
+            }
+            None => response.await,
+        }
+    }
+}
+
+impl Drop for NodeGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(node) = self.in_flight {
+            if node.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+                node.idle.notify_waiters();
+            }
+        }
+    }
+}
+
+/// Slot of the relay subscription limit held while subscribing, see
+/// [`WakuNodeContext::reserve_relay_subscription`]
+pub struct RelayReservation<'a> {
+    ctx: &'a WakuNodeContext,
+    /// `None` if no slot had to be reserved
+    pubsub_topic: Option<PubsubTopic>,
+}
+
+impl Drop for RelayReservation<'_> {
+    fn drop(&mut self) {
+        if let Some(pubsub_topic) = &self.pubsub_topic {
+            self.ctx
+                .pending_relay_topics
+                .lock()
+                .expect("pending relay topics lock")
+                .remove(pubsub_topic);
+        }
+    }
+}
 
 pub struct WakuNodeContext {
-    obj_ptr: *mut c_void,
+    obj_ptr: Arc<NodePtr>,
+    /// Thread disconnecting the peers rejected by the peer gate, if any
+    disconnector: Mutex<Option<JoinHandle<()>>>,
     msg_observer: Arc<Mutex<Box<LibwakuResponseClosure>>>,
+    event_hooks: Arc<EventHooks>,
+    node_key: Option<SecretKey>,
+    /// Cluster the node was configured to run in
+    cluster_id: usize,
+    /// Protocols the node was configured to mount
+    capabilities: Vec<Protocol>,
+    discovery_status: Mutex<DiscoveryStatus>,
+    relay_topics: Mutex<HashSet<PubsubTopic>>,
+    /// Topics being subscribed to, holding a slot of the relay subscription limit meanwhile
+    pending_relay_topics: Mutex<HashSet<PubsubTopic>>,
+    max_relay_subscriptions: Option<usize>,
+    filter_subscriptions: Mutex<HashMap<PubsubTopic, HashSet<WakuContentTopic>>>,
+    queued_relay_topics: Mutex<Vec<PubsubTopic>>,
+    /// ENRs of the peers learnt by the bindings, by peer id
+    peer_enrs: Mutex<HashMap<String, Enr>>,
+    /// Events buffered for [`WakuNodeContext::try_next_event`]
+    buffered_events: Mutex<Option<EventStream>>,
+    /// Whether the node is started, watched by [`WakuNodeContext::wait_stopped`]
+    running: watch::Sender<bool>,
+    publish_stats: Mutex<PublishStats>,
+    /// Peers dialed whenever the node starts
+    static_bootstrap_peers: Vec<Multiaddr>,
 }
 
 impl WakuNodeContext {
     pub fn new(obj_ptr: *mut c_void) -> Self {
         let me = Self {
-            obj_ptr,
+            obj_ptr: Arc::new(NodePtr::new(obj_ptr)),
+            disconnector: Default::default(),
             msg_observer: Arc::new(Mutex::new(Box::new(|_| {}))),
+            event_hooks: Default::default(),
+            node_key: None,
+            cluster_id: 0,
+            capabilities: Vec::new(),
+            discovery_status: Default::default(),
+            relay_topics: Default::default(),
+            pending_relay_topics: Default::default(),
+            max_relay_subscriptions: None,
+            filter_subscriptions: Default::default(),
+            queued_relay_topics: Default::default(),
+            peer_enrs: Default::default(),
+            buffered_events: Default::default(),
+            running: watch::Sender::new(false),
+            publish_stats: Default::default(),
+            static_bootstrap_peers: Vec::new(),
         };
 
         // By default we set a callback that will panic if the user didn't specify a valid callback.
@@ -31,12 +522,468 @@ impl WakuNodeContext {
         panic!("callback not set. Please use waku_set_event_callback to set a valid callback")
     }
 
-    pub fn get_ptr(&self) -> *mut c_void {
-        self.obj_ptr
+    /// Enforce the given allow/deny lists on the events received by the event callback
+    pub fn with_peer_gate(self, peer_gate: Option<PeerGate>) -> Self {
+        if peer_gate.is_some() {
+            let (disconnects, rejected) = std_mpsc::channel();
+            *self
+                .event_hooks
+                .disconnects
+                .lock()
+                .expect("disconnects lock") = Some(disconnects);
+            *self.disconnector.lock().expect("disconnector lock") =
+                Some(disconnector(self.obj_ptr.clone(), rejected));
+        }
+        *self.event_hooks.peer_gate.lock().expect("peer gate lock") = peer_gate;
+        self
+    }
+
+    /// Drop the messages received from a peer beyond `max_per_sec` within a second
+    pub fn with_message_rate_limit(self, max_per_sec: Option<u32>) -> Self {
+        *self
+            .event_hooks
+            .rate_limiter
+            .lock()
+            .expect("rate limiter lock") = max_per_sec.map(PeerRateLimiter::new);
+        self
+    }
+
+    /// Number of messages dropped for exceeding the rate limit of their peer
+    pub fn rate_limited_messages(&self) -> u64 {
+        self.event_hooks.rate_limited.load(Ordering::Relaxed)
+    }
+
+    /// Let the peer gate, if any, know the node dialed `peer_id`
+    pub fn peer_dialed(&self, peer_id: &str) {
+        if let Some(peer_gate) = self
+            .event_hooks
+            .peer_gate
+            .lock()
+            .expect("peer gate lock")
+            .as_mut()
+        {
+            peer_gate.dialed(peer_id);
+        }
+    }
+
+    /// Whether the node only stays connected to the peers it dialed
+    pub fn manual_connections_only(&self) -> bool {
+        self.event_hooks
+            .peer_gate
+            .lock()
+            .expect("peer gate lock")
+            .as_ref()
+            .is_some_and(PeerGate::manual_only)
+    }
+
+    /// Keep the key the node was configured with, used to sign messages
+    pub fn with_node_key(mut self, node_key: Option<SecretKey>) -> Self {
+        self.node_key = node_key;
+        self
+    }
+
+    pub fn node_key(&self) -> Option<&SecretKey> {
+        self.node_key.as_ref()
+    }
+
+    /// Keep the cluster the node was configured to run in
+    pub fn with_cluster_id(mut self, cluster_id: usize) -> Self {
+        self.cluster_id = cluster_id;
+        self
+    }
+
+    pub fn cluster_id(&self) -> usize {
+        self.cluster_id
+    }
+
+    /// Keep the peers to dial whenever the node starts
+    pub fn with_static_bootstrap_peers(mut self, peers: Vec<Multiaddr>) -> Self {
+        self.static_bootstrap_peers = peers;
+        self
+    }
+
+    pub fn static_bootstrap_peers(&self) -> &[Multiaddr] {
+        &self.static_bootstrap_peers
+    }
+
+    /// Keep the protocols the node was configured to mount
+    pub fn with_capabilities(mut self, capabilities: Vec<Protocol>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn capabilities(&self) -> &[Protocol] {
+        &self.capabilities
+    }
+
+    /// Keep which discovery mechanisms the node was configured with
+    pub fn with_discovery_status(self, discovery_status: DiscoveryStatus) -> Self {
+        *self.discovery_status.lock().expect("discovery status lock") = discovery_status;
+        self
     }
 
-    pub fn reset_ptr(mut self) {
-        self.obj_ptr = null_mut();
+    /// Count a discovery through `mechanism` that found `peers`
+    pub fn discovery_succeeded(&self, mechanism: DiscoveryMechanism, peers: usize) {
+        self.discovery_status
+            .lock()
+            .expect("discovery status lock")
+            .record_success(mechanism, peers)
+    }
+
+    pub fn set_discovery_enabled(&self, mechanism: DiscoveryMechanism, enabled: bool) {
+        self.discovery_status
+            .lock()
+            .expect("discovery status lock")
+            .set_enabled(mechanism, enabled)
+    }
+
+    pub fn discovery_status(&self) -> DiscoveryStatus {
+        self.discovery_status
+            .lock()
+            .expect("discovery status lock")
+            .clone()
+    }
+
+    /// Count `message` in the publish stats and remember its `hash` as seen, once published
+    pub fn message_published(&self, message: &WakuMessage, hash: &MessageHash) {
+        self.publish_stats
+            .lock()
+            .expect("publish stats lock")
+            .record(message);
+        self.event_hooks
+            .seen
+            .lock()
+            .expect("seen messages lock")
+            .insert(hash);
+    }
+
+    /// Whether a message with `hash` was received or published among the latest
+    /// [`SEEN_MESSAGES_CAPACITY`] messages
+    pub fn has_seen(&self, hash: &MessageHash) -> bool {
+        self.event_hooks
+            .seen
+            .lock()
+            .expect("seen messages lock")
+            .contains(hash)
+    }
+
+    pub fn publish_stats(&self) -> PublishStats {
+        self.publish_stats
+            .lock()
+            .expect("publish stats lock")
+            .clone()
+    }
+
+    pub fn with_max_relay_subscriptions(mut self, max: Option<usize>) -> Self {
+        self.max_relay_subscriptions = max;
+        self
+    }
+
+    /// Hold a slot of the relay subscription limit for `pubsub_topic` while subscribing to it,
+    /// [`WakuError::SubscriptionLimitReached`] if subscribed and pending topics fill the limit
+    /// already. Resubscribing to a subscribed or pending topic is always fine. The slot is
+    /// released once the returned reservation is dropped, so a successful subscription must be
+    /// recorded with [`Self::relay_subscribed`] before
+    pub fn reserve_relay_subscription(
+        &self,
+        pubsub_topic: &PubsubTopic,
+    ) -> Result<RelayReservation<'_>> {
+        let Some(limit) = self.max_relay_subscriptions else {
+            return Ok(RelayReservation {
+                ctx: self,
+                pubsub_topic: None,
+            });
+        };
+        let relay_topics = self.relay_topics.lock().expect("relay topics lock");
+        let mut pending = self
+            .pending_relay_topics
+            .lock()
+            .expect("pending relay topics lock");
+        if relay_topics.contains(pubsub_topic) || pending.contains(pubsub_topic) {
+            return Ok(RelayReservation {
+                ctx: self,
+                pubsub_topic: None,
+            });
+        }
+        if relay_topics.len() + pending.len() >= limit {
+            return Err(WakuError::SubscriptionLimitReached { limit });
+        }
+        pending.insert(pubsub_topic.clone());
+        Ok(RelayReservation {
+            ctx: self,
+            pubsub_topic: Some(pubsub_topic.clone()),
+        })
+    }
+
+    /// Keep track of a successful relay subscription
+    pub fn relay_subscribed(&self, pubsub_topic: &PubsubTopic) {
+        self.relay_topics
+            .lock()
+            .expect("relay topics lock")
+            .insert(pubsub_topic.clone());
+    }
+
+    /// Keep track of a successful relay unsubscription
+    pub fn relay_unsubscribed(&self, pubsub_topic: &PubsubTopic) {
+        self.relay_topics
+            .lock()
+            .expect("relay topics lock")
+            .remove(pubsub_topic);
+    }
+
+    /// Relay topics currently subscribed to
+    pub fn relay_topics(&self) -> Vec<PubsubTopic> {
+        self.relay_topics
+            .lock()
+            .expect("relay topics lock")
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Keep track of a successful filter subscription
+    pub fn filter_subscribed(
+        &self,
+        pubsub_topic: &PubsubTopic,
+        content_topics: &[WakuContentTopic],
+    ) {
+        self.filter_subscriptions
+            .lock()
+            .expect("filter subscriptions lock")
+            .entry(pubsub_topic.clone())
+            .or_default()
+            .extend(content_topics.iter().cloned());
+    }
+
+    /// Keep track of a successful filter unsubscription
+    pub fn filter_unsubscribed(
+        &self,
+        pubsub_topic: &PubsubTopic,
+        content_topics: &[WakuContentTopic],
+    ) {
+        let mut subscriptions = self
+            .filter_subscriptions
+            .lock()
+            .expect("filter subscriptions lock");
+        if let Some(subscribed) = subscriptions.get_mut(pubsub_topic) {
+            content_topics.iter().for_each(|topic| {
+                subscribed.remove(topic);
+            });
+            if subscribed.is_empty() {
+                subscriptions.remove(pubsub_topic);
+            }
+        }
+    }
+
+    /// Keep track of a successful unsubscription from every filter subscription
+    pub fn filter_unsubscribed_all(&self) {
+        self.filter_subscriptions
+            .lock()
+            .expect("filter subscriptions lock")
+            .clear();
+    }
+
+    /// Content topics currently subscribed to through filter, by pubsub topic
+    pub fn filter_subscriptions(&self) -> Vec<(PubsubTopic, Vec<WakuContentTopic>)> {
+        self.filter_subscriptions
+            .lock()
+            .expect("filter subscriptions lock")
+            .iter()
+            .map(|(pubsub_topic, content_topics)| {
+                (
+                    pubsub_topic.clone(),
+                    content_topics.iter().cloned().collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Keep the ENR of a peer, replacing the one previously known
+    pub fn remember_peer_enr(&self, enr: Enr) {
+        self.peer_enrs
+            .lock()
+            .expect("peer enrs lock")
+            .insert(enr_peer_id(&enr), enr);
+    }
+
+    pub fn peer_enrs(&self) -> Vec<Enr> {
+        self.peer_enrs
+            .lock()
+            .expect("peer enrs lock")
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Remember a relay topic to subscribe to whenever the node starts
+    pub fn queue_relay_subscription(&self, pubsub_topic: &PubsubTopic) {
+        let mut queued = self
+            .queued_relay_topics
+            .lock()
+            .expect("queued relay topics lock");
+        if !queued.contains(pubsub_topic) {
+            queued.push(pubsub_topic.clone());
+        }
+    }
+
+    /// Relay topics to subscribe to whenever the node starts, in queueing order
+    pub fn queued_relay_subscriptions(&self) -> Vec<PubsubTopic> {
+        self.queued_relay_topics
+            .lock()
+            .expect("queued relay topics lock")
+            .clone()
+    }
+
+    /// Last health reported by a `relay_topic_health_change` event for `pubsub_topic`
+    pub fn topic_health(&self, pubsub_topic: &PubsubTopic) -> Option<TopicHealth> {
+        self.event_hooks
+            .topic_health
+            .lock()
+            .expect("topic health lock")
+            .get(pubsub_topic)
+            .cloned()
+    }
+
+    /// Pointer to the libwaku node, [`WakuError::NodeDestroyed`] once the node was destroyed.
+    /// Nothing keeps it valid afterwards, FFI calls use [`ptr_guard`](Self::ptr_guard) instead
+    pub fn get_ptr(&self) -> Result<*mut c_void> {
+        self.obj_ptr.get()
+    }
+
+    /// Pointer to the libwaku node for an FFI call: the node isn't destroyed while the guard
+    /// is alive. [`WakuError::NodeDestroyed`] once the node was destroyed
+    pub fn ptr_guard(&self) -> Result<NodeGuard<'_>> {
+        Ok(self.obj_ptr.guard()?.watched_by(&self.event_hooks))
+    }
+
+    /// Invalidate the pointer for every other user so the node can be destroyed, once the
+    /// FFI calls still using it are done. Only the first call gets the pointer
+    pub async fn take_ptr(&self) -> Result<NodeGuard<'_>> {
+        self.set_running(false);
+        let node = self.obj_ptr.take().await?;
+        self.stop_disconnector().await;
+        Ok(node.watched_by(&self.event_hooks))
+    }
+
+    /// Stop the thread disconnecting the peers rejected by the peer gate, if any.
+    /// Once the pointer is invalidated it can't start any disconnection anymore
+    async fn stop_disconnector(&self) {
+        self.event_hooks
+            .disconnects
+            .lock()
+            .expect("disconnects lock")
+            .take();
+        let disconnector = self.disconnector.lock().expect("disconnector lock").take();
+        if let Some(disconnector) = disconnector {
+            // The thread may be finishing a disconnection, a libwaku round trip, so it is
+            // joined off the runtime threads
+            let joined = tokio::task::spawn_blocking(move || disconnector.join()).await;
+            if !matches!(joined, Ok(Ok(()))) {
+                log::warn!("the thread disconnecting disallowed peers panicked");
+            }
+        }
+    }
+
+    /// Keep whether the node is started
+    pub fn set_running(&self, running: bool) {
+        self.running.send_replace(running);
+    }
+
+    /// Resolves once the node is no longer started: stopped, destroyed or dropped
+    pub fn wait_stopped(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut running = self.running.subscribe();
+        async move {
+            // The sender is gone only once the node is dropped
+            let _ = running.wait_for(|running| !running).await;
+        }
+    }
+
+    /// Register a listener called with every parsed event before the event callback.
+    /// The listener is dropped as soon as it returns [`ControlFlow::Break`].
+    /// Listeners run on the libwaku thread, so they must neither block nor register
+    /// or remove listeners themselves.
+    pub fn add_event_listener<F>(&self, listener: F) -> ListenerId
+    where
+        F: FnMut(&WakuEvent) -> ControlFlow<()> + Send + 'static,
+    {
+        let id = ListenerId(
+            self.event_hooks
+                .next_listener_id
+                .fetch_add(1, Ordering::Relaxed),
+        );
+        self.event_hooks
+            .listeners
+            .lock()
+            .expect("event listeners lock")
+            .push((id, Box::new(listener)));
+        id
+    }
+
+    /// Remove a listener, doing nothing if it was already dropped
+    pub fn remove_event_listener(&self, id: ListenerId) {
+        self.event_hooks
+            .listeners
+            .lock()
+            .expect("event listeners lock")
+            .retain(|(listener_id, _)| *listener_id != id);
+    }
+
+    /// Drop the messages whose payload is larger than `max_payload` bytes before they reach
+    /// the event listeners and callback, `None` to deliver every message
+    pub fn set_max_event_payload(&self, max_payload: Option<usize>) {
+        *self
+            .event_hooks
+            .max_payload
+            .lock()
+            .expect("max payload lock") = max_payload;
+    }
+
+    /// Keep the events from the event callback until [`resume_events`](Self::resume_events),
+    /// up to [`PAUSED_EVENTS_CAPACITY`] of them. Hooks and listeners keep running
+    pub fn pause_events(&self) {
+        self.event_hooks.pause()
+    }
+
+    /// Deliver the events kept while paused to the event callback, on the calling thread,
+    /// then let new events reach it directly again
+    pub fn resume_events(&self) {
+        self.event_hooks.resume()
+    }
+
+    /// Number of events dropped because more than [`PAUSED_EVENTS_CAPACITY`] arrived while paused
+    pub fn events_dropped_while_paused(&self) -> u64 {
+        self.event_hooks
+            .dropped_while_paused
+            .load(Ordering::Relaxed)
+    }
+
+    /// Keep `events`, for them to be polled
+    pub fn buffer_events(&self, events: EventStream) {
+        *self.buffered_events.lock().expect("buffered events lock") = Some(events);
+    }
+
+    /// Oldest buffered event, if any, without waiting
+    pub fn try_next_event(&self) -> Option<WakuEvent> {
+        self.buffered_events
+            .lock()
+            .expect("buffered events lock")
+            .as_mut()?
+            .try_recv()
+    }
+
+    /// Number of events dropped because the buffer was full
+    pub fn buffered_events_dropped(&self) -> u64 {
+        self.buffered_events
+            .lock()
+            .expect("buffered events lock")
+            .as_ref()
+            .map_or(0, EventStream::dropped)
+    }
+
+    /// Emit a [`WakuEvent::NodeStalled`] event whenever libwaku makes no callback for longer
+    /// than `window` while an operation awaits its result, `None` to disable the watchdog
+    pub fn set_stall_window(&self, window: Option<Duration>) {
+        self.event_hooks.watchdog.set_window(window)
     }
 
     /// Register callback to act as event handler and receive application events,
@@ -44,20 +991,354 @@ impl WakuNodeContext {
     pub fn waku_set_event_callback<F: FnMut(LibwakuResponse) + 'static + Sync + Send>(
         &self,
         closure: F,
-    ) -> Result<(), String> {
+    ) -> Result<()> {
+        let node = self.ptr_guard()?;
+        let obj_ptr = node.ptr();
         if let Ok(mut boxed_closure) = self.msg_observer.lock() {
-            *boxed_closure = Box::new(closure);
+            *boxed_closure = self.event_hooks.clone().hooked_callback(closure);
             unsafe {
                 let cb = get_trampoline(&(*boxed_closure));
                 waku_sys::waku_set_event_callback(
-                    self.obj_ptr,
+                    obj_ptr,
                     cb,
                     &mut (*boxed_closure) as *mut _ as *mut c_void,
                 )
             };
             Ok(())
         } else {
-            Err("Failed to acquire lock in waku_set_event_callback!".to_string())
+            Err("Failed to acquire lock in waku_set_event_callback!".into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::contenttopic::Encoding;
+    use std::str::FromStr;
+
+    #[test]
+    fn destroyed_pointer_is_seen_by_every_user() {
+        let mut node = 0u8;
+        let node_addr = &mut node as *mut u8 as *mut c_void as usize;
+        let ptr = Arc::new(NodePtr::new(node_addr as *mut c_void));
+
+        let users: Vec<_> = (0..8)
+            .map(|_| {
+                let ptr = ptr.clone();
+                thread::spawn(move || {
+                    let mut destroyed = false;
+                    for _ in 0..10_000 {
+                        match ptr.guard() {
+                            Ok(guard) => {
+                                assert!(!destroyed, "pointer handed out after destroy");
+                                assert_eq!(guard.ptr() as usize, node_addr);
+                            }
+                            Err(e) => {
+                                assert_eq!(e, WakuError::NodeDestroyed);
+                                destroyed = true;
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        let destroyers: Vec<_> = (0..2)
+            .map(|_| {
+                let ptr = ptr.clone();
+                thread::spawn(move || futures::executor::block_on(ptr.take()).is_ok())
+            })
+            .collect();
+
+        for user in users {
+            user.join().unwrap();
         }
+        let taken = destroyers
+            .into_iter()
+            .map(|destroyer| destroyer.join().unwrap())
+            .filter(|taken| *taken)
+            .count();
+        assert_eq!(taken, 1);
+        assert_eq!(ptr.get(), Err(WakuError::NodeDestroyed));
+    }
+
+    #[tokio::test]
+    async fn destroy_waits_for_calls_in_flight() {
+        let mut node = 0u8;
+        let ptr = Arc::new(NodePtr::new(&mut node as *mut u8 as *mut c_void));
+        let (in_flight, done) = tokio::sync::oneshot::channel::<()>();
+        let call = {
+            let ptr = ptr.clone();
+            tokio::spawn(async move {
+                let _guard = ptr.guard().unwrap();
+                done.await.unwrap();
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let destroy = {
+            let ptr = ptr.clone();
+            tokio::spawn(async move { ptr.take().await.map(|guard| guard.ptr() as usize) })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!destroy.is_finished());
+        assert!(matches!(ptr.guard(), Err(WakuError::NodeDestroyed)));
+
+        in_flight.send(()).unwrap();
+        call.await.unwrap();
+        assert_eq!(destroy.await.unwrap(), Ok(&mut node as *mut u8 as usize));
+    }
+
+    #[test]
+    fn listener_dropped_after_break() {
+        let hooks = EventHooks::default();
+        let mut calls = 0;
+        hooks.listeners.lock().unwrap().push((
+            ListenerId(0),
+            Box::new(move |_: &WakuEvent| {
+                calls += 1;
+                if calls == 2 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            }),
+        ));
+
+        let event = LibwakuResponse::Success(Some(
+            "{\"eventType\":\"connection_change\",\"peerId\":\"peerA\",\"peerEvent\":\"Left\"}"
+                .to_string(),
+        ));
+        hooks.dispatch(&event);
+        assert_eq!(hooks.listeners.lock().unwrap().len(), 1);
+        hooks.dispatch(&LibwakuResponse::Success(None));
+        assert_eq!(hooks.listeners.lock().unwrap().len(), 1);
+        hooks.dispatch(&event);
+        assert!(hooks.listeners.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejected_peers_sent_to_disconnector() {
+        let hooks = EventHooks::default();
+        *hooks.peer_gate.lock().unwrap() = PeerGate::from_config(&crate::WakuNodeConfig {
+            denied_peers: Some(vec!["peerA".to_string()]),
+            ..Default::default()
+        });
+        let (disconnects, rejected) = std_mpsc::channel();
+        *hooks.disconnects.lock().unwrap() = Some(disconnects);
+        let event = |peer_id: &str, peer_event: &str| {
+            LibwakuResponse::Success(Some(format!(
+                "{{\"eventType\":\"connection_change\",\"peerId\":\"{peer_id}\",\"peerEvent\":\"{peer_event}\"}}"
+            )))
+        };
+
+        hooks.dispatch(&event("peerB", "Joined"));
+        hooks.dispatch(&event("peerA", "Left"));
+        hooks.dispatch(&event("peerA", "Joined"));
+        assert_eq!(rejected.try_iter().collect::<Vec<_>>(), vec!["peerA"]);
+    }
+
+    #[test]
+    fn oversized_messages_are_dropped() {
+        let hooks = Arc::new(EventHooks::default());
+        *hooks.max_payload.lock().unwrap() = Some(4);
+        let message = |payload: &str| {
+            LibwakuResponse::Success(Some(format!(
+                "{{\"eventType\":\"message\",\"messageHash\":[91, 70, 26, 8, 141, 232, 150, 200, 26, 206, 224, 175, 249, 74, 61, 140, 231, 126, 224, 160, 91, 80, 162, 65, 250, 171, 84, 149, 133, 110, 214, 101],\"pubsubTopic\":\"/waku/2/rs/16/32\",\"wakuMessage\":{{\"payload\":\"{payload}\",\"contentTopic\":\"/toychat/2/huilong/proto\"}}}}"
+            )))
+        };
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_cloned = received.clone();
+        let mut callback =
+            hooks.hooked_callback(move |response| received_cloned.lock().unwrap().push(response));
+
+        // "Hi from 🦀!" is 13 bytes long, "Hi!" is 3 bytes long
+        callback(message("SGkgZnJvbSDwn6aAIQ=="));
+        callback(message("SGkh"));
+        callback(LibwakuResponse::Success(None));
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![message("SGkh"), LibwakuResponse::Success(None)]
+        );
+    }
+
+    #[test]
+    fn publish_stats_by_content_topic() {
+        let chat = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        let game = WakuContentTopic::new("tictactoe", "1", "moves", Encoding::Proto);
+        let mut stats = PublishStats::default();
+        for payload in ["Hi!", "Hi from 🦀!"] {
+            stats.record(&WakuMessage::new(
+                payload,
+                chat.clone(),
+                0,
+                Vec::new(),
+                false,
+            ));
+        }
+        stats.record(&WakuMessage::new("a1", game.clone(), 0, Vec::new(), false));
+
+        assert_eq!(
+            stats.by_content_topic[&chat],
+            TopicPublishStats {
+                messages: 2,
+                bytes: 16
+            }
+        );
+        assert_eq!(
+            stats.by_content_topic[&game],
+            TopicPublishStats {
+                messages: 1,
+                bytes: 2
+            }
+        );
+        assert_eq!(
+            stats.total(),
+            TopicPublishStats {
+                messages: 3,
+                bytes: 18
+            }
+        );
+    }
+
+    #[test]
+    fn messages_beyond_peer_rate_are_dropped() {
+        let hooks = Arc::new(EventHooks::default());
+        *hooks.rate_limiter.lock().unwrap() = Some(PeerRateLimiter::new(3));
+        let message = |peer_id: &str| {
+            LibwakuResponse::Success(Some(format!(
+                "{{\"eventType\":\"message\",\"messageHash\":[91, 70, 26, 8, 141, 232, 150, 200, 26, 206, 224, 175, 249, 74, 61, 140, 231, 126, 224, 160, 91, 80, 162, 65, 250, 171, 84, 149, 133, 110, 214, 101],\"pubsubTopic\":\"/waku/2/rs/16/32\",\"peerId\":\"{peer_id}\",\"wakuMessage\":{{\"payload\":\"SGkh\",\"contentTopic\":\"/toychat/2/huilong/proto\"}}}}"
+            )))
+        };
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_cloned = received.clone();
+        let mut callback = hooks
+            .clone()
+            .hooked_callback(move |response| received_cloned.lock().unwrap().push(response));
+
+        for _ in 0..10 {
+            callback(message("16Uiu2HAmSpammer"));
+        }
+        callback(message("16Uiu2HAmHonest"));
+        assert_eq!(received.lock().unwrap().len(), 4);
+        assert_eq!(hooks.rate_limited.load(Ordering::Relaxed), 7);
+    }
+
+    #[test]
+    fn peer_rate_window_slides() {
+        let mut limiter = PeerRateLimiter::new(2);
+        let start = Instant::now();
+        assert!(limiter.allow("peer", start));
+        assert!(limiter.allow("peer", start + Duration::from_millis(100)));
+        assert!(!limiter.allow("peer", start + Duration::from_millis(900)));
+        assert!(limiter.allow("peer", start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn received_messages_seen() {
+        let hooks = EventHooks::default();
+        let hash = MessageHash::from_str(&"5b".repeat(32)).unwrap();
+        let event = LibwakuResponse::Success(Some(format!(
+            "{{\"eventType\":\"message\",\"messageHash\":{},\"pubsubTopic\":\"/waku/2/rs/16/1\",\"wakuMessage\":{{\"contentTopic\":\"/toychat/2/huilong/proto\"}}}}",
+            serde_json::to_string(&hash).unwrap()
+        )));
+
+        assert!(!hooks.seen.lock().unwrap().contains(&hash));
+        hooks.dispatch(&event);
+        assert!(hooks.seen.lock().unwrap().contains(&hash));
+    }
+
+    #[test]
+    fn oldest_seen_messages_forgotten() {
+        let mut seen = SeenMessages::default();
+        let hash = |i: usize| MessageHash::from_str(&format!("{i:064x}")).unwrap();
+
+        for i in 0..SEEN_MESSAGES_CAPACITY + 1 {
+            seen.insert(&hash(i));
+        }
+        seen.insert(&hash(1));
+
+        assert!(!seen.contains(&hash(0)));
+        assert!(seen.contains(&hash(1)));
+        assert!(seen.contains(&hash(SEEN_MESSAGES_CAPACITY)));
+        assert_eq!(seen.order.len(), SEEN_MESSAGES_CAPACITY);
+    }
+
+    #[test]
+    fn paused_events_delivered_on_resume() {
+        let hooks = Arc::new(EventHooks::default());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_cloned = received.clone();
+        let mut callback = hooks
+            .clone()
+            .hooked_callback(move |response| received_cloned.lock().unwrap().push(response));
+        let response = |i: usize| LibwakuResponse::Success(Some(i.to_string()));
+
+        hooks.pause();
+        for i in 0..PAUSED_EVENTS_CAPACITY + 2 {
+            callback(response(i));
+        }
+        assert!(received.lock().unwrap().is_empty());
+        assert_eq!(hooks.dropped_while_paused.load(Ordering::Relaxed), 2);
+
+        hooks.resume();
+        callback(response(PAUSED_EVENTS_CAPACITY + 2));
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), PAUSED_EVENTS_CAPACITY + 1);
+        assert_eq!(received[0], response(2));
+        assert_eq!(received.last(), Some(&response(PAUSED_EVENTS_CAPACITY + 2)));
+    }
+
+    #[tokio::test]
+    async fn stalled_node_reported_to_listeners() {
+        let hooks = Arc::new(EventHooks::default());
+        hooks.watchdog.set_window(Some(Duration::from_millis(50)));
+        let stalls = Arc::new(Mutex::new(Vec::new()));
+        let stalls_cloned = stalls.clone();
+        hooks.listeners.lock().unwrap().push((
+            ListenerId(0),
+            Box::new(move |event: &WakuEvent| {
+                if let WakuEvent::NodeStalled(evt) = event {
+                    stalls_cloned.lock().unwrap().push(evt.stalled_for());
+                }
+                ControlFlow::Continue(())
+            }),
+        ));
+        let mut node = 0u8;
+        let ptr = NodePtr::new(&mut node as *mut u8 as *mut c_void);
+
+        // Stand-in for an FFI call whose callback fires late
+        let guard = ptr.guard().unwrap().watched_by(&hooks);
+        guard
+            .await_response(tokio::time::sleep(Duration::from_millis(200)))
+            .await;
+
+        let stalls = stalls.lock().unwrap();
+        assert_eq!(stalls.len(), 1);
+        assert!(stalls[0] >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn topic_health_tracked_from_events() {
+        let hooks = EventHooks::default();
+        let event = |health: &str| {
+            LibwakuResponse::Success(Some(format!(
+                "{{\"eventType\":\"relay_topic_health_change\",\"pubsubTopic\":\"/waku/2/rs/16/1\",\"topicHealth\":\"{health}\"}}"
+            )))
+        };
+        let topic = PubsubTopic::new("/waku/2/rs/16/1");
+
+        hooks.dispatch(&event("MinimallyHealthy"));
+        assert_eq!(
+            hooks.topic_health.lock().unwrap().get(&topic),
+            Some(&TopicHealth::MinimallyHealthy)
+        );
+        hooks.dispatch(&event("UnHealthy"));
+        assert_eq!(
+            hooks.topic_health.lock().unwrap().get(&topic),
+            Some(&TopicHealth::UnHealthy)
+        );
     }
 }
@@ -0,0 +1,223 @@
+//! Peer discovery mechanisms of a node: their status, discv5 control and peer exchange
+
+// std
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+// crates
+use multiaddr::Multiaddr;
+// internal
+use crate::general::libwaku_response::{handle_no_response, handle_response, LibwakuResponse};
+use crate::general::Result;
+use crate::handle_ffi_call;
+use crate::node::config::WakuNodeConfig;
+use crate::node::context::WakuNodeContext;
+use crate::node::dns::{enr_multiaddrs, enr_peer_id, Enr};
+use crate::node::peers::{waku_connect, waku_get_peerids_from_peerstore};
+
+/// Ways a node discovers peers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiscoveryMechanism {
+    Discv5,
+    Dns,
+    PeerExchange,
+    Rendezvous,
+}
+
+/// Whether a discovery mechanism is enabled and when it last found peers
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiscoveryMechanismStatus {
+    pub enabled: bool,
+    /// Last time a discovery through the bindings succeeded
+    pub last_success: Option<SystemTime>,
+    /// Peers found by the successful discoveries through the bindings
+    pub peers_found: u64,
+}
+
+/// Status of each discovery mechanism of a node.
+/// `enabled` follows the node configuration and the libwaku defaults, then
+/// [`discv5_start`](crate::WakuNodeHandle::discv5_start) and
+/// [`discv5_stop`](crate::WakuNodeHandle::discv5_stop). libwaku reports nothing
+/// about the discoveries it runs by itself, so `last_success` and `peers_found` only account
+/// for the discoveries made through the bindings, i.e.
+/// [`dns_discovery`](crate::WakuNodeHandle::dns_discovery)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiscoveryStatus {
+    pub discv5: DiscoveryMechanismStatus,
+    pub dns: DiscoveryMechanismStatus,
+    pub peer_exchange: DiscoveryMechanismStatus,
+    pub rendezvous: DiscoveryMechanismStatus,
+}
+
+impl DiscoveryStatus {
+    /// Status of a node created with `config`, before any discovery
+    pub(crate) fn configured(config: &WakuNodeConfig) -> Self {
+        let enabled = |enabled| DiscoveryMechanismStatus {
+            enabled,
+            ..Default::default()
+        };
+        Self {
+            discv5: enabled(config.discv5_discovery.unwrap_or(false)),
+            dns: enabled(config.dns_discovery.unwrap_or(false)),
+            // libwaku serves peer exchange unless told otherwise, which the config can't do
+            peer_exchange: enabled(true),
+            rendezvous: enabled(config.rendezvous.unwrap_or(true)),
+        }
+    }
+
+    pub fn get(&self, mechanism: DiscoveryMechanism) -> &DiscoveryMechanismStatus {
+        match mechanism {
+            DiscoveryMechanism::Discv5 => &self.discv5,
+            DiscoveryMechanism::Dns => &self.dns,
+            DiscoveryMechanism::PeerExchange => &self.peer_exchange,
+            DiscoveryMechanism::Rendezvous => &self.rendezvous,
+        }
+    }
+
+    fn get_mut(&mut self, mechanism: DiscoveryMechanism) -> &mut DiscoveryMechanismStatus {
+        match mechanism {
+            DiscoveryMechanism::Discv5 => &mut self.discv5,
+            DiscoveryMechanism::Dns => &mut self.dns,
+            DiscoveryMechanism::PeerExchange => &mut self.peer_exchange,
+            DiscoveryMechanism::Rendezvous => &mut self.rendezvous,
+        }
+    }
+
+    /// Count a discovery through `mechanism` that found `peers`
+    pub(crate) fn record_success(&mut self, mechanism: DiscoveryMechanism, peers: usize) {
+        let status = self.get_mut(mechanism);
+        status.last_success = Some(SystemTime::now());
+        status.peers_found += peers as u64;
+    }
+
+    pub(crate) fn set_enabled(&mut self, mechanism: DiscoveryMechanism, enabled: bool) {
+        self.get_mut(mechanism).enabled = enabled;
+    }
+}
+
+/// Start the discv5 service of the node, which must be configured with a discv5 udp port
+pub async fn waku_discv5_start(ctx: &WakuNodeContext) -> Result<()> {
+    handle_ffi_call!(
+        waku_sys::waku_start_discv5,
+        handle_no_response,
+        ctx.ptr_guard()?
+    )?;
+    ctx.set_discovery_enabled(DiscoveryMechanism::Discv5, true);
+    Ok(())
+}
+
+/// Stop the discv5 service of the node
+pub async fn waku_discv5_stop(ctx: &WakuNodeContext) -> Result<()> {
+    handle_ffi_call!(
+        waku_sys::waku_stop_discv5,
+        handle_no_response,
+        ctx.ptr_guard()?
+    )?;
+    ctx.set_discovery_enabled(DiscoveryMechanism::Discv5, false);
+    Ok(())
+}
+
+/// Request up to `num_peers` peers through peer exchange, after dialing `peer_addr` within
+/// `dial_timeout` so it can serve the request. libwaku takes no timeout for the request itself.
+/// libwaku only reports how many peers it received and adds them to the peer store, so the
+/// peers new to the peer store are returned, see [`exchanged_addresses`]
+pub async fn waku_peer_exchange_request(
+    ctx: &WakuNodeContext,
+    peer_addr: &Multiaddr,
+    num_peers: u64,
+    dial_timeout: Option<Duration>,
+) -> Result<Vec<Multiaddr>> {
+    waku_connect(ctx, peer_addr, dial_timeout).await?;
+    let known: HashSet<String> = waku_get_peerids_from_peerstore(ctx)
+        .await?
+        .into_iter()
+        .collect();
+
+    let received: usize = handle_ffi_call!(
+        waku_sys::waku_peer_exchange_request,
+        handle_response,
+        ctx.ptr_guard()?,
+        num_peers.try_into().unwrap_or(i32::MAX)
+    )?;
+    ctx.discovery_succeeded(DiscoveryMechanism::PeerExchange, received);
+
+    let exchanged: Vec<String> = waku_get_peerids_from_peerstore(ctx)
+        .await?
+        .into_iter()
+        .filter(|peer_id| !known.contains(peer_id))
+        .collect();
+    exchanged_addresses(&exchanged, &ctx.peer_enrs())
+}
+
+/// Addresses of the peers `peer_ids`: the dialable ones listed by their ENR when the bindings
+/// know it, a bare `/p2p/{peer_id}`, dialable once the node knows the peer, otherwise
+fn exchanged_addresses(peer_ids: &[String], enrs: &[Enr]) -> Result<Vec<Multiaddr>> {
+    let mut addresses = Vec::new();
+    for peer_id in peer_ids {
+        match enrs.iter().find(|enr| enr_peer_id(enr) == *peer_id) {
+            Some(enr) => addresses.extend(enr_multiaddrs(enr)),
+            None => addresses.push(
+                format!("/p2p/{peer_id}")
+                    .parse()
+                    .map_err(|e| format!("invalid peer id {peer_id}: {e}"))?,
+            ),
+        }
+    }
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{exchanged_addresses, DiscoveryMechanism, DiscoveryStatus};
+    use crate::node::config::WakuNodeConfig;
+    use crate::node::dns::{enr_peer_id, Enr};
+
+    #[test]
+    fn enabled_as_configured() {
+        let status = DiscoveryStatus::configured(&WakuNodeConfig {
+            dns_discovery: Some(true),
+            rendezvous: Some(false),
+            ..Default::default()
+        });
+
+        assert!(status.dns.enabled);
+        assert!(!status.discv5.enabled);
+        assert!(!status.rendezvous.enabled);
+        assert!(status.peer_exchange.enabled);
+        assert_eq!(status.dns.last_success, None);
+    }
+
+    #[test]
+    fn successes_recorded() {
+        let mut status = DiscoveryStatus::configured(&WakuNodeConfig {
+            dns_discovery: Some(true),
+            ..Default::default()
+        });
+
+        status.record_success(DiscoveryMechanism::Dns, 3);
+        status.record_success(DiscoveryMechanism::Dns, 2);
+
+        let dns = status.get(DiscoveryMechanism::Dns);
+        assert!(dns.last_success.is_some());
+        assert_eq!(dns.peers_found, 5);
+        assert_eq!(status.discv5.last_success, None);
+    }
+
+    #[test]
+    fn exchanged_peers_addressed_by_their_enr() {
+        // Peer of cluster 1 listening on 10.0.0.7:60000
+        let enr: Enr = "enr:-Ja4QHMgOFLhm1PRJdZSoqR5YgTUqkgThhQNmR-fcKknqGbWbvnrF4ScYlNcd5F3Gg5pykksV6CgFkiXl5HXGsREn-wBgmlkgnY0gmlwhAoAAAeCcnOHAAECAAIABYlzZWNwMjU2azGhApicC3bLVjlx_cm-8x7AbDVg8ySdbunl2DxXYlWW4F9vg3RjcILqYIV3YWt1MgM".parse().unwrap();
+        let unknown = "16Uiu2HAmVFXtAfSj4EiR7mL2KvL4EE2wztuQgUSBoj2Jx2KeXFLN".to_string();
+
+        let addresses = exchanged_addresses(&[enr_peer_id(&enr), unknown.clone()], &[enr]).unwrap();
+        let addresses: Vec<String> = addresses.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            addresses,
+            vec![
+                "/ip4/10.0.0.7/tcp/60000/p2p/16Uiu2HAm5hPxT7s3TA2f4b4YjAPzS7yLRSgdj87ysDZ6NAXMiRuY"
+                    .to_string(),
+                format!("/p2p/{unknown}"),
+            ]
+        );
+        assert!(exchanged_addresses(&["not a peer id".to_string()], &[]).is_err());
+    }
+}
@@ -0,0 +1,431 @@
+//! Peer discovery through [EIP-1459](https://eips.ethereum.org/EIPS/eip-1459) DNS trees
+//!
+//! libwaku walks the tree with its own resolver, querying a given nameserver. With a custom
+//! [`DnsResolver`], e.g. a DNS-over-HTTPS client or a canned one in tests, the bindings walk it
+//! instead, as libwaku can't be given one.
+
+// std
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::time::Duration;
+// crates
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use data_encoding::BASE32_NOPAD;
+use enr::secp256k1 as enr_secp256k1;
+use enr::EnrPublicKey;
+use futures::future::BoxFuture;
+use multiaddr::{multihash::Multihash, Multiaddr, Protocol};
+use sha3::{Digest, Keccak256};
+use url::Url;
+// internal
+use crate::general::libwaku_response::{handle_response, LibwakuResponse};
+use crate::general::Result;
+use crate::handle_ffi_call;
+use crate::node::context::WakuNodeContext;
+use crate::node::discovery::DiscoveryMechanism;
+use crate::node::peers::address_peer_id;
+
+/// Nameserver libwaku queries when none is given
+pub const DEFAULT_DNS_SERVER: &str = "1.1.1.1";
+
+/// Time given to a discovery when none is given
+const DEFAULT_DNS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest number of tree entries resolved for a single discovery
+const MAX_TREE_ENTRIES: usize = 1000;
+
+pub(crate) type Enr = enr::Enr<enr_secp256k1::SecretKey>;
+
+/// Node found through DNS discovery
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsInfo {
+    pub peer_id: String,
+    /// Dialable addresses of the node, each with its `/p2p/{peer_id}`
+    pub addresses: Vec<Multiaddr>,
+    /// `enr:` encoded ENR of the node, when known to the bindings. libwaku only reports
+    /// addresses, the ENRs are the ones found through a custom [`DnsResolver`] or given to
+    /// [`remember_peer_enr`](crate::WakuNodeHandle::remember_peer_enr)
+    pub enr: Option<String>,
+}
+
+/// Resolver of the TXT records a DNS discovery tree is made of
+pub trait DnsResolver: Sync {
+    /// TXT records of `name`, each one with its character-strings concatenated
+    fn resolve_txt<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<String>>>;
+}
+
+/// Nodes listed in the DNS discovery tree at `enrtree_url`, found within `timeout`.
+/// libwaku resolves the tree through the `nameserver` unless a `resolver` is given
+pub async fn waku_dns_discovery(
+    ctx: &WakuNodeContext,
+    enrtree_url: &Url,
+    nameserver: Option<&str>,
+    resolver: Option<&dyn DnsResolver>,
+    timeout: Option<Duration>,
+) -> Result<Vec<DnsInfo>> {
+    let timeout = timeout.unwrap_or(DEFAULT_DNS_TIMEOUT);
+    let nodes = match resolver {
+        Some(resolver) => {
+            // No FFI call is made, the walk can be dropped at any point
+            let enrs = tokio::time::timeout(timeout, walk_enr_tree(enrtree_url.as_str(), resolver))
+                .await
+                .map_err(|_| format!("dns discovery not done within {timeout:?}"))??;
+            let nodes = enrs.iter().map(enr_info).collect();
+            enrs.into_iter().for_each(|enr| ctx.remember_peer_enr(enr));
+            nodes
+        }
+        None => {
+            let addresses = libwaku_dns_discovery(ctx, enrtree_url, nameserver, timeout).await?;
+            group_by_node(addresses, &ctx.peer_enrs())?
+        }
+    };
+    ctx.discovery_succeeded(DiscoveryMechanism::Dns, nodes.len());
+    Ok(nodes)
+}
+
+/// Addresses of the peers listed in the DNS discovery tree at `enrtree_url`, resolved by
+/// libwaku through the `nameserver`
+async fn libwaku_dns_discovery(
+    ctx: &WakuNodeContext,
+    enrtree_url: &Url,
+    nameserver: Option<&str>,
+    timeout: Duration,
+) -> Result<Vec<Multiaddr>> {
+    let enrtree_url = CString::new(enrtree_url.as_str())
+        .expect("CString should build properly from the enrtree url");
+    let nameserver = CString::new(nameserver.unwrap_or(DEFAULT_DNS_SERVER))
+        .expect("CString should build properly from the nameserver");
+
+    let peers: String = handle_ffi_call!(
+        waku_sys::waku_dns_discovery,
+        handle_response,
+        ctx.ptr_guard()?,
+        enrtree_url.as_ptr(),
+        nameserver.as_ptr(),
+        timeout.as_millis().try_into().unwrap_or(i32::MAX)
+    )?;
+    // A json array of multiaddresses, each with its `/p2p/{peer_id}`
+    serde_json::from_str::<Vec<String>>(&peers)
+        .map_err(|e| format!("invalid dns discovery response {peers}: {e}"))?
+        .iter()
+        .map(|peer| {
+            peer.parse()
+                .map_err(|e| format!("invalid discovered address {peer}: {e}").into())
+        })
+        .collect()
+}
+
+/// `addresses` grouped by the node they belong to, along with its ENR among `enrs`
+fn group_by_node(addresses: Vec<Multiaddr>, enrs: &[Enr]) -> Result<Vec<DnsInfo>> {
+    let mut nodes: Vec<DnsInfo> = Vec::new();
+    for address in addresses {
+        let peer_id = address_peer_id(&address)
+            .ok_or_else(|| format!("discovered address {address} has no peer id"))?;
+        match nodes.iter_mut().find(|node| node.peer_id == peer_id) {
+            Some(node) => node.addresses.push(address),
+            None => nodes.push(DnsInfo {
+                enr: enrs
+                    .iter()
+                    .find(|enr| enr_peer_id(enr) == peer_id)
+                    .map(Enr::to_base64),
+                peer_id,
+                addresses: vec![address],
+            }),
+        }
+    }
+    Ok(nodes)
+}
+
+/// Node described by `enr`, with an address per ip and tcp port pair of the ENR
+fn enr_info(enr: &Enr) -> DnsInfo {
+    DnsInfo {
+        peer_id: enr_peer_id(enr),
+        addresses: enr_multiaddrs(enr),
+        enr: Some(enr.to_base64()),
+    }
+}
+
+/// ENRs listed in the DNS discovery tree at `enrtree_url`.
+/// The root signature and the hash of every entry are checked, links to other trees aren't
+/// followed
+async fn walk_enr_tree(enrtree_url: &str, resolver: &dyn DnsResolver) -> Result<Vec<Enr>> {
+    let (public_key, domain) = parse_enrtree_url(enrtree_url)?;
+    let root = resolve_entry(resolver, &domain, "enrtree-root:v1").await?;
+    let enr_root = verify_root(&root, &public_key)?;
+
+    let mut pending = vec![enr_root];
+    let mut seen = HashSet::new();
+    let mut peers = Vec::new();
+    while let Some(hash) = pending.pop() {
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+        if seen.len() > MAX_TREE_ENTRIES {
+            return Err(
+                format!("dns tree at {domain} has more than {MAX_TREE_ENTRIES} entries").into(),
+            );
+        }
+
+        let entry = resolve_entry(resolver, &format!("{hash}.{domain}"), "enr").await?;
+        if entry_hash(&entry) != hash {
+            return Err(format!("dns tree entry {hash}.{domain} doesn't match its hash").into());
+        }
+
+        if let Some(children) = entry.strip_prefix("enrtree-branch:") {
+            pending.extend(
+                children
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|child| !child.is_empty())
+                    .map(str::to_string)
+                    .rev(),
+            );
+        } else if entry.starts_with("enr:") {
+            let enr: Enr = entry
+                .parse()
+                .map_err(|e| format!("invalid enr at {hash}.{domain}: {e}"))?;
+            peers.push(enr);
+        }
+        // enrtree:// links to other trees are left out
+    }
+
+    Ok(peers)
+}
+
+/// Public key and domain of an `enrtree://{base32 public key}@{domain}` url
+fn parse_enrtree_url(url: &str) -> Result<(enr_secp256k1::PublicKey, String)> {
+    let (public_key, domain) = url
+        .strip_prefix("enrtree://")
+        .and_then(|url| url.split_once('@'))
+        .ok_or_else(|| format!("invalid enrtree url {url}"))?;
+    let public_key = BASE32_NOPAD
+        .decode(public_key.as_bytes())
+        .ok()
+        .and_then(|key| enr_secp256k1::PublicKey::from_slice(&key).ok())
+        .ok_or_else(|| format!("invalid public key in enrtree url {url}"))?;
+    Ok((public_key, domain.to_string()))
+}
+
+/// First TXT record of `name` starting with `prefix`, other records may be unrelated to the tree
+async fn resolve_entry(resolver: &dyn DnsResolver, name: &str, prefix: &str) -> Result<String> {
+    resolver
+        .resolve_txt(name)
+        .await?
+        .into_iter()
+        .find(|record| record.starts_with(prefix))
+        .ok_or_else(|| format!("no dns tree entry at {name}").into())
+}
+
+/// Check the signature of `enrtree-root:v1 e={enr root} l={link root} seq={n} sig={sig}`
+/// against `public_key`, returning the hash of the enr subtree root
+fn verify_root(root: &str, public_key: &enr_secp256k1::PublicKey) -> Result<String> {
+    let (signed, signature) = root
+        .rsplit_once(" sig=")
+        .ok_or_else(|| format!("unsigned dns tree root {root}"))?;
+    let enr_root = signed
+        .split(' ')
+        .find_map(|field| field.strip_prefix("e="))
+        .ok_or_else(|| format!("dns tree root without enr root {root}"))?;
+
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature.trim())
+        .map_err(|e| format!("invalid dns tree root signature: {e}"))?;
+    // Signed like ENRs are, the 65th byte is a recovery id unneeded with a known public key
+    if !public_key.verify_v4(signed.as_bytes(), signature.get(..64).unwrap_or_default()) {
+        return Err("dns tree root signature doesn't match the enrtree public key".into());
+    }
+
+    Ok(enr_root.to_string())
+}
+
+/// Subdomain of a tree entry: its keccak256 digest truncated to 16 bytes, base32 encoded
+fn entry_hash(entry: &str) -> String {
+    BASE32_NOPAD.encode(&Keccak256::digest(entry.as_bytes())[..16])
+}
+
+/// libp2p peer id of the peer described by `enr`: identity multihash of its protobuf encoded
+/// secp256k1 public key
+fn enr_peer_multihash(enr: &Enr) -> Multihash {
+    let mut key = vec![0x08, 0x02, 0x12, 0x21];
+    key.extend_from_slice(&enr.public_key().serialize());
+    let mut peer_id = vec![0x00, key.len() as u8];
+    peer_id.extend(key);
+    Multihash::from_bytes(&peer_id).expect("identity multihash of a public key")
+}
+
+/// Base58 peer id of the peer described by `enr`
+pub(crate) fn enr_peer_id(enr: &Enr) -> String {
+    Multiaddr::empty()
+        .with(Protocol::P2p(enr_peer_multihash(enr)))
+        .to_string()
+        .trim_start_matches("/p2p/")
+        .to_string()
+}
+
+/// Dialable addresses of the peer described by `enr`, with its `/p2p/{peer_id}`
+pub(crate) fn enr_multiaddrs(enr: &Enr) -> Vec<Multiaddr> {
+    let peer_id = enr_peer_multihash(enr);
+
+    let ip4 = enr.ip4().zip(enr.tcp4()).map(|(ip, port)| {
+        Multiaddr::empty()
+            .with(Protocol::Ip4(ip))
+            .with(Protocol::Tcp(port))
+    });
+    let ip6 = enr.ip6().zip(enr.tcp6()).map(|(ip, port)| {
+        Multiaddr::empty()
+            .with(Protocol::Ip6(ip))
+            .with(Protocol::Tcp(port))
+    });
+    ip4.into_iter()
+        .chain(ip6)
+        .map(|address| address.with(Protocol::P2p(peer_id)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        enr_info, enr_multiaddrs, entry_hash, group_by_node, walk_enr_tree, DnsInfo, DnsResolver,
+        Enr,
+    };
+    use crate::general::Result;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use data_encoding::BASE32_NOPAD;
+    use enr::secp256k1::{SecretKey, SECP256K1};
+    use enr::EnrKey;
+    use futures::future::{ready, BoxFuture, FutureExt};
+    use multiaddr::Multiaddr;
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+
+    /// Canned TXT records by name
+    struct MockResolver(HashMap<String, Vec<String>>);
+
+    impl DnsResolver for MockResolver {
+        fn resolve_txt<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<String>>> {
+            ready(
+                self.0
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| format!("NXDOMAIN {name}").into()),
+            )
+            .boxed()
+        }
+    }
+
+    fn peer_enr(byte: u8, port: u16) -> Enr {
+        let key = SecretKey::from_slice(&[byte; 32]).unwrap();
+        enr::EnrBuilder::new("v4")
+            .ip4(Ipv4Addr::new(10, 0, 0, byte))
+            .tcp4(port)
+            .build(&key)
+            .unwrap()
+    }
+
+    /// Tree signed with `tree_key`: a root, a branch and two leaves, served under `domain`
+    fn signed_tree(tree_key: &SecretKey, domain: &str, enrs: &[Enr]) -> MockResolver {
+        let mut records = HashMap::new();
+        let leaves: Vec<String> = enrs.iter().map(|enr| enr.to_base64()).collect();
+        for leaf in &leaves {
+            records.insert(format!("{}.{domain}", entry_hash(leaf)), vec![leaf.clone()]);
+        }
+        let branch = format!(
+            "enrtree-branch:{}",
+            leaves
+                .iter()
+                .map(|leaf| entry_hash(leaf))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        records.insert(
+            format!("{}.{domain}", entry_hash(&branch)),
+            vec![branch.clone()],
+        );
+
+        let signed = format!(
+            "enrtree-root:v1 e={} l=FDXN3SN67NA5DKA4J2GOK7BVQI seq=1",
+            entry_hash(&branch)
+        );
+        let signature = tree_key.sign_v4(signed.as_bytes()).unwrap();
+        let root = format!("{signed} sig={}", URL_SAFE_NO_PAD.encode(signature));
+        records.insert(domain.to_string(), vec!["v=spf1 -all".to_string(), root]);
+
+        MockResolver(records)
+    }
+
+    fn enrtree_url(tree_key: &SecretKey, domain: &str) -> String {
+        let public_key = tree_key.public_key(SECP256K1).serialize();
+        format!("enrtree://{}@{domain}", BASE32_NOPAD.encode(&public_key))
+    }
+
+    async fn discover(enrtree_url: &str, resolver: &MockResolver) -> Result<Vec<DnsInfo>> {
+        let enrs = walk_enr_tree(enrtree_url, resolver).await?;
+        Ok(enrs.iter().map(enr_info).collect())
+    }
+
+    #[tokio::test]
+    async fn peers_extracted_from_tree() {
+        let tree_key = SecretKey::from_slice(&[7; 32]).unwrap();
+        let enrs = [peer_enr(1, 60000), peer_enr(2, 60001)];
+        let resolver = signed_tree(&tree_key, "nodes.example.org", &enrs);
+
+        let nodes = discover(&enrtree_url(&tree_key, "nodes.example.org"), &resolver)
+            .await
+            .unwrap();
+
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes[0].addresses[0]
+            .to_string()
+            .starts_with("/ip4/10.0.0.1/tcp/60000/p2p/16Uiu2"));
+        assert!(nodes[1].addresses[0]
+            .to_string()
+            .starts_with("/ip4/10.0.0.2/tcp/60001/p2p/16Uiu2"));
+        assert_eq!(nodes[0].enr, Some(enrs[0].to_base64()));
+    }
+
+    #[tokio::test]
+    async fn tree_signed_by_another_key_rejected() {
+        let tree_key = SecretKey::from_slice(&[7; 32]).unwrap();
+        let other_key = SecretKey::from_slice(&[8; 32]).unwrap();
+        let resolver = signed_tree(&other_key, "nodes.example.org", &[peer_enr(1, 60000)]);
+
+        let peers = discover(&enrtree_url(&tree_key, "nodes.example.org"), &resolver).await;
+
+        assert!(peers.is_err());
+    }
+
+    #[tokio::test]
+    async fn tampered_entry_rejected() {
+        let tree_key = SecretKey::from_slice(&[7; 32]).unwrap();
+        let enr = peer_enr(1, 60000);
+        let mut resolver = signed_tree(&tree_key, "nodes.example.org", std::slice::from_ref(&enr));
+        let leaf_name = format!("{}.nodes.example.org", entry_hash(&enr.to_base64()));
+        resolver
+            .0
+            .insert(leaf_name, vec![peer_enr(3, 60000).to_base64()]);
+
+        let peers = discover(&enrtree_url(&tree_key, "nodes.example.org"), &resolver).await;
+
+        assert!(peers.is_err());
+    }
+
+    #[test]
+    fn addresses_grouped_by_node() {
+        let enr = peer_enr(1, 60000);
+        let other = peer_enr(2, 60001);
+        let addresses: Vec<Multiaddr> = enr_multiaddrs(&enr)
+            .into_iter()
+            .chain(enr_multiaddrs(&other))
+            .chain(enr_multiaddrs(&enr))
+            .collect();
+
+        let nodes = group_by_node(addresses, std::slice::from_ref(&enr)).unwrap();
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].addresses.len(), 2);
+        assert_eq!(nodes[0].enr, Some(enr.to_base64()));
+        assert_eq!(nodes[1].addresses, enr_multiaddrs(&other));
+        assert_eq!(nodes[1].enr, None);
+        assert!(group_by_node(vec!["/ip4/10.0.0.1/tcp/60000".parse().unwrap()], &[]).is_err());
+    }
+}
@@ -0,0 +1,330 @@
+//! Bounded stream of the node events, for consumers slower than the event rate.
+//! Every way of consuming events apart from the event callback goes through it
+
+// std
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+// crates
+use tokio::sync::{mpsc, Notify};
+// internal
+use crate::node::context::WakuNodeContext;
+use crate::node::events::{ConnectionChangeEvent, WakuEvent};
+
+/// Connection changes kept by [`connection_events`](crate::WakuNodeHandle::connection_events)
+/// until received, the oldest ones being dropped first
+pub const CONNECTION_EVENTS_CAPACITY: usize = 1024;
+
+/// What happens to a new event when the [`EventStream`] is full.
+/// Events are queued from the libwaku thread, which also delivers the responses of the node
+/// calls, so it never waits for the consumer: a consumer awaiting a node call would deadlock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drop the oldest queued event to make room, counting it in [`EventStream::dropped`]
+    DropOldest,
+    /// Wait for the consumer to make room, so no event is lost. The libwaku thread can't be
+    /// held back, so the events it delivers meanwhile wait in a task forwarding them to the
+    /// stream, which has to be created within a tokio runtime
+    Block,
+}
+
+struct EventQueue<T> {
+    events: Mutex<VecDeque<T>>,
+    capacity: usize,
+    dropped: AtomicU64,
+    /// Set once either end is gone
+    closed: AtomicBool,
+    /// Wakes the consumer when an event is queued or the producer is gone
+    queued: Notify,
+    /// Wakes a waiting producer when room is made or the consumer is gone
+    room: Notify,
+}
+
+impl<T> EventQueue<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            queued: Notify::new(),
+            room: Notify::new(),
+        }
+    }
+
+    /// Queue `event`, dropping the oldest ones to make room, returning `false` once the consumer
+    /// is gone
+    fn push(&self, event: T) -> bool {
+        if self.closed.load(Ordering::Acquire) {
+            return false;
+        }
+        let mut events = self.events.lock().expect("event queue lock");
+        while events.len() >= self.capacity {
+            events.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        events.push_back(event);
+        drop(events);
+        self.queued.notify_one();
+        true
+    }
+
+    /// Queue `event` once there is room for it, returning `false` once the consumer is gone
+    async fn push_waiting(&self, event: T) -> bool {
+        loop {
+            if self.closed.load(Ordering::Acquire) {
+                return false;
+            }
+            let has_room = {
+                let events = self.events.lock().expect("event queue lock");
+                events.len() < self.capacity
+            };
+            if has_room {
+                // The consumer only takes events out, so the room is still there
+                self.events
+                    .lock()
+                    .expect("event queue lock")
+                    .push_back(event);
+                self.queued.notify_one();
+                return true;
+            }
+            self.room.notified().await;
+        }
+    }
+
+    fn pop(&self) -> Option<T> {
+        let event = self.events.lock().expect("event queue lock").pop_front();
+        if event.is_some() {
+            self.room.notify_one();
+        }
+        event
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.queued.notify_one();
+        self.room.notify_one();
+    }
+}
+
+/// Closes the queue when the listener feeding it is dropped along with the node
+struct ProducerGuard<T>(Arc<EventQueue<T>>);
+
+impl<T> Drop for ProducerGuard<T> {
+    fn drop(&mut self) {
+        self.0.close()
+    }
+}
+
+/// Events of the node, from the stream creation on, queued up to a fixed capacity
+/// as per an [`OverflowPolicy`]
+pub struct EventStream<T = WakuEvent> {
+    queue: Arc<EventQueue<T>>,
+}
+
+impl<T> EventStream<T> {
+    fn new(capacity: usize) -> (Self, ProducerGuard<T>) {
+        let queue = Arc::new(EventQueue::new(capacity));
+        (
+            Self {
+                queue: queue.clone(),
+            },
+            ProducerGuard(queue),
+        )
+    }
+
+    /// Next event, `None` once the node is gone and every queued event was received
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            if let Some(event) = self.queue.pop() {
+                return Some(event);
+            }
+            if self.queue.closed.load(Ordering::Acquire) {
+                return self.queue.pop();
+            }
+            self.queue.queued.notified().await;
+        }
+    }
+
+    /// Oldest queued event, without waiting, `None` if there is none yet
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.queue.pop()
+    }
+
+    /// Number of events waiting to be received
+    pub fn queue_depth(&self) -> usize {
+        self.queue.events.lock().expect("event queue lock").len()
+    }
+
+    /// Number of events dropped by the [`OverflowPolicy::DropOldest`] policy
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Drop for EventStream<T> {
+    fn drop(&mut self) {
+        self.queue.close()
+    }
+}
+
+/// Stream of the node events, from now on. The listener feeding it is removed
+/// once the stream is dropped
+pub fn waku_event_stream(
+    ctx: &WakuNodeContext,
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> EventStream {
+    waku_event_stream_of(ctx, capacity, policy, |event| Some(event.clone()))
+}
+
+/// Connection changes of the node, from now on, up to [`CONNECTION_EVENTS_CAPACITY`]
+/// waiting to be received
+pub fn waku_connection_events(ctx: &WakuNodeContext) -> EventStream<ConnectionChangeEvent> {
+    waku_event_stream_of(
+        ctx,
+        CONNECTION_EVENTS_CAPACITY,
+        OverflowPolicy::DropOldest,
+        |event| match event {
+            WakuEvent::ConnectionChange(evt) => Some(evt.clone()),
+            _ => None,
+        },
+    )
+}
+
+/// Stream of the node events `select` keeps, from now on
+fn waku_event_stream_of<T: Send + 'static>(
+    ctx: &WakuNodeContext,
+    capacity: usize,
+    policy: OverflowPolicy,
+    select: impl Fn(&WakuEvent) -> Option<T> + Send + 'static,
+) -> EventStream<T> {
+    let (stream, producer) = EventStream::new(capacity);
+    match policy {
+        OverflowPolicy::DropOldest => ctx.add_event_listener(move |event| {
+            // Removed once the stream is dropped, whether or not the event is kept
+            let open = match select(event) {
+                Some(event) => producer.0.push(event),
+                None => !producer.0.closed.load(Ordering::Acquire),
+            };
+            if open {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        }),
+        OverflowPolicy::Block => {
+            let (pending, mut forwarded) = mpsc::unbounded_channel();
+            // Ends once the stream or the listener is dropped, closing the queue on its way out
+            tokio::spawn(async move {
+                while let Some(event) = forwarded.recv().await {
+                    if !producer.0.push_waiting(event).await {
+                        break;
+                    }
+                }
+            });
+            ctx.add_event_listener(move |event| {
+                let open = match select(event) {
+                    Some(event) => pending.send(event).is_ok(),
+                    None => !pending.is_closed(),
+                };
+                if open {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            })
+        }
+    };
+    stream
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventStream;
+    use crate::node::events::WakuEvent;
+
+    fn event(i: usize) -> WakuEvent {
+        WakuEvent::Unrecognized(serde_json::json!(i))
+    }
+
+    fn index(event: WakuEvent) -> u64 {
+        match event {
+            WakuEvent::Unrecognized(value) => value.as_u64().unwrap(),
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_newest_events() {
+        let (mut stream, producer) = EventStream::new(4);
+        for i in 0..10 {
+            assert!(producer.0.push(event(i)));
+        }
+        assert_eq!(stream.queue_depth(), 4);
+        assert_eq!(stream.dropped(), 6);
+
+        drop(producer);
+        let mut received = Vec::new();
+        while let Some(event) = stream.recv().await {
+            received.push(index(event));
+        }
+        assert_eq!(received, vec![6, 7, 8, 9]);
+    }
+
+    #[tokio::test]
+    async fn ends_once_producer_gone() {
+        let (mut stream, producer) = EventStream::new(2);
+        assert!(producer.0.push(event(0)));
+        drop(producer);
+        assert_eq!(stream.recv().await.map(index), Some(0));
+        assert!(stream.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn waiting_producer_loses_nothing() {
+        let (mut stream, producer) = EventStream::new(2);
+        let flood = tokio::spawn(async move {
+            for i in 0..100 {
+                assert!(producer.0.push_waiting(event(i)).await);
+            }
+        });
+
+        let mut received = Vec::new();
+        while let Some(event) = stream.recv().await {
+            assert!(stream.queue_depth() <= 2);
+            received.push(index(event));
+        }
+        flood.await.unwrap();
+        assert_eq!(received, (0..100).collect::<Vec<_>>());
+        assert_eq!(stream.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn waiting_producer_released_when_stream_dropped() {
+        let (stream, producer) = EventStream::<WakuEvent>::new(1);
+        assert!(producer.0.push(event(0)));
+        let waiting = tokio::spawn(async move { producer.0.push_waiting(event(1)).await });
+        tokio::task::yield_now().await;
+        drop(stream);
+        assert!(!waiting.await.unwrap());
+    }
+
+    #[test]
+    fn try_recv_does_not_wait() {
+        let (mut stream, producer) = EventStream::new(2);
+        assert!(stream.try_recv().is_none());
+        assert!(producer.0.push(event(0)));
+        assert_eq!(stream.try_recv().map(index), Some(0));
+        assert!(stream.try_recv().is_none());
+    }
+
+    #[test]
+    fn producer_stopped_when_stream_dropped() {
+        let (stream, producer) = EventStream::<WakuEvent>::new(1);
+        assert!(producer.0.push(event(0)));
+        drop(stream);
+        assert!(!producer.0.push(event(1)));
+    }
+}
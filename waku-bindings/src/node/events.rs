@@ -4,18 +4,29 @@
 //! An example of an asynchronous event that might be emitted is receiving a message.
 //! When an event is emitted, this callback will be triggered receiving an [`WakuEvent`]
 
+// std
+use std::convert::Infallible;
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::str;
+use std::str::FromStr;
+use std::time::Duration;
 // crates
 use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
 // internal
-use crate::general::WakuMessage;
-use std::str;
+use crate::general::libwaku_response::LibwakuResponse;
+use crate::general::payload::{PayloadRegistry, TypedPayload};
+use crate::general::pubsubtopic::PubsubTopic;
+use crate::general::{Result, WakuMessage};
+use crate::node::context::{ListenerId, WakuNodeContext};
 
 use crate::MessageHash;
 
 /// Waku event
 /// For now just WakuMessage is supported
 #[non_exhaustive]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(tag = "eventType", rename_all = "camelCase")]
 pub enum WakuEvent {
     #[serde(rename = "message")]
@@ -27,46 +38,372 @@ pub enum WakuEvent {
     #[serde(rename = "connection_change")]
     ConnectionChange(ConnectionChangeEvent),
 
+    /// Emitted by the bindings, not libwaku, when the node stalls.
+    /// See [`WakuNodeHandle::set_stall_watchdog`](crate::node::WakuNodeHandle::set_stall_watchdog)
+    #[serde(rename = "node_stalled")]
+    NodeStalled(NodeStalledEvent),
+
+    /// A received message was dropped for carrying an invalid RLN proof.
+    /// libwaku drops such messages silently for now, without emitting this event: it is only
+    /// understood in advance of the upstream hook, until then such drops can't be observed
+    #[serde(rename = "rln_proof_invalid")]
+    RlnProofInvalid(RlnProofInvalidEvent),
+
     Unrecognized(serde_json::Value),
 }
 
 /// Type of `event` field for a `message` event
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct WakuMessageEvent {
     /// The pubsub topic on which the message was received
     pub pubsub_topic: String,
     /// The message hash
     pub message_hash: MessageHash,
-    /// The message in [`WakuMessage`] format
+    /// The message in [`WakuMessage`] format, empty if missing from the event
+    #[serde(default)]
     pub waku_message: WakuMessage,
+    /// Peer the message was received from, when the event names it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_id: Option<String>,
+}
+
+impl WakuMessageEvent {
+    /// The pubsub topic on which the message was received, as a [`PubsubTopic`]
+    pub fn pubsub_topic_parsed(&self) -> Result<PubsubTopic> {
+        Ok(self.pubsub_topic.parse()?)
+    }
+
+    /// `(cluster_id, shard)` the message was received on, `None` for named pubsub topics
+    pub fn shard(&self) -> Option<(u16, u16)> {
+        self.pubsub_topic_parsed().ok()?.shard()
+    }
+
+    /// The message payload, decoded as per the kind `registry` has for its content topic
+    pub fn decoded_payload(&self, registry: &PayloadRegistry) -> Result<TypedPayload> {
+        registry.decode(&self.waku_message)
+    }
 }
 
 /// Type of `event` field for a `topic health` event
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct TopicHealthEvent {
     /// The pubsub topic on which the message was received
     pub pubsub_topic: String,
     /// The message hash
-    pub topic_health: String,
+    pub topic_health: TopicHealth,
+}
+
+/// Health of a relay topic, as reported by nwaku
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum TopicHealth {
+    /// No peers to exchange messages on the topic with
+    UnHealthy,
+    /// Enough peers to exchange messages, below the target mesh size
+    MinimallyHealthy,
+    /// Mesh of the topic fully formed
+    SufficientlyHealthy,
+    /// Health unknown to the bindings, as reported by a newer nwaku
+    Unknown(String),
+}
+
+impl FromStr for TopicHealth {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self::from(s.to_string()))
+    }
+}
+
+impl From<String> for TopicHealth {
+    fn from(health: String) -> Self {
+        match health.as_str() {
+            "UnHealthy" => Self::UnHealthy,
+            "MinimallyHealthy" => Self::MinimallyHealthy,
+            "SufficientlyHealthy" => Self::SufficientlyHealthy,
+            _ => Self::Unknown(health),
+        }
+    }
+}
+
+impl From<TopicHealth> for String {
+    fn from(health: TopicHealth) -> Self {
+        match health {
+            TopicHealth::UnHealthy => "UnHealthy".to_string(),
+            TopicHealth::MinimallyHealthy => "MinimallyHealthy".to_string(),
+            TopicHealth::SufficientlyHealthy => "SufficientlyHealthy".to_string(),
+            TopicHealth::Unknown(health) => health,
+        }
+    }
+}
+
+impl TopicHealth {
+    /// Whether messages can be exchanged on the topic, that is at least
+    /// [`TopicHealth::MinimallyHealthy`]. An [unknown](TopicHealth::Unknown) health isn't
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, Self::MinimallyHealthy | Self::SufficientlyHealthy)
+    }
+}
+
+/// Fraction of `healths` that are [healthy](TopicHealth::is_healthy), `1.0` if there are none
+pub(crate) fn healthy_ratio<'a>(healths: impl IntoIterator<Item = &'a TopicHealth>) -> f64 {
+    let (healthy, total) = healths
+        .into_iter()
+        .fold((0usize, 0usize), |(healthy, total), health| {
+            (healthy + health.is_healthy() as usize, total + 1)
+        });
+    if total == 0 {
+        return 1.0;
+    }
+    healthy as f64 / total as f64
 }
 
 /// Type of `event` field for a `connection change` event
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ConnectionChangeEvent {
-    /// The pubsub topic on which the message was received
+    /// The peer whose connection changed
     pub peer_id: String,
-    /// The message hash
-    pub peer_event: String,
+    /// Whether the peer connected or disconnected
+    pub peer_event: PeerConnectionEvent,
+}
+
+/// Change in the connection to a peer, as reported by nwaku
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum PeerConnectionEvent {
+    Joined,
+    Left,
+    /// Change unknown to the bindings, as reported by a newer nwaku
+    Unknown(String),
+}
+
+impl From<String> for PeerConnectionEvent {
+    fn from(event: String) -> Self {
+        match event.as_str() {
+            "Joined" => Self::Joined,
+            "Left" => Self::Left,
+            _ => Self::Unknown(event),
+        }
+    }
+}
+
+impl From<PeerConnectionEvent> for String {
+    fn from(event: PeerConnectionEvent) -> Self {
+        match event {
+            PeerConnectionEvent::Joined => "Joined".to_string(),
+            PeerConnectionEvent::Left => "Left".to_string(),
+            PeerConnectionEvent::Unknown(event) => event,
+        }
+    }
+}
+
+/// Type of `event` field for a `rln proof invalid` event
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RlnProofInvalidEvent {
+    /// Hash of the dropped message
+    pub message_hash: MessageHash,
+    /// Why the proof was rejected, i.e. an invalid root or a reused nullifier
+    pub reason: String,
+}
+
+/// Type of `event` field for a `node stalled` event
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStalledEvent {
+    /// Time libwaku went without calling back, in milliseconds
+    pub stalled_for_millis: u64,
+}
+
+impl NodeStalledEvent {
+    /// Time libwaku went without calling back
+    pub fn stalled_for(&self) -> Duration {
+        Duration::from_millis(self.stalled_for_millis)
+    }
+}
+
+/// Wait for the first received message matching `predicate`.
+/// The listener used to watch the events is removed once a message matches
+/// or after `timeout`, whichever happens first.
+pub async fn waku_await_message<F>(
+    ctx: &WakuNodeContext,
+    predicate: F,
+    timeout: Duration,
+) -> Result<WakuMessageEvent>
+where
+    F: Fn(&WakuMessageEvent) -> bool + Send + 'static,
+{
+    waku_message_waiter(ctx, predicate, timeout).await
+}
+
+/// Removes its listener once dropped, so a waiter dropped before being polled, as when the
+/// publish it awaits a reply to fails, does not leave the listener behind
+struct ListenerGuard<'a> {
+    ctx: &'a WakuNodeContext,
+    id: ListenerId,
+}
+
+impl Drop for ListenerGuard<'_> {
+    fn drop(&mut self) {
+        self.ctx.remove_event_listener(self.id);
+    }
+}
+
+/// Same as [`waku_await_message`], except the listener is registered before the returned
+/// future is first polled, so messages received in between are considered
+pub(crate) fn waku_message_waiter<F>(
+    ctx: &WakuNodeContext,
+    predicate: F,
+    timeout: Duration,
+) -> impl Future<Output = Result<WakuMessageEvent>> + '_
+where
+    F: Fn(&WakuMessageEvent) -> bool + Send + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    let mut sender = Some(sender);
+    let listener = ListenerGuard {
+        ctx,
+        id: ctx.add_event_listener(move |event| match event {
+            WakuEvent::WakuMessage(evt) if predicate(evt) => {
+                if let Some(sender) = sender.take() {
+                    // The receiver is gone only if the wait already timed out
+                    let _ = sender.send(evt.clone());
+                }
+                ControlFlow::Break(())
+            }
+            _ => ControlFlow::Continue(()),
+        }),
+    };
+
+    async move {
+        let result = tokio::time::timeout(timeout, receiver).await;
+        drop(listener);
+
+        match result {
+            Ok(Ok(evt)) => Ok(evt),
+            Ok(Err(_)) => Err("message listener dropped before a match".into()),
+            Err(_) => Err(format!("no matching message received within {timeout:?}").into()),
+        }
+    }
+}
+
+/// Event callback handing `closure` every event along with its json as received from libwaku.
+/// Events of a type unknown to the bindings are [`WakuEvent::Unrecognized`], responses which
+/// aren't json events are skipped
+pub(crate) fn raw_event_callback<F>(mut closure: F) -> impl FnMut(LibwakuResponse)
+where
+    F: FnMut(WakuEvent, &str),
+{
+    move |response| {
+        let LibwakuResponse::Success(Some(json)) = response else {
+            return;
+        };
+        let event = match serde_json::from_str::<WakuEvent>(&json) {
+            Ok(event) => event,
+            Err(_) => match serde_json::from_str(&json) {
+                Ok(value) => WakuEvent::Unrecognized(value),
+                Err(_) => {
+                    log::debug!("dropping unparsable event {json}");
+                    return;
+                }
+            },
+        };
+        closure(event, &json)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{healthy_ratio, raw_event_callback, PeerConnectionEvent, TopicHealth};
+    use crate::general::libwaku_response::LibwakuResponse;
+    use crate::general::pubsubtopic::PubsubTopic;
     use crate::WakuEvent;
     use crate::WakuEvent::{ConnectionChange, RelayTopicHealthChange};
 
+    #[test]
+    fn raw_events_keep_their_json() {
+        let received = std::sync::Mutex::new(Vec::new());
+        let mut callback = raw_event_callback(|event, json: &str| {
+            received.lock().unwrap().push((event, json.to_string()))
+        });
+
+        let known = r#"{"eventType":"node_stalled","stalledForMillis":1200}"#;
+        let unknown = r#"{"eventType":"shiny_new_event", "detail": [1, 2]}"#;
+        for json in [known, unknown, "not json"] {
+            callback(LibwakuResponse::Success(Some(json.to_string())));
+        }
+        callback(LibwakuResponse::Success(None));
+        drop(callback);
+
+        let received = received.into_inner().unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(matches!(received[0].0, WakuEvent::NodeStalled(_)));
+        assert_eq!(received[0].1, known);
+        match &received[1].0 {
+            WakuEvent::Unrecognized(value) => assert_eq!(value["eventType"], "shiny_new_event"),
+            other => panic!("unexpected event {other:?}"),
+        }
+        assert_eq!(received[1].1, unknown);
+    }
+
+    #[test]
+    fn healthy_ratio_of_topics() {
+        assert_eq!(
+            healthy_ratio(&[TopicHealth::MinimallyHealthy, TopicHealth::UnHealthy]),
+            0.5
+        );
+        assert_eq!(healthy_ratio(&[TopicHealth::SufficientlyHealthy]), 1.0);
+        assert_eq!(healthy_ratio(&[]), 1.0);
+    }
+
+    #[test]
+    fn message_event_payload_decoded_by_topic() {
+        use crate::general::contenttopic::WakuContentTopic;
+        use crate::general::payload::{PayloadKind, PayloadRegistry, TypedPayload};
+
+        // Payload is "Hi from 🦀!"
+        let s = "{\"eventType\":\"message\",\"messageHash\":[91, 70, 26, 8, 141, 232, 150, 200, 26, 206, 224, 175, 249, 74, 61, 140, 231, 126, 224, 160, 91, 80, 162, 65, 250, 171, 84, 149, 133, 110, 214, 101],\"pubsubTopic\":\"/waku/2/rs/16/32\",\"wakuMessage\":{\"payload\":\"SGkgZnJvbSDwn6aAIQ==\",\"contentTopic\":\"/toychat/2/huilong/proto\"}}";
+        let WakuEvent::WakuMessage(evt) = serde_json::from_str(s).unwrap() else {
+            panic!("expected a message event");
+        };
+        let topic: WakuContentTopic = "/toychat/2/huilong/proto".parse().unwrap();
+
+        let registry = PayloadRegistry::new().with_kind(topic, PayloadKind::Utf8);
+        assert_eq!(
+            evt.decoded_payload(&registry).unwrap(),
+            TypedPayload::Utf8("Hi from 🦀!".to_string())
+        );
+        assert_eq!(
+            evt.decoded_payload(&PayloadRegistry::new()).unwrap(),
+            TypedPayload::Binary("Hi from 🦀!".as_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn deserialize_minimal_message_event() {
+        let s = "{\"eventType\":\"message\",\"messageHash\":[91, 70, 26, 8, 141, 232, 150, 200, 26, 206, 224, 175, 249, 74, 61, 140, 231, 126, 224, 160, 91, 80, 162, 65, 250, 171, 84, 149, 133, 110, 214, 101],\"pubsubTopic\":\"/waku/2/rs/16/32\",\"wakuMessage\":{\"contentTopic\":\"/toychat/2/huilong/proto\"}}";
+        let evt: WakuEvent = serde_json::from_str(s).unwrap();
+        let WakuEvent::WakuMessage(evt) = evt else {
+            panic!("expected a message event, got {evt:?}");
+        };
+        assert_eq!(
+            evt.waku_message.content_topic.to_string(),
+            "/toychat/2/huilong/proto"
+        );
+        assert!(evt.waku_message.payload().is_empty());
+        assert!(evt.waku_message.meta.is_empty());
+        assert_eq!(evt.waku_message.timestamp, 0);
+        assert_eq!(evt.waku_message.version, 0);
+        assert!(!evt.waku_message.ephemeral);
+
+        let s = "{\"eventType\":\"message\",\"messageHash\":[91, 70, 26, 8, 141, 232, 150, 200, 26, 206, 224, 175, 249, 74, 61, 140, 231, 126, 224, 160, 91, 80, 162, 65, 250, 171, 84, 149, 133, 110, 214, 101],\"pubsubTopic\":\"/waku/2/rs/16/32\"}";
+        let evt: WakuEvent = serde_json::from_str(s).unwrap();
+        assert!(matches!(evt, WakuEvent::WakuMessage(_)));
+    }
+
     #[test]
     fn deserialize_message_event() {
         let s = "{\"eventType\":\"message\",\"messageHash\":[91, 70, 26, 8, 141, 232, 150, 200, 26, 206, 224, 175, 249, 74, 61, 140, 231, 126, 224, 160, 91, 80, 162, 65, 250, 171, 84, 149, 133, 110, 214, 101],\"pubsubTopic\":\"/waku/2/default-waku/proto\",\"wakuMessage\":{\"payload\":\"SGkgZnJvbSDwn6aAIQ==\",\"contentTopic\":\"/toychat/2/huilong/proto\",\"timestamp\":1665580926660}}";
@@ -74,6 +411,26 @@ mod tests {
         assert!(matches!(evt, WakuEvent::WakuMessage(_)));
     }
 
+    #[test]
+    fn message_event_shard() {
+        let s = "{\"eventType\":\"message\",\"messageHash\":[91, 70, 26, 8, 141, 232, 150, 200, 26, 206, 224, 175, 249, 74, 61, 140, 231, 126, 224, 160, 91, 80, 162, 65, 250, 171, 84, 149, 133, 110, 214, 101],\"pubsubTopic\":\"/waku/2/rs/16/32\",\"wakuMessage\":{\"payload\":\"SGkgZnJvbSDwn6aAIQ==\",\"contentTopic\":\"/toychat/2/huilong/proto\",\"timestamp\":1665580926660}}";
+        let WakuEvent::WakuMessage(mut evt) = serde_json::from_str(s).unwrap() else {
+            panic!("Expected WakuMessage event");
+        };
+        assert_eq!(
+            evt.pubsub_topic_parsed().unwrap(),
+            PubsubTopic::new("/waku/2/rs/16/32")
+        );
+        assert_eq!(evt.shard(), Some((16, 32)));
+
+        evt.pubsub_topic = "/waku/2/default-waku/proto".to_string();
+        assert_eq!(
+            evt.pubsub_topic_parsed().unwrap(),
+            PubsubTopic::new("/waku/2/default-waku/proto")
+        );
+        assert_eq!(evt.shard(), None);
+    }
+
     #[test]
     fn deserialize_topic_health_change_event() {
         let s = "{\"eventType\":\"relay_topic_health_change\", \"pubsubTopic\":\"/waku/2/rs/16/1\",\"topicHealth\":\"MinimallyHealthy\"}";
@@ -81,12 +438,32 @@ mod tests {
         match evt {
             RelayTopicHealthChange(topic_health_event) => {
                 assert_eq!(topic_health_event.pubsub_topic, "/waku/2/rs/16/1");
-                assert_eq!(topic_health_event.topic_health, "MinimallyHealthy");
+                assert_eq!(
+                    topic_health_event.topic_health,
+                    TopicHealth::MinimallyHealthy
+                );
             }
             _ => panic!("Expected RelayTopicHealthChange event, but got {:?}", evt),
         }
     }
 
+    #[test]
+    fn unknown_topic_health_kept() {
+        let s = "{\"eventType\":\"relay_topic_health_change\", \"pubsubTopic\":\"/waku/2/rs/16/1\",\"topicHealth\":\"SomewhatHealthy\"}";
+        let WakuEvent::RelayTopicHealthChange(evt) = serde_json::from_str(s).unwrap() else {
+            panic!("Expected RelayTopicHealthChange event");
+        };
+        assert_eq!(
+            evt.topic_health,
+            TopicHealth::Unknown("SomewhatHealthy".to_string())
+        );
+        assert!(!evt.topic_health.is_healthy());
+        assert_eq!(
+            serde_json::to_value(&evt).unwrap()["topicHealth"],
+            "SomewhatHealthy"
+        );
+    }
+
     #[test]
     fn deserialize_connection_change_event() {
         let s = "{\"eventType\":\"connection_change\", \"peerId\":\"16Uiu2HAmAR24Mbb6VuzoyUiGx42UenDkshENVDj4qnmmbabLvo31\",\"peerEvent\":\"Joined\"}";
@@ -97,9 +474,38 @@ mod tests {
                     conn_change_event.peer_id,
                     "16Uiu2HAmAR24Mbb6VuzoyUiGx42UenDkshENVDj4qnmmbabLvo31"
                 );
-                assert_eq!(conn_change_event.peer_event, "Joined");
+                assert_eq!(conn_change_event.peer_event, PeerConnectionEvent::Joined);
             }
             _ => panic!("Expected RelayTopicHealthChange event, but got {:?}", evt),
         }
     }
+
+    #[test]
+    fn deserialize_rln_proof_invalid_event() {
+        let s = "{\"eventType\":\"rln_proof_invalid\",\"messageHash\":[91, 70, 26, 8, 141, 232, 150, 200, 26, 206, 224, 175, 249, 74, 61, 140, 231, 126, 224, 160, 91, 80, 162, 65, 250, 171, 84, 149, 133, 110, 214, 101],\"reason\":\"invalid message: provided root does not belong to acceptable window of roots\"}";
+        let evt: WakuEvent = serde_json::from_str(s).unwrap();
+        match evt {
+            WakuEvent::RlnProofInvalid(rln_event) => {
+                assert_eq!(rln_event.message_hash.to_string().len(), 64);
+                assert!(rln_event.reason.contains("root"));
+            }
+            _ => panic!("Expected RlnProofInvalid event, but got {:?}", evt),
+        }
+    }
+
+    #[test]
+    fn unknown_peer_event_kept() {
+        let s = "{\"eventType\":\"connection_change\", \"peerId\":\"16Uiu2HAmAR24Mbb6VuzoyUiGx42UenDkshENVDj4qnmmbabLvo31\",\"peerEvent\":\"Identified\"}";
+        let ConnectionChange(evt) = serde_json::from_str(s).unwrap() else {
+            panic!("Expected ConnectionChange event");
+        };
+        assert_eq!(
+            evt.peer_event,
+            PeerConnectionEvent::Unknown("Identified".to_string())
+        );
+        assert_eq!(
+            serde_json::to_value(&evt).unwrap()["peerEvent"],
+            "Identified"
+        );
+    }
 }
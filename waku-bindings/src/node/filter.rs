@@ -2,32 +2,53 @@
 
 // std
 use std::ffi::CString;
+use std::time::Duration;
+// crates
+use multiaddr::Multiaddr;
 // internal
 use crate::general::contenttopic::WakuContentTopic;
 use crate::general::libwaku_response::{handle_no_response, LibwakuResponse};
 use crate::general::pubsubtopic::PubsubTopic;
-use crate::general::Result;
+use crate::general::{Result, WakuError};
 use crate::handle_ffi_call;
 use crate::node::context::WakuNodeContext;
+use crate::node::peers::waku_connect;
 
+/// Longest time spent dialing the filter service peer before subscribing through it
+const FILTER_DIAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Subscribe to `content_topics` on `pubsub_topic`, through the service node at `peer_addr`
+/// if given. libwaku takes no peer for a subscription and picks one among the connected
+/// filter peers, so `peer_addr` is dialed beforehand to make it available. With `None` the
+/// peer is left for libwaku to pick
 pub async fn waku_filter_subscribe(
     ctx: &WakuNodeContext,
     pubsub_topic: &PubsubTopic,
     content_topics: Vec<WakuContentTopic>,
+    peer_addr: Option<&str>,
 ) -> Result<()> {
-    let pubsub_topic = CString::new(String::from(pubsub_topic))
+    if let Some(peer_addr) = peer_addr {
+        let address: Multiaddr = peer_addr
+            .parse()
+            .map_err(|e| WakuError::Parse(format!("invalid filter peer {peer_addr}: {e}")))?;
+        waku_connect(ctx, &address, Some(FILTER_DIAL_TIMEOUT)).await?;
+    }
+
+    let topic = CString::new(String::from(pubsub_topic))
         .expect("CString should build properly from pubsub topic");
-    let content_topics = WakuContentTopic::join_content_topics(content_topics);
-    let content_topics =
-        CString::new(content_topics).expect("CString should build properly from content topic");
+    let joined_topics = WakuContentTopic::join_content_topics(content_topics.clone());
+    let joined_topics =
+        CString::new(joined_topics).expect("CString should build properly from content topic");
 
     handle_ffi_call!(
         waku_sys::waku_filter_subscribe,
         handle_no_response,
-        ctx.get_ptr(),
-        pubsub_topic.as_ptr(),
-        content_topics.as_ptr()
-    )
+        ctx.ptr_guard()?,
+        topic.as_ptr(),
+        joined_topics.as_ptr()
+    )?;
+    ctx.filter_subscribed(pubsub_topic, &content_topics);
+    Ok(())
 }
 
 pub async fn waku_filter_unsubscribe(
@@ -35,25 +56,37 @@ pub async fn waku_filter_unsubscribe(
     pubsub_topic: &PubsubTopic,
     content_topics: Vec<WakuContentTopic>, // comma-separated list of content topics
 ) -> Result<()> {
-    let pubsub_topic = CString::new(String::from(pubsub_topic))
+    let topic = CString::new(String::from(pubsub_topic))
         .expect("CString should build properly from pubsub topic");
-    let content_topics = WakuContentTopic::join_content_topics(content_topics);
-    let content_topics =
-        CString::new(content_topics).expect("CString should build properly from content topic");
+    let joined_topics = WakuContentTopic::join_content_topics(content_topics.clone());
+    let joined_topics =
+        CString::new(joined_topics).expect("CString should build properly from content topic");
 
     handle_ffi_call!(
         waku_sys::waku_filter_unsubscribe,
         handle_no_response,
-        ctx.get_ptr(),
-        pubsub_topic.as_ptr(),
-        content_topics.as_ptr()
-    )
+        ctx.ptr_guard()?,
+        topic.as_ptr(),
+        joined_topics.as_ptr()
+    )?;
+    ctx.filter_unsubscribed(pubsub_topic, &content_topics);
+    Ok(())
 }
 
 pub async fn waku_filter_unsubscribe_all(ctx: &WakuNodeContext) -> Result<()> {
     handle_ffi_call!(
         waku_sys::waku_filter_unsubscribe_all,
         handle_no_response,
-        ctx.get_ptr()
-    )
+        ctx.ptr_guard()?
+    )?;
+    ctx.filter_unsubscribed_all();
+    Ok(())
+}
+
+/// libwaku mounts the filter client once, when the node is created, and offers no call to
+/// mount or unmount it afterwards, so toggling the role always requires recreating the node
+pub async fn waku_filter_set_enabled(_ctx: &WakuNodeContext, _enabled: bool) -> Result<()> {
+    Err(WakuError::InvalidConfig(
+        "filter toggling requires restart".to_string(),
+    ))
 }
@@ -0,0 +1,140 @@
+//! Overall health of a node, derived from its connectivity and relay topic healths
+
+// std
+use std::time::{Duration, Instant};
+// internal
+use crate::general::libwaku_response::{handle_response, LibwakuResponse};
+use crate::general::{Result, WakuError};
+use crate::handle_ffi_call;
+use crate::node::context::WakuNodeContext;
+use crate::node::events::TopicHealth;
+
+/// Time between two health checks while waiting for the node to be ready
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Overall health of a running node.
+/// libwaku exposes no health endpoint, so it is derived from whether libwaku reports the node
+/// online and from the last health reported for each subscribed relay topic
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NodeHealth {
+    /// Online, but no subscribed relay topic had its health reported yet
+    Initializing,
+    /// Online, some subscribed relay topics are not healthy yet
+    Synchronizing,
+    /// Online, with every subscribed relay topic healthy, or none subscribed
+    Ready,
+    /// libwaku reports the node offline
+    NotReady,
+}
+
+impl NodeHealth {
+    /// Health of a node `online` or not, given its relay topic healths, `None` if not reported yet
+    fn derive(online: bool, topic_healths: &[Option<TopicHealth>]) -> Self {
+        if !online {
+            return Self::NotReady;
+        }
+        if !topic_healths.is_empty() && topic_healths.iter().all(Option::is_none) {
+            return Self::Initializing;
+        }
+        if topic_healths
+            .iter()
+            .all(|health| health.as_ref().is_some_and(TopicHealth::is_healthy))
+        {
+            Self::Ready
+        } else {
+            Self::Synchronizing
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        *self == Self::Ready
+    }
+}
+
+/// Whether libwaku reports the node online
+pub async fn waku_is_online(ctx: &WakuNodeContext) -> Result<bool> {
+    let online: String =
+        handle_ffi_call!(waku_sys::waku_is_online, handle_response, ctx.ptr_guard()?)?;
+    match online.trim() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        other => Err(WakuError::Decode(format!(
+            "could not parse online status {other}"
+        ))),
+    }
+}
+
+pub async fn waku_health(ctx: &WakuNodeContext) -> Result<NodeHealth> {
+    let online = waku_is_online(ctx).await?;
+    let topic_healths: Vec<Option<TopicHealth>> = ctx
+        .relay_topics()
+        .iter()
+        .map(|pubsub_topic| ctx.topic_health(pubsub_topic))
+        .collect();
+    Ok(NodeHealth::derive(online, &topic_healths))
+}
+
+/// Poll the node health until it is [`NodeHealth::Ready`], erroring with the last health seen
+/// once `timeout` elapses
+pub async fn waku_wait_until_ready(ctx: &WakuNodeContext, timeout: Duration) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let health = waku_health(ctx).await?;
+        if health.is_ready() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(format!("node not ready within {timeout:?}, last {health:?}").into());
+        }
+        tokio::time::sleep(READY_POLL_INTERVAL.min(deadline - now)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NodeHealth;
+    use crate::node::events::TopicHealth;
+
+    #[test]
+    fn offline_is_not_ready() {
+        assert_eq!(
+            NodeHealth::derive(false, &[Some(TopicHealth::SufficientlyHealthy)]),
+            NodeHealth::NotReady
+        );
+        assert_eq!(NodeHealth::derive(false, &[]), NodeHealth::NotReady);
+    }
+
+    #[test]
+    fn online_follows_topic_healths() {
+        assert_eq!(NodeHealth::derive(true, &[]), NodeHealth::Ready);
+        assert_eq!(
+            NodeHealth::derive(true, &[None, None]),
+            NodeHealth::Initializing
+        );
+        assert_eq!(
+            NodeHealth::derive(true, &[Some(TopicHealth::MinimallyHealthy), None]),
+            NodeHealth::Synchronizing
+        );
+        assert_eq!(
+            NodeHealth::derive(
+                true,
+                &[
+                    Some(TopicHealth::SufficientlyHealthy),
+                    Some(TopicHealth::UnHealthy)
+                ]
+            ),
+            NodeHealth::Synchronizing
+        );
+        assert_eq!(
+            NodeHealth::derive(
+                true,
+                &[
+                    Some(TopicHealth::SufficientlyHealthy),
+                    Some(TopicHealth::MinimallyHealthy)
+                ]
+            ),
+            NodeHealth::Ready
+        );
+    }
+}
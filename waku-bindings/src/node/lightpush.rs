@@ -2,20 +2,53 @@
 
 // std
 use std::ffi::CString;
+use std::time::Duration;
+// crates
+use multiaddr::Multiaddr;
 // internal
 use crate::general::libwaku_response::{handle_response, LibwakuResponse};
-use crate::general::{messagehash::MessageHash, Result, WakuMessage};
+use crate::general::{messagehash::MessageHash, Result, WakuError, WakuMessage};
 use crate::handle_ffi_call;
 use crate::node::context::WakuNodeContext;
+use crate::node::peers::waku_connect;
 
 use crate::general::pubsubtopic::PubsubTopic;
 
+/// Longest time spent dialing a lightpush peer before publishing through it
+const LIGHTPUSH_DIAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of a lightpush publication.
+/// libwaku only answers a lightpush request with the message hash, and picks the serving peer
+/// itself, so the peer reported is the one dialed before the publication succeeded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LightpushResult {
+    /// Address of the peer dialed before the publication succeeded, which libwaku may not
+    /// have picked to serve the request
+    pub peer: String,
+    /// Hash of the published message
+    pub message_hash: MessageHash,
+    /// Number of relay peers the service node forwarded the message to, `None` as long as
+    /// libwaku doesn't report it
+    pub relay_peer_count: Option<usize>,
+}
+
+/// Publish `message` with lightpush, dialing `peer_addr` beforehand if given.
+/// libwaku takes no peer for a lightpush request and picks one among the connected lightpush
+/// peers, so a dialed peer is only made available, not pinned
 pub async fn waku_lightpush_publish_message(
     ctx: &WakuNodeContext,
     message: &WakuMessage,
     pubsub_topic: &PubsubTopic,
+    peer_addr: Option<&str>,
 ) -> Result<MessageHash> {
-    let message = CString::new(
+    if let Some(peer_addr) = peer_addr {
+        let address: Multiaddr = peer_addr
+            .parse()
+            .map_err(|e| WakuError::Parse(format!("invalid lightpush peer {peer_addr}: {e}")))?;
+        waku_connect(ctx, &address, Some(LIGHTPUSH_DIAL_TIMEOUT)).await?;
+    }
+
+    let json = CString::new(
         serde_json::to_string(&message)
             .expect("WakuMessages should always be able to success serializing"),
     )
@@ -24,11 +57,45 @@ pub async fn waku_lightpush_publish_message(
     let pubsub_topic = CString::new(String::from(pubsub_topic))
         .expect("CString should build properly from pubsub topic");
 
-    handle_ffi_call!(
+    let hash = handle_ffi_call!(
         waku_sys::waku_lightpush_publish,
         handle_response,
-        ctx.get_ptr(),
+        ctx.ptr_guard()?,
         pubsub_topic.as_ptr(),
-        message.as_ptr()
-    )
+        json.as_ptr()
+    )?;
+    ctx.message_published(message, &hash);
+    Ok(hash)
+}
+
+/// Publish `message` after dialing the first of `peers` that can be dialed, moving on to the
+/// next one when dialing or publishing fails. libwaku takes no peer for a lightpush request and
+/// picks one among the connected lightpush peers, so the peer reported is the one dialed last.
+/// Fails with the last error when every attempt failed
+pub async fn waku_lightpush_publish_failover(
+    ctx: &WakuNodeContext,
+    message: &WakuMessage,
+    pubsub_topic: &PubsubTopic,
+    peers: &[&str],
+) -> Result<LightpushResult> {
+    let mut last_error = WakuError::Other("no lightpush peer to publish through".to_string());
+    for peer in peers {
+        let published =
+            waku_lightpush_publish_message(ctx, message, pubsub_topic, Some(peer)).await;
+
+        match published {
+            Ok(message_hash) => {
+                return Ok(LightpushResult {
+                    peer: peer.to_string(),
+                    message_hash,
+                    relay_peer_count: None,
+                })
+            }
+            Err(e) => {
+                log::debug!("lightpush through {peer} failed: {e}");
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
 }
@@ -2,28 +2,53 @@
 
 // std
 use std::ffi::CString;
+use std::net::{Ipv4Addr, Ipv6Addr};
 // crates
 use libc::c_void;
-use multiaddr::Multiaddr;
+use multiaddr::{Multiaddr, Protocol};
 use std::sync::Arc;
 use tokio::sync::Notify;
 // internal
 use super::config::WakuNodeConfig;
-use crate::general::libwaku_response::{handle_no_response, handle_response, LibwakuResponse};
-use crate::general::Result;
+use crate::general::libwaku_response::{
+    failure_error, handle_no_response, handle_response, LibwakuResponse,
+};
+use crate::general::pubsubtopic::PubsubTopic;
+use crate::general::{Result, WakuError};
 use crate::handle_ffi_call;
 use crate::macros::get_trampoline;
 use crate::node::context::WakuNodeContext;
+use crate::node::discovery::DiscoveryStatus;
+use crate::node::dns::Enr;
+use crate::node::peers::{PeerGate, Protocol as WakuProtocol};
+use crate::node::version::NwakuVersion;
 
 /// Instantiates a Waku node
 /// as per the [specification](https://rfc.vac.dev/spec/36/#extern-char-waku_newchar-jsonconfig)
 pub async fn waku_new(config: Option<WakuNodeConfig>) -> Result<WakuNodeContext> {
     let config = config.unwrap_or_default();
-    let config = CString::new(
-        serde_json::to_string(&config)
-            .expect("Serialization from properly built NodeConfig should never fail"),
-    )
-    .expect("CString should build properly from the config");
+    config.validate().map_err(WakuError::InvalidConfig)?;
+    let config = config
+        .to_libwaku_config()
+        .map_err(WakuError::InvalidConfig)?;
+    let peer_gate = PeerGate::from_config(&config);
+    let node_key = config.node_key;
+    let max_messages_per_peer_per_sec = config.max_messages_per_peer_per_sec;
+    let cluster_id = config.cluster_id.unwrap_or_default();
+    let capabilities = capabilities(&config);
+    let discovery_status = DiscoveryStatus::configured(&config);
+    let max_relay_subscriptions = config.max_relay_subscriptions;
+    let static_bootstrap_peers = config.static_bootstrap_peers.clone().unwrap_or_default();
+    let relay_topics: Vec<PubsubTopic> = config
+        .relay_topics
+        .iter()
+        .map(|topic| PubsubTopic::new(topic))
+        .collect();
+    if log::log_enabled!(log::Level::Debug) {
+        log::debug!("libwaku config: {}", config.to_redacted_json()?);
+    }
+    let config = CString::new(config.to_libwaku_json()?)
+        .expect("CString should build properly from the config");
     let config_ptr = config.as_ptr();
 
     let notify = Arc::new(Notify::new());
@@ -42,31 +67,73 @@ pub async fn waku_new(config: Option<WakuNodeConfig>) -> Result<WakuNodeContext>
     notify.notified().await; // Wait until a result is received
 
     match result {
-        LibwakuResponse::MissingCallback => panic!("callback is required"),
-        LibwakuResponse::Failure(v) => Err(v),
-        _ => Ok(WakuNodeContext::new(obj_ptr)),
+        LibwakuResponse::MissingCallback => Err(WakuError::MissingCallback),
+        LibwakuResponse::Failure(v) => Err(failure_error(v)),
+        _ => {
+            let ctx = WakuNodeContext::new(obj_ptr)
+                .with_peer_gate(peer_gate)
+                .with_message_rate_limit(max_messages_per_peer_per_sec)
+                .with_node_key(node_key)
+                .with_cluster_id(cluster_id)
+                .with_capabilities(capabilities)
+                .with_discovery_status(discovery_status)
+                .with_max_relay_subscriptions(max_relay_subscriptions)
+                .with_static_bootstrap_peers(static_bootstrap_peers);
+            // libwaku subscribes to the configured relay topics by itself
+            for topic in &relay_topics {
+                ctx.relay_subscribed(topic);
+            }
+            Ok(ctx)
+        }
     }
 }
 
 pub async fn waku_destroy(ctx: &WakuNodeContext) -> Result<()> {
-    handle_ffi_call!(waku_sys::waku_destroy, handle_no_response, ctx.get_ptr())
+    handle_ffi_call!(
+        waku_sys::waku_destroy,
+        handle_no_response,
+        ctx.take_ptr().await?
+    )
 }
 
 /// Start a Waku node mounting all the protocols that were enabled during the Waku node instantiation.
 /// as per the [specification](https://rfc.vac.dev/spec/36/#extern-char-waku_start)
 pub async fn waku_start(ctx: &WakuNodeContext) -> Result<()> {
-    handle_ffi_call!(waku_sys::waku_start, handle_no_response, ctx.get_ptr())
+    handle_ffi_call!(waku_sys::waku_start, handle_no_response, ctx.ptr_guard()?)?;
+    ctx.set_running(true);
+    Ok(())
+}
+
+/// Protocols mounted by a node created with `config`, following the libwaku defaults.
+/// The filter client is always mounted
+fn capabilities(config: &WakuNodeConfig) -> Vec<WakuProtocol> {
+    [
+        (WakuProtocol::Relay, config.relay.unwrap_or(true)),
+        (WakuProtocol::Store, config.store.unwrap_or(false)),
+        (WakuProtocol::Lightpush, config.lightpush.unwrap_or(false)),
+        (WakuProtocol::Filter, true),
+    ]
+    .into_iter()
+    .filter_map(|(protocol, mounted)| mounted.then_some(protocol))
+    .collect()
 }
 
 /// Stops a Waku node
 /// as per the [specification](https://rfc.vac.dev/spec/36/#extern-char-waku_stop)
 pub async fn waku_stop(ctx: &WakuNodeContext) -> Result<()> {
-    handle_ffi_call!(waku_sys::waku_stop, handle_no_response, ctx.get_ptr())
+    handle_ffi_call!(waku_sys::waku_stop, handle_no_response, ctx.ptr_guard()?)?;
+    ctx.set_running(false);
+    Ok(())
 }
 
 /// nwaku version
 pub async fn waku_version(ctx: &WakuNodeContext) -> Result<String> {
-    handle_ffi_call!(waku_sys::waku_version, handle_response, ctx.get_ptr())
+    handle_ffi_call!(waku_sys::waku_version, handle_response, ctx.ptr_guard()?)
+}
+
+/// nwaku version, parsed
+pub async fn waku_version_parsed(ctx: &WakuNodeContext) -> Result<NwakuVersion> {
+    handle_ffi_call!(waku_sys::waku_version, handle_response, ctx.ptr_guard()?)
 }
 
 /// Get the multiaddresses the Waku node is listening to
@@ -75,10 +142,61 @@ pub async fn waku_listen_addresses(ctx: &WakuNodeContext) -> Result<Vec<Multiadd
     handle_ffi_call!(
         waku_sys::waku_listen_addresses,
         handle_response,
-        ctx.get_ptr()
+        ctx.ptr_guard()?
+    )
+}
+
+/// `enr:` encoded ENR of the Waku node
+pub async fn waku_get_my_enr(ctx: &WakuNodeContext) -> Result<String> {
+    handle_ffi_call!(waku_sys::waku_get_my_enr, handle_response, ctx.ptr_guard()?)
+}
+
+/// ENR of the Waku node, parsed
+pub async fn waku_get_my_enr_parsed(ctx: &WakuNodeContext) -> Result<Enr> {
+    let enr = waku_get_my_enr(ctx).await?;
+    enr.parse()
+        .map_err(|e| WakuError::Decode(format!("could not parse enr {enr}: {e}")))
+}
+
+/// Get the peer id of the Waku node
+pub async fn waku_get_my_peerid(ctx: &WakuNodeContext) -> Result<String> {
+    handle_ffi_call!(
+        waku_sys::waku_get_my_peerid,
+        handle_response,
+        ctx.ptr_guard()?
     )
 }
 
+/// First listen address of the node, made dialable from the same host: an unspecified
+/// listening ip (`0.0.0.0` or `::`) is replaced by the loopback address, and the node's
+/// `/p2p/{peer_id}` is appended if missing
+pub async fn waku_store_peer_address(ctx: &WakuNodeContext) -> Result<Multiaddr> {
+    let address = waku_listen_addresses(ctx)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or("node listens on no address")?;
+
+    let mut dialable: Multiaddr = address
+        .iter()
+        .map(|protocol| match protocol {
+            Protocol::Ip4(ip) if ip.is_unspecified() => Protocol::Ip4(Ipv4Addr::LOCALHOST),
+            Protocol::Ip6(ip) if ip.is_unspecified() => Protocol::Ip6(Ipv6Addr::LOCALHOST),
+            protocol => protocol,
+        })
+        .collect();
+    if !dialable
+        .iter()
+        .any(|protocol| matches!(protocol, Protocol::P2p(_)))
+    {
+        let peer_id = waku_get_my_peerid(ctx).await?;
+        dialable = format!("{dialable}/p2p/{}", peer_id.trim())
+            .parse()
+            .map_err(|e| format!("invalid peer id {peer_id}: {e}"))?;
+    }
+    Ok(dialable)
+}
+
 #[cfg(test)]
 mod test {
     use super::waku_new;
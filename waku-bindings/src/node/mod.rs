@@ -2,32 +2,66 @@
 
 mod config;
 mod context;
+mod discovery;
+mod dns;
+mod event_stream;
 mod events;
 mod filter;
+mod health;
 mod lightpush;
 mod management;
 mod peers;
 mod relay;
+#[cfg(feature = "rest")]
+mod rest;
 mod store;
+mod subscriptions;
+mod version;
+mod watchdog;
 
 // std
 pub use aes_gcm::Key;
+use futures::stream::{self, StreamExt};
 pub use multiaddr::Multiaddr;
 pub use secp256k1::{PublicKey, SecretKey};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::ffi::c_void;
+use std::future::Future;
 use std::marker::PhantomData;
 use std::time::Duration;
-use store::{StoreQueryRequest, StoreWakuMessageResponse};
+use store::{STORE_MAX_PAGE_SIZE, STORE_MAX_PARALLEL_QUERIES};
 // internal
 use crate::general::contenttopic::{Encoding, WakuContentTopic};
 use crate::general::libwaku_response::LibwakuResponse;
+use crate::general::messagehash::{compute_message_hash, MessageHash};
 pub use crate::general::pubsubtopic::PubsubTopic;
-use crate::general::{messagehash::MessageHash, Result, WakuMessage};
+use crate::general::{signing, Result, WakuError, WakuMessage};
 
 use crate::node::context::WakuNodeContext;
-pub use config::RLNConfig;
-pub use config::WakuNodeConfig;
-pub use events::{WakuEvent, WakuMessageEvent};
-pub use relay::waku_create_content_topic;
+pub use config::{Fleet, WakuNodeConfig, WakuNodeConfigBuilder};
+pub use config::{RLNConfig, RLNConfigBuilder};
+pub use context::{
+    PublishStats, TopicPublishStats, PAUSED_EVENTS_CAPACITY, SEEN_MESSAGES_CAPACITY,
+};
+pub use discovery::{DiscoveryMechanism, DiscoveryMechanismStatus, DiscoveryStatus};
+pub use dns::{DnsInfo, DnsResolver, DEFAULT_DNS_SERVER};
+pub use event_stream::{EventStream, OverflowPolicy, CONNECTION_EVENTS_CAPACITY};
+pub use events::{
+    ConnectionChangeEvent, NodeStalledEvent, PeerConnectionEvent, RlnProofInvalidEvent,
+    TopicHealth, WakuEvent, WakuMessageEvent,
+};
+pub use health::NodeHealth;
+pub use lightpush::LightpushResult;
+pub use peers::{Protocol, WakuPeerData, WAKU_PROTOCOLS};
+pub use relay::{waku_create_content_topic, RELAY_MIN_PEERS_TO_PUBLISH};
+#[cfg(feature = "rest")]
+pub use rest::{RestConfig, DEFAULT_REST_ADDRESS};
+pub use store::{
+    MessageSink, StoreQueryRequest, StoreResponse, StoreStatus, StoreWakuMessageResponse,
+};
+pub use subscriptions::{FilterSubscription, Subscription, SubscriptionState};
+pub use version::NwakuVersion;
 
 // Define state marker types
 pub struct Initialized;
@@ -49,18 +83,94 @@ pub async fn waku_new(config: Option<WakuNodeConfig>) -> Result<WakuNodeHandle<I
 }
 
 impl<State> WakuNodeHandle<State> {
+    /// Protocols the node mounts as per its configuration. The filter client is always mounted
+    pub fn capabilities(&self) -> Vec<Protocol> {
+        self.ctx.capabilities().to_vec()
+    }
+
     /// Get the nwaku version
     pub async fn version(&self) -> Result<String> {
         management::waku_version(&self.ctx).await
     }
 
+    /// Get the nwaku version, parsed. Its [`raw`](NwakuVersion::raw) field keeps the full string
+    pub async fn version_parsed(&self) -> Result<NwakuVersion> {
+        management::waku_version_parsed(&self.ctx).await
+    }
+
+    /// Destroy the node, once the operations already calling into libwaku are done.
+    /// Any operation still holding the context fails with
+    /// [`WakuError::NodeDestroyed`](crate::general::WakuError::NodeDestroyed) from now on
     pub async fn waku_destroy(self) -> Result<()> {
-        let res = management::waku_destroy(&self.ctx).await;
-        self.ctx.reset_ptr();
-        res
+        management::waku_destroy(&self.ctx).await
+    }
+
+    /// Raw libwaku context of the node, to call `waku_sys` functions this crate doesn't wrap.
+    /// This escape hatch is unstable and may change with any release.
+    ///
+    /// # Safety
+    /// The pointer is only valid while this handle is alive: it dangles once the handle is
+    /// dropped or [`waku_destroy`](Self::waku_destroy) is called, and must not be destroyed
+    /// or have its event callback replaced through it. libwaku calls made with it must not
+    /// be issued from within the event callback, which runs on the libwaku thread.
+    pub unsafe fn raw_context_ptr(&self) -> *mut c_void {
+        self.ctx.get_ptr().unwrap_or(std::ptr::null_mut())
+    }
+
+    /// Stop the event callback from firing, keeping the events until
+    /// [`resume_events`](Self::resume_events) without touching any subscription.
+    /// At most [`PAUSED_EVENTS_CAPACITY`] events are kept, the oldest ones being dropped first
+    pub fn pause_events(&self) {
+        self.ctx.pause_events()
+    }
+
+    /// Deliver the events kept while paused to the event callback, on the calling thread,
+    /// then let new events reach it directly again
+    pub fn resume_events(&self) {
+        self.ctx.resume_events()
+    }
+
+    /// Number of events dropped because the event callback was paused for too long
+    pub fn events_dropped_while_paused(&self) -> u64 {
+        self.ctx.events_dropped_while_paused()
+    }
+
+    /// Oldest event buffered since [`buffer_events`](WakuNodeHandle::buffer_events), without
+    /// waiting, `None` if there is none yet. Meant for loops polling other sources as well
+    pub fn try_next_event(&self) -> Option<WakuEvent> {
+        self.ctx.try_next_event()
+    }
+
+    /// Number of events dropped because the buffer of
+    /// [`buffer_events`](WakuNodeHandle::buffer_events) was full
+    pub fn buffered_events_dropped(&self) -> u64 {
+        self.ctx.buffered_events_dropped()
+    }
+
+    /// Messages and bytes published through relay and lightpush since the node was created,
+    /// by content topic
+    pub fn publish_stats(&self) -> PublishStats {
+        self.ctx.publish_stats()
+    }
+
+    /// Number of messages dropped for exceeding
+    /// [`max_messages_per_peer_per_sec`](WakuNodeConfig::max_messages_per_peer_per_sec)
+    pub fn rate_limited_messages(&self) -> u64 {
+        self.ctx.rate_limited_messages()
+    }
+
+    /// Watch for a stalled libwaku: whenever an operation awaits its result and libwaku made
+    /// no callback, neither a response nor an event, for longer than `window`, a
+    /// [`WakuEvent::NodeStalled`] event is emitted, once until libwaku calls back again.
+    /// The operation keeps waiting, so a stalled node is best stopped or dropped and recreated.
+    /// `None`, the default, disables the watchdog
+    pub fn set_stall_watchdog(&self, window: Option<Duration>) {
+        self.ctx.set_stall_window(window)
     }
 
     /// Subscribe to WakuRelay to receive messages matching a content filter.
+    /// Fails with [`WakuError::SubscriptionLimitReached`](crate::general::WakuError::SubscriptionLimitReached)
+    /// when already subscribed to [`max_relay_subscriptions`](WakuNodeConfig::max_relay_subscriptions) topics
     pub async fn relay_subscribe(&self, pubsub_topic: &PubsubTopic) -> Result<()> {
         relay::waku_relay_subscribe(&self.ctx, pubsub_topic).await
     }
@@ -69,19 +179,84 @@ impl<State> WakuNodeHandle<State> {
 impl WakuNodeHandle<Initialized> {
     /// Start a Waku node mounting all the protocols that were enabled during the Waku node instantiation.
     /// as per the [specification](https://rfc.vac.dev/spec/36/#extern-char-waku_start)
+    /// Relay subscriptions queued with [`queue_relay_subscribe`](Self::queue_relay_subscribe)
+    /// are applied right after the node starts, then the
+    /// [`static_bootstrap_peers`](WakuNodeConfig::static_bootstrap_peers) are dialed.
     pub async fn start(self) -> Result<WakuNodeHandle<Running>> {
-        management::waku_start(&self.ctx)
-            .await
-            .map(|_| WakuNodeHandle {
-                ctx: self.ctx,
-                _state: PhantomData,
-            })
+        management::waku_start(&self.ctx).await?;
+
+        for pubsub_topic in self.ctx.queued_relay_subscriptions() {
+            if let Err(e) = relay::waku_relay_subscribe(&self.ctx, &pubsub_topic).await {
+                // Don't leave the node running without the subscriptions it was set up with
+                let _ = management::waku_stop(&self.ctx).await;
+                return Err(e);
+            }
+        }
+        peers::waku_connect_static_bootstrap_peers(&self.ctx).await;
+
+        Ok(WakuNodeHandle {
+            ctx: self.ctx,
+            _state: PhantomData,
+        })
+    }
+
+    /// Subscribe to `pubsub_topic` with relay as part of every [`start`](Self::start),
+    /// including the ones following a [`stop`](WakuNodeHandle::stop)
+    pub fn queue_relay_subscribe(&self, pubsub_topic: &PubsubTopic) {
+        self.ctx.queue_relay_subscription(pubsub_topic)
     }
 
     pub fn set_event_callback<F: FnMut(LibwakuResponse) + 'static + Sync + Send>(
         &self,
         closure: F,
     ) -> Result<()> {
+        self.ctx.set_max_event_payload(None);
+        self.ctx.waku_set_event_callback(closure)
+    }
+
+    /// Replace the event callback with one doing nothing, and stream every event of the node
+    /// instead, for consumers doing async work with them. An [`event_stream`](Self::event_stream)
+    /// of `buffer` events, the oldest ones being dropped once it is full
+    pub fn event_channel(&self, buffer: usize) -> Result<EventStream> {
+        self.set_event_callback(|_| {})?;
+        Ok(event_stream::waku_event_stream(
+            &self.ctx,
+            buffer,
+            OverflowPolicy::DropOldest,
+        ))
+    }
+
+    /// Replace the event callback with one doing nothing, and keep up to `buffer` events, to be
+    /// polled with [`try_next_event`](WakuNodeHandle::try_next_event). The oldest ones are
+    /// dropped once the buffer is full, see
+    /// [`buffered_events_dropped`](WakuNodeHandle::buffered_events_dropped)
+    pub fn buffer_events(&self, buffer: usize) -> Result<()> {
+        let events = self.event_channel(buffer)?;
+        self.ctx.buffer_events(events);
+        Ok(())
+    }
+
+    /// Like [`set_event_callback`](Self::set_event_callback), but `closure` is handed every event
+    /// parsed, along with the json received from libwaku, verbatim. Events of types unknown to
+    /// the bindings come as [`WakuEvent::Unrecognized`]
+    pub fn set_event_callback_raw<F: FnMut(WakuEvent, &str) + 'static + Sync + Send>(
+        &self,
+        closure: F,
+    ) -> Result<()> {
+        self.ctx.set_max_event_payload(None);
+        self.ctx
+            .waku_set_event_callback(events::raw_event_callback(closure))
+    }
+
+    /// Like [`set_event_callback`](Self::set_event_callback), but messages whose payload is
+    /// larger than `max_bytes` are dropped with a debug log instead of reaching `closure`.
+    /// They are dropped for [`await_message`](WakuNodeHandle::await_message) as well
+    pub fn set_event_callback_max_payload<F: FnMut(LibwakuResponse) + 'static + Sync + Send>(
+        &self,
+        max_bytes: usize,
+        closure: F,
+    ) -> Result<()> {
+        self.ctx.set_max_event_payload(Some(max_bytes));
         self.ctx.waku_set_event_callback(closure)
     }
 }
@@ -98,12 +273,96 @@ impl WakuNodeHandle<Running> {
             })
     }
 
+    /// Resolves once the node stops running, i.e. once [`stop`](Self::stop) or
+    /// [`waku_destroy`](WakuNodeHandle::waku_destroy) is done, whoever calls it, or once the node
+    /// is dropped. The future doesn't borrow the handle, so it can be spawned to trigger restart
+    /// logic. libwaku emits no lifecycle event, a node crashing within libwaku isn't noticed
+    pub fn wait_closed(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        let stopped = self.ctx.wait_stopped();
+        async move {
+            stopped.await;
+            Ok(())
+        }
+    }
+
     /// Get the multiaddresses the Waku node is listening to
     /// as per [specification](https://rfc.vac.dev/spec/36/#extern-char-waku_listen_addresses)
     pub async fn listen_addresses(&self) -> Result<Vec<Multiaddr>> {
         management::waku_listen_addresses(&self.ctx).await
     }
 
+    /// `enr:-...` encoded ENR of this node, to share as a discv5 bootstrap entry
+    pub async fn local_enr(&self) -> Result<String> {
+        management::waku_get_my_enr(&self.ctx).await
+    }
+
+    /// ENR of this node, parsed
+    pub async fn local_enr_parsed(&self) -> Result<enr::Enr<enr::secp256k1::SecretKey>> {
+        management::waku_get_my_enr_parsed(&self.ctx).await
+    }
+
+    /// Keep the `enr:` encoded ENR of a peer, so it is reported by `peers_in_shard` when it
+    /// advertises the shard. The ENRs found by [`dns_discovery`](Self::dns_discovery) with a
+    /// custom resolver are kept already
+    pub fn remember_peer_enr(&self, enr: &str) -> Result<()> {
+        peers::waku_remember_peer_enr(&self.ctx, enr)
+    }
+
+    /// Nodes listed in the [EIP-1459](https://eips.ethereum.org/EIPS/eip-1459) DNS tree at the
+    /// `enrtree://` url `enrtree_url`, each with its peer id and addresses, ready to be dialed
+    /// with [`connect`](Self::connect). libwaku resolves the tree through `nameserver`,
+    /// [`DEFAULT_DNS_SERVER`] if `None`. With a `resolver`, the bindings walk the tree instead,
+    /// checking its signature and entries but not following links to other trees, and keep the
+    /// ENRs found for [`Self::peers_in_shard`]
+    pub async fn dns_discovery(
+        &self,
+        enrtree_url: &url::Url,
+        nameserver: Option<&str>,
+        resolver: Option<&dyn DnsResolver>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<DnsInfo>> {
+        dns::waku_dns_discovery(&self.ctx, enrtree_url, nameserver, resolver, timeout).await
+    }
+
+    /// Start the discv5 service, on the
+    /// [`discv5_udp_port`](WakuNodeConfig::discv5_udp_port) of the node
+    pub async fn discv5_start(&self) -> Result<()> {
+        discovery::waku_discv5_start(&self.ctx).await
+    }
+
+    /// Stop the discv5 service, until [`discv5_start`](Self::discv5_start) is called
+    pub async fn discv5_stop(&self) -> Result<()> {
+        discovery::waku_discv5_stop(&self.ctx).await
+    }
+
+    /// Request up to `num_peers` peers through peer exchange from the node at `peer_addr`,
+    /// dialed first within `dial_timeout`. Returns the addresses of the peers received that
+    /// were new to the peer store: the ones listed by their ENR when it is
+    /// [known](Self::remember_peer_enr) to the bindings, a bare `/p2p/{peer_id}` otherwise, as
+    /// libwaku doesn't report the addresses of the peers it exchanges
+    pub async fn peer_exchange_request(
+        &self,
+        peer_addr: &Multiaddr,
+        num_peers: u64,
+        dial_timeout: Option<Duration>,
+    ) -> Result<Vec<Multiaddr>> {
+        discovery::waku_peer_exchange_request(&self.ctx, peer_addr, num_peers, dial_timeout).await
+    }
+
+    /// Whether each discovery mechanism is enabled, and how the discoveries made through the
+    /// bindings went. libwaku reports nothing about the discoveries it runs by itself
+    pub fn discovery_status(&self) -> DiscoveryStatus {
+        self.ctx.discovery_status()
+    }
+
+    /// Multiaddress of this node, including its peer id, that another node on the same host
+    /// can use as `peer_addr` to query its store. Meant for tests and local setups
+    pub async fn store_peer_address(&self) -> Result<String> {
+        management::waku_store_peer_address(&self.ctx)
+            .await
+            .map(|address| address.to_string())
+    }
+
     /// Dial peer using a multiaddress
     /// If `timeout` as milliseconds doesn't fit into a `i32` it is clamped to [`i32::MAX`]
     /// If the function execution takes longer than `timeout` value, the execution will be canceled and an error returned.
@@ -113,6 +372,56 @@ impl WakuNodeHandle<Running> {
         peers::waku_connect(&self.ctx, address, timeout).await
     }
 
+    /// Waku protocols supported by the peer at `peer_addr`, a multiaddress including its
+    /// `/p2p/` peer id. The node dials the peer if needed to run the identify exchange,
+    /// and leaves the connection open.
+    /// Only the protocols listed in [`WAKU_PROTOCOLS`] can be reported
+    pub async fn peer_protocols(&self, peer_addr: &str) -> Result<Vec<String>> {
+        peers::waku_peer_protocols(&self.ctx, peer_addr).await
+    }
+
+    /// Ids of the peers in the peer store, whether the node is connected to them or not
+    pub async fn known_peers(&self) -> Result<Vec<String>> {
+        peers::waku_get_peerids_from_peerstore(&self.ctx).await
+    }
+
+    /// Ids of the peers the node is currently connected to
+    pub async fn connected_peer_ids(&self) -> Result<Vec<String>> {
+        peers::waku_get_connected_peers(&self.ctx).await
+    }
+
+    /// Peers the node is currently connected to, with the protocol ids they support, without
+    /// their addresses which libwaku doesn't report. Takes a call to libwaku for each of
+    /// [`WAKU_PROTOCOLS`]
+    pub async fn connected_peers(&self) -> Result<Vec<WakuPeerData>> {
+        peers::waku_connected_peers(&self.ctx).await
+    }
+
+    /// Number of peers the node is currently connected to
+    pub async fn connected_peer_count(&self) -> Result<usize> {
+        peers::waku_connected_peer_count(&self.ctx).await
+    }
+
+    /// Peers whose ENR advertises `shard` of cluster `cluster_id`, among the ones whose ENR
+    /// is known to the bindings, see [`WakuNodeHandle::remember_peer_enr`]. libwaku doesn't
+    /// expose the ENRs of the peers it discovers through discv5 or peer exchange
+    pub async fn peers_in_shard(&self, cluster_id: u16, shard: u16) -> Result<Vec<WakuPeerData>> {
+        peers::waku_peers_in_shard(&self.ctx, cluster_id, shard).await
+    }
+
+    /// Peers in the peer store known to support `protocol`
+    pub async fn peers_by_protocol(&self, protocol: Protocol) -> Result<Vec<WakuPeerData>> {
+        peers::waku_peers_by_protocol(&self.ctx, protocol).await
+    }
+
+    /// Serve the HTTP debugging bridge on [`RestConfig::bind_addr`].
+    /// Node calls requested over HTTP are executed by the returned future, so it must be kept
+    /// polled for the endpoints to answer. Dropping it shuts the server down.
+    #[cfg(feature = "rest")]
+    pub async fn serve_rest(&self, config: RestConfig) -> Result<()> {
+        rest::serve(self, config).await
+    }
+
     pub async fn relay_publish_txt(
         &self,
         pubsub_topic: &PubsubTopic,
@@ -137,17 +446,223 @@ impl WakuNodeHandle<Running> {
         relay::waku_relay_publish_message(&self.ctx, message, pubsub_topic, timeout).await
     }
 
+    /// Whether the node received or published a message with `hash` lately, among the latest
+    /// [`SEEN_MESSAGES_CAPACITY`] messages. libwaku keeps no such cache, the bindings do
+    pub fn has_seen(&self, hash: &MessageHash) -> bool {
+        self.ctx.has_seen(hash)
+    }
+
+    /// Publish a message using Waku Relay on the pubsub topic derived from its content topic
+    /// through [RFC 51](https://rfc.vac.dev/spec/51/) autosharding, as
+    /// [`auto_shard`](WakuContentTopic::auto_shard) computes it for The Waku Network and its
+    /// [`WAKU_NETWORK_SHARD_COUNT`](crate::WAKU_NETWORK_SHARD_COUNT) shards of generation `0`.
+    /// Fails with [`WakuError::InvalidConfig`] when the node runs in another cluster, whose
+    /// shard count isn't known
+    pub async fn relay_publish_autoshard(
+        &self,
+        message: &WakuMessage,
+        timeout: Option<Duration>,
+    ) -> Result<MessageHash> {
+        relay::waku_relay_publish_autoshard(&self.ctx, message, timeout).await
+    }
+
+    /// Publish a message using Waku Relay, returning its hash along with the json handed over
+    /// to libwaku, for audit logs. A missing timestamp is set to the current time beforehand
+    pub async fn relay_publish_message_logged(
+        &self,
+        message: &WakuMessage,
+        pubsub_topic: &PubsubTopic,
+        timeout: Option<Duration>,
+    ) -> Result<(MessageHash, String)> {
+        relay::waku_relay_publish_message_logged(&self.ctx, message, pubsub_topic, timeout).await
+    }
+
+    /// Publish a message using Waku Relay, retrying up to `max_attempts` attempts in total
+    /// while the failure is [retryable](crate::general::WakuError::is_retryable), as when the
+    /// mesh isn't formed yet. The delay between attempts starts at `backoff` and doubles after
+    /// each of them. Other failures are returned at once. `max_attempts` of 0 is rejected with
+    /// [`WakuError::InvalidConfig`]
+    pub async fn relay_publish_with_retry(
+        &self,
+        message: &WakuMessage,
+        pubsub_topic: &PubsubTopic,
+        max_attempts: usize,
+        backoff: Duration,
+    ) -> Result<MessageHash> {
+        if max_attempts == 0 {
+            return Err(WakuError::InvalidConfig(
+                "relay publication needs at least one attempt".to_string(),
+            ));
+        }
+        let mut delay = backoff;
+        for _ in 1..max_attempts {
+            match self
+                .relay_publish_message(message, pubsub_topic, None)
+                .await
+            {
+                Err(err) if err.is_retryable() => {
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+        self.relay_publish_message(message, pubsub_topic, None)
+            .await
+    }
+
+    /// Publish `message` on `pubsub_topic` unless the store node at `store_peer` already has it,
+    /// making republication idempotent. Returns `None` when the message was already stored.
+    /// Messages are matched by their deterministic hash, so the message must keep its timestamp
+    /// between attempts. The check isn't atomic: a copy published by someone else between the
+    /// store query and the publication, or not yet archived by the store node, goes unnoticed
+    pub async fn publish_if_absent(
+        &self,
+        message: &WakuMessage,
+        pubsub_topic: &PubsubTopic,
+        store_peer: &str,
+    ) -> Result<Option<MessageHash>> {
+        let hash = compute_message_hash(message, pubsub_topic);
+        let query = StoreQueryRequest::new()
+            .with_include_data(false)
+            .with_pubsub_topic(Some(pubsub_topic.clone()))
+            .with_message_hashes(vec![hash.clone()]);
+        let response = store::waku_store_query(&self.ctx, query, store_peer, None).await?;
+        if response.messages.iter().any(|msg| msg.message_hash == hash) {
+            return Ok(None);
+        }
+
+        self.relay_publish_message(message, pubsub_topic, None)
+            .await
+            .map(Some)
+    }
+
+    /// Publish `payload` as a message signed with the configured `node_key`,
+    /// so receivers can verify authorship with [`recover_signer`](crate::general::signing::recover_signer).
+    /// libwaku doesn't sign on publish, hence the node must be created with an explicit `node_key`
+    pub async fn relay_publish_signed<PAYLOAD: AsRef<[u8]>>(
+        &self,
+        payload: PAYLOAD,
+        content_topic: WakuContentTopic,
+        pubsub_topic: &PubsubTopic,
+    ) -> Result<MessageHash> {
+        let node_key = self
+            .ctx
+            .node_key()
+            .ok_or_else(|| "signing requires a node_key in the node configuration".to_string())?;
+        let message = WakuMessage::new(payload, content_topic, 0, Vec::new(), false);
+        let message = signing::sign_message(&message, node_key);
+        relay::waku_relay_publish_message(&self.ctx, &message, pubsub_topic, None).await
+    }
+
+    /// Current health of a subscribed relay topic, as last reported by a
+    /// `relay_topic_health_change` event. Topics without any report yet are
+    /// [`TopicHealth::UnHealthy`]. Errors if the node isn't subscribed to `pubsub_topic`
+    pub async fn topic_health(&self, pubsub_topic: &PubsubTopic) -> Result<TopicHealth> {
+        relay::waku_relay_topic_health(&self.ctx, pubsub_topic)
+    }
+
+    /// Current health of every subscribed relay topic
+    pub async fn all_topic_healths(&self) -> Result<HashMap<PubsubTopic, TopicHealth>> {
+        self.ctx
+            .relay_topics()
+            .into_iter()
+            .map(|pubsub_topic| {
+                let health = relay::waku_relay_topic_health(&self.ctx, &pubsub_topic)?;
+                Ok((pubsub_topic, health))
+            })
+            .collect()
+    }
+
+    /// Fraction of the subscribed relay topics that are at least
+    /// [`TopicHealth::MinimallyHealthy`], as a single readiness signal.
+    /// A node without any relay subscription reports `1.0`, as it has nothing to be unhealthy on
+    pub async fn subscription_health_ratio(&self) -> Result<f64> {
+        let healths = self.all_topic_healths().await?;
+        Ok(events::healthy_ratio(healths.values()))
+    }
+
+    /// Overall health of the node, to probe readiness before publishing. libwaku has no health
+    /// endpoint, so it is derived from libwaku's online status and the relay topic healths
+    pub async fn health(&self) -> Result<NodeHealth> {
+        health::waku_health(&self.ctx).await
+    }
+
+    /// Wait until the node is [`NodeHealth::Ready`], checking its [`health`](Self::health)
+    /// periodically, erroring once `timeout` elapses
+    pub async fn wait_until_ready(&self, timeout: Duration) -> Result<()> {
+        health::waku_wait_until_ready(&self.ctx, timeout).await
+    }
+
+    /// Whether the relay mesh of `pubsub_topic` has enough peers for a publication to reach
+    /// the network, see [`RELAY_MIN_PEERS_TO_PUBLISH`]
+    pub async fn relay_enough_peers(&self, pubsub_topic: &PubsubTopic) -> Result<bool> {
+        relay::waku_relay_enough_peers(&self.ctx, pubsub_topic).await
+    }
+
     /// Closes the pubsub subscription to stop receiving messages matching a content filter. No more messages will be received from this pubsub topic
     pub async fn relay_unsubscribe(&self, pubsub_topic: &PubsubTopic) -> Result<()> {
         relay::waku_relay_unsubscribe(&self.ctx, pubsub_topic).await
     }
 
+    /// Stream of the peer connections and disconnections of the node, from now on.
+    /// Up to [`CONNECTION_EVENTS_CAPACITY`] changes wait to be received, the oldest ones being
+    /// dropped first, see [`EventStream::dropped`]
+    pub async fn connection_events(&self) -> Result<EventStream<ConnectionChangeEvent>> {
+        Ok(event_stream::waku_connection_events(&self.ctx))
+    }
+
+    /// Stream of every event of the node, from now on, queued up to `capacity` events.
+    /// A slow consumer loses events or leaves them waiting as per `policy`, the node is never
+    /// held back
+    pub fn event_stream(&self, capacity: usize, policy: OverflowPolicy) -> EventStream {
+        event_stream::waku_event_stream(&self.ctx, capacity, policy)
+    }
+
+    /// Wait for the first message matching `predicate` received by the node, via relay or filter.
+    /// Messages received before the call are not considered.
+    /// The event callback still receives every message, matching ones included.
+    pub async fn await_message<F>(
+        &self,
+        predicate: F,
+        timeout: Duration,
+    ) -> Result<WakuMessageEvent>
+    where
+        F: Fn(&WakuMessageEvent) -> bool + Send + 'static,
+    {
+        events::waku_await_message(&self.ctx, predicate, timeout).await
+    }
+
+    /// Publish `request` on `pubsub_topic` through relay and wait for the first message
+    /// matching `reply_predicate`, the reply of a request/response service, all within `timeout`.
+    /// The node is subscribed to `pubsub_topic` for the exchange when it isn't already
+    pub async fn request_reply<F>(
+        &self,
+        request: &WakuMessage,
+        reply_predicate: F,
+        pubsub_topic: &PubsubTopic,
+        timeout: Duration,
+    ) -> Result<WakuMessageEvent>
+    where
+        F: Fn(&WakuMessageEvent) -> bool + Send + 'static,
+    {
+        relay::waku_request_reply(&self.ctx, request, reply_predicate, pubsub_topic, timeout).await
+    }
+
+    /// Subscribe to `content_topics` on `pubsub_topic` with filter, through the service node at
+    /// `peer_addr`, a multiaddress including its `/p2p/` peer id, or through any connected
+    /// filter peer with `None`. libwaku picks the filter peer among the connected ones, so
+    /// `peer_addr` is dialed before subscribing: it is favoured, but another connected filter
+    /// peer may serve the subscription. The filter client is mounted when the node is created
+    /// and stays so for the whole lifetime of the node, whether relay is enabled or not: it
+    /// can't be unmounted at runtime
     pub async fn filter_subscribe(
         &self,
         pubsub_topic: &PubsubTopic,
         content_topics: Vec<WakuContentTopic>,
+        peer_addr: Option<&str>,
     ) -> Result<()> {
-        filter::waku_filter_subscribe(&self.ctx, pubsub_topic, content_topics).await
+        filter::waku_filter_subscribe(&self.ctx, pubsub_topic, content_topics, peer_addr).await
     }
 
     pub async fn filter_unsubscribe(
@@ -162,14 +677,110 @@ impl WakuNodeHandle<Running> {
         filter::waku_filter_unsubscribe_all(&self.ctx).await
     }
 
+    /// Mount or unmount the filter client role on a running node.
+    /// libwaku can't change the mounted protocols at runtime, so this always fails with
+    /// [`WakuError::InvalidConfig`] and the node has to be recreated instead. The filter client
+    /// stays mounted for the whole lifetime of the node: `filter_subscribe`,
+    /// `filter_unsubscribe` and `filter_unsubscribe_all` remain available, while relay
+    /// availability only depends on [`WakuNodeConfig::relay`]
+    pub async fn filter_set_enabled(&self, enabled: bool) -> Result<()> {
+        filter::waku_filter_set_enabled(&self.ctx, enabled).await
+    }
+
+    /// Content topics subscribed to through filter, by pubsub topic, sorted.
+    /// libwaku doesn't list them, so these are the subscriptions made through these bindings
+    pub async fn filter_subscriptions(&self) -> Result<Vec<(PubsubTopic, Vec<WakuContentTopic>)>> {
+        Ok(subscriptions::waku_subscription_state(&self.ctx)
+            .filter_subscriptions
+            .into_iter()
+            .map(|subscription| (subscription.pubsub_topic, subscription.content_topics))
+            .collect())
+    }
+
+    /// Relay topics and filter subscriptions of the node, made through these bindings or
+    /// configured at creation, to reapply with
+    /// [`apply_subscription_state`](Self::apply_subscription_state) after a restart
+    pub fn subscription_state(&self) -> SubscriptionState {
+        subscriptions::waku_subscription_state(&self.ctx)
+    }
+
+    /// Subscribe to the relay topics and filter content topics of `state` the node isn't
+    /// subscribed to yet. Existing subscriptions absent from `state` are kept
+    pub async fn apply_subscription_state(&self, state: &SubscriptionState) -> Result<()> {
+        subscriptions::waku_apply_subscription_state(&self.ctx, state).await
+    }
+
+    /// Subscribe to `content_topics` on `pubsub_topic` the way the node allows: with relay
+    /// when it is mounted, as on full nodes, and with filter otherwise, as on edge clients.
+    /// Relay delivers every message of the pubsub topic, so shared code should keep the ones
+    /// [`Subscription::matches`] accepts
+    pub async fn subscribe_auto(
+        &self,
+        pubsub_topic: &PubsubTopic,
+        content_topics: Vec<WakuContentTopic>,
+    ) -> Result<Subscription> {
+        subscriptions::waku_subscribe_auto(&self.ctx, pubsub_topic, content_topics).await
+    }
+
+    /// Undo a subscription made with [`subscribe_auto`](Self::subscribe_auto)
+    pub async fn unsubscribe(&self, subscription: Subscription) -> Result<()> {
+        subscriptions::waku_unsubscribe(&self.ctx, subscription).await
+    }
+
+    /// Publish a message with lightpush through one of the connected lightpush peers, picked
+    /// by libwaku
     pub async fn lightpush_publish_message(
         &self,
         message: &WakuMessage,
         pubsub_topic: &PubsubTopic,
     ) -> Result<MessageHash> {
-        lightpush::waku_lightpush_publish_message(&self.ctx, message, pubsub_topic).await
+        lightpush::waku_lightpush_publish_message(&self.ctx, message, pubsub_topic, None).await
+    }
+
+    /// Publish a message with lightpush through the service node at `peer_addr`, a multiaddress
+    /// including its `/p2p/` peer id, or through any connected lightpush peer with `None`.
+    /// libwaku takes no peer for a lightpush request and picks one among the connected ones,
+    /// so `peer_addr` is dialed before publishing: it is made available to libwaku, but another
+    /// connected lightpush peer may still serve the request
+    pub async fn lightpush_publish_message_to(
+        &self,
+        message: &WakuMessage,
+        pubsub_topic: &PubsubTopic,
+        peer_addr: Option<&str>,
+    ) -> Result<MessageHash> {
+        lightpush::waku_lightpush_publish_message(&self.ctx, message, pubsub_topic, peer_addr).await
+    }
+
+    /// Publish a message with lightpush, dialing `peers` in turn, multiaddresses including
+    /// their `/p2p/` peer id, until the publication succeeds.
+    /// libwaku picks the lightpush peer among the connected ones, so the peer reported in the
+    /// [`LightpushResult`] is the one dialed last, not necessarily the one that served the
+    /// request. Fails with the last error if every attempt failed
+    pub async fn lightpush_publish_failover(
+        &self,
+        message: &WakuMessage,
+        pubsub_topic: &PubsubTopic,
+        peers: &[&str],
+    ) -> Result<LightpushResult> {
+        lightpush::waku_lightpush_publish_failover(&self.ctx, message, pubsub_topic, peers).await
+    }
+
+    /// Run a single store query, leaving pagination to the caller: the next page is requested
+    /// with the response's `pagination_cursor` set in the next `request`, until it is `None`
+    pub async fn store_query_page(
+        &self,
+        request: StoreQueryRequest,
+        peer_addr: &str,
+        timeout: Option<Duration>,
+    ) -> Result<StoreResponse> {
+        store::waku_store_query(&self.ctx, request, peer_addr, timeout).await
     }
 
+    /// Retrieve every stored message matching the criteria, page after page.
+    /// An empty result means nothing matched: an unreachable store node fails with
+    /// [`WakuError::StorePeerUnreachable`](crate::general::WakuError::StorePeerUnreachable)
+    /// and a refused query with
+    /// [`WakuError::StorePeerRejected`](crate::general::WakuError::StorePeerRejected)
     #[allow(clippy::too_many_arguments)]
     pub async fn store_query(
         &self,
@@ -195,8 +806,9 @@ impl WakuNodeHandle<Running> {
                 .with_pagination_cursor(cursor)
                 .with_pagination_forward(true);
 
-            let response =
-                store::waku_store_query(&self.ctx, query, peer_addr, timeout_millis).await?;
+            let response = self
+                .store_query_page(query, peer_addr, timeout_millis)
+                .await?;
 
             messages.extend(response.messages);
 
@@ -210,4 +822,197 @@ impl WakuNodeHandle<Running> {
 
         Ok(messages)
     }
+
+    /// Retrieve the stored messages with the given hashes, whatever their topics and timestamps,
+    /// page after page. Meant to fetch the content of messages known by hash, as listed by
+    /// a query made with `include_data` unset
+    pub async fn store_query_by_hashes(
+        &self,
+        hashes: Vec<MessageHash>,
+        peer_addr: &str,
+        include_data: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<StoreWakuMessageResponse>> {
+        let query = StoreQueryRequest::new()
+            .with_include_data(include_data)
+            .with_message_hashes(hashes);
+
+        let mut messages = Vec::new();
+        let mut cursor: Option<MessageHash> = None;
+        loop {
+            let response = self
+                .store_query_page(
+                    query.clone().with_pagination_cursor(cursor),
+                    peer_addr,
+                    timeout,
+                )
+                .await?;
+            messages.extend(response.messages);
+            cursor = response.pagination_cursor;
+            if cursor.is_none() {
+                return Ok(messages);
+            }
+        }
+    }
+
+    /// Retrieve the stored messages of any of `content_topics`, bucketed by content topic.
+    /// Every requested topic has an entry, empty when nothing was stored for it.
+    /// Within a bucket messages are sorted newest first, as with [`store_query`](Self::store_query)
+    pub async fn store_query_grouped(
+        &self,
+        content_topics: Vec<WakuContentTopic>,
+        pubsub_topic: Option<PubsubTopic>,
+        peer_addr: &str,
+    ) -> Result<HashMap<WakuContentTopic, Vec<StoreWakuMessageResponse>>> {
+        let messages = self
+            .store_query(
+                pubsub_topic,
+                content_topics.clone(),
+                peer_addr,
+                true,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        Ok(store::group_by_content_topic(&content_topics, messages))
+    }
+
+    /// Run `query` against the store node at `peer_addr`, feeding every message to `sink` as its
+    /// page arrives instead of buffering the whole result.
+    /// The next page is only requested once the sink accepted every message of the current one.
+    /// Returns the number of messages accepted by the sink.
+    pub async fn store_query_into<S: MessageSink>(
+        &self,
+        query: StoreQueryRequest,
+        peer_addr: &str,
+        mut sink: S,
+    ) -> Result<usize> {
+        let mut query = query;
+        let mut count = 0;
+
+        loop {
+            let response =
+                store::waku_store_query(&self.ctx, query.clone(), peer_addr, None).await?;
+
+            for message in response.messages {
+                sink.accept(message).await?;
+                count += 1;
+            }
+
+            match response.pagination_cursor {
+                Some(cursor) => query = query.next_page(cursor),
+                None => break,
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Run one paged [`store_query`](Self::store_query) per `(pubsub_topic, content_topics)` group.
+    /// At most 4 groups are queried concurrently, the remaining ones start as soon as one finishes.
+    /// Pages of a single group are still followed sequentially since each one needs the previous cursor.
+    /// Results are merged, deduplicated by message hash and sorted newest first.
+    /// A failed group doesn't cancel the others, its error is returned once all of them are done.
+    pub async fn store_query_parallel(
+        &self,
+        topic_groups: Vec<(PubsubTopic, Vec<WakuContentTopic>)>,
+        peer_addr: &str,
+    ) -> Result<Vec<StoreWakuMessageResponse>> {
+        let mut groups = stream::iter(topic_groups)
+            .map(|(pubsub_topic, content_topics)| {
+                self.store_query(
+                    Some(pubsub_topic),
+                    content_topics,
+                    peer_addr,
+                    true,
+                    None,
+                    None,
+                    None,
+                )
+            })
+            .buffer_unordered(STORE_MAX_PARALLEL_QUERIES);
+
+        let mut seen = HashSet::new();
+        let mut messages: Vec<StoreWakuMessageResponse> = Vec::new();
+        let mut first_error = None;
+        // Every query runs to completion, as an FFI call must not be dropped while in flight
+        while let Some(group) = groups.next().await {
+            match group {
+                Ok(group) => messages.extend(
+                    group
+                        .into_iter()
+                        .filter(|msg| seen.insert(msg.message_hash.clone())),
+                ),
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        if let Some(err) = first_error {
+            return Err(err);
+        }
+
+        messages.sort_by_key(|msg| Reverse(msg.message.as_ref().map(|msg| msg.timestamp)));
+
+        Ok(messages)
+    }
+
+    /// Timestamps of the oldest and newest messages held by the store node at `peer_addr`, in
+    /// unix nanoseconds, or `None` if it holds none. libwaku refuses to dial its own node, so the
+    /// store of this node can only be inspected from another one. A peer that doesn't mount the
+    /// store protocol fails the query, with an error
+    /// [classified](crate::general::WakuError::classify) as `ProtocolNotSupported` when
+    /// libwaku reports why
+    pub async fn store_time_range(&self, peer_addr: &str) -> Result<Option<(u64, u64)>> {
+        store::waku_store_time_range(&self.ctx, peer_addr).await
+    }
+
+    /// Delete the messages of this node's store older than `older_than`, returning how many were
+    /// removed. libwaku has no call for it yet, so this always fails with
+    /// [`WakuError::InvalidConfig`], whether the store protocol is mounted or not: configure
+    /// [`store_message_retention_policy`](WakuNodeConfig::store_message_retention_policy) instead
+    pub async fn store_prune(&self, older_than: Duration) -> Result<u64> {
+        store::waku_store_prune(&self.ctx, older_than).await
+    }
+
+    /// Retrieve the `n` newest messages, querying the store backward from now.
+    /// Pages are requested only until `n` messages are gathered.
+    /// The result is sorted newest first.
+    pub async fn store_latest(
+        &self,
+        content_topics: Vec<WakuContentTopic>,
+        pubsub_topic: Option<PubsubTopic>,
+        n: usize,
+        peer_addr: &str,
+    ) -> Result<Vec<StoreWakuMessageResponse>> {
+        let mut cursor: Option<MessageHash> = None;
+
+        let mut messages: Vec<StoreWakuMessageResponse> = Vec::with_capacity(n);
+
+        while messages.len() < n {
+            let remaining = (n - messages.len()).min(STORE_MAX_PAGE_SIZE) as u64;
+            let query = StoreQueryRequest::new()
+                .with_pubsub_topic(pubsub_topic.clone())
+                .with_content_topics(content_topics.clone())
+                .with_pagination_cursor(cursor)
+                .with_pagination_forward(false)
+                .with_pagination_limit(Some(remaining));
+
+            let response = store::waku_store_query(&self.ctx, query, peer_addr, None).await?;
+
+            // Each page is sorted oldest first, even when paginating backward
+            messages.extend(response.messages.into_iter().rev());
+
+            if response.pagination_cursor.is_none() {
+                break;
+            }
+            cursor = response.pagination_cursor;
+        }
+
+        messages.truncate(n);
+
+        Ok(messages)
+    }
 }
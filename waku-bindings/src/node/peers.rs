@@ -1,15 +1,19 @@
 //! Waku [peer handling and connection](https://rfc.vac.dev/spec/36/#connecting-to-peers) methods
 
 // std
+use std::collections::HashSet;
 use std::ffi::CString;
 use std::time::Duration;
 // crates
-use multiaddr::Multiaddr;
+use multiaddr::{Multiaddr, Protocol as MultiaddrProtocol};
 // internal
-use crate::general::libwaku_response::{handle_no_response, LibwakuResponse};
-use crate::general::Result;
+use crate::general::libwaku_response::{handle_no_response, handle_response, LibwakuResponse};
+use crate::general::{Result, WakuError};
 use crate::handle_ffi_call;
-use crate::node::context::WakuNodeContext;
+use crate::node::config::WakuNodeConfig;
+use crate::node::context::{NodeGuard, WakuNodeContext};
+use crate::node::dns::{enr_multiaddrs, enr_peer_id, Enr};
+use crate::node::events::{PeerConnectionEvent, WakuEvent};
 
 /// Dial peer using a multiaddress
 /// If `timeout` as milliseconds doesn't fit into a `i32` it is clamped to [`i32::MAX`]
@@ -21,16 +25,512 @@ pub async fn waku_connect(
     address: &Multiaddr,
     timeout: Option<Duration>,
 ) -> Result<()> {
+    // Allowed before dialing, the connection may be reported before the dial returns
+    match address_peer_id(address) {
+        Some(peer_id) => ctx.peer_dialed(&peer_id),
+        None if ctx.manual_connections_only() => {
+            return Err(WakuError::Parse(format!(
+                "peer address {address} is missing its /p2p/ peer id, required to dial with auto_connect_discovered off"
+            )))
+        }
+        None => {}
+    }
+
     let address =
         CString::new(address.to_string()).expect("CString should build properly from multiaddress");
 
     handle_ffi_call!(
         waku_sys::waku_connect,
         handle_no_response,
-        ctx.get_ptr(),
+        ctx.ptr_guard()?,
         address.as_ptr(),
         timeout
             .map(|duration| duration.as_millis().try_into().unwrap_or(u32::MAX))
             .unwrap_or(0)
     )
 }
+
+/// Dial the static bootstrap peers of the node concurrently. Best-effort: the peers that
+/// can't be reached are only logged
+pub async fn waku_connect_static_bootstrap_peers(ctx: &WakuNodeContext) {
+    let dials = ctx
+        .static_bootstrap_peers()
+        .iter()
+        .map(|address| async move {
+            if let Err(e) = waku_connect(ctx, address, None).await {
+                log::warn!("could not dial static bootstrap peer {address}: {e}");
+            }
+        });
+    futures::future::join_all(dials).await;
+}
+
+/// Peer id of the `/p2p/{peer_id}` component of `address`
+pub(crate) fn address_peer_id(address: &Multiaddr) -> Option<String> {
+    address
+        .iter()
+        .find_map(|protocol| match protocol {
+            MultiaddrProtocol::P2p(_) => Some(Multiaddr::empty().with(protocol)),
+            _ => None,
+        })
+        .and_then(|p2p| p2p.to_string().strip_prefix("/p2p/").map(str::to_string))
+}
+
+/// Protocol ids of the Waku protocols a peer can support, the ones libwaku speaks.
+/// Each id reads `/vac/waku/{name}/{version}`, the name being the key of
+/// [`protocol_versions`](crate::WakuNodeConfig::protocol_versions), where `store-query` and
+/// `filter-subscribe` are listed as `store` and `filter`
+pub const WAKU_PROTOCOLS: [&str; 7] = [
+    "/vac/waku/relay/2.0.0",
+    // store v3 and the legacy store v2
+    "/vac/waku/store-query/3.0.0",
+    "/vac/waku/store/2.0.0-beta4",
+    "/vac/waku/filter-subscribe/2.0.0-beta1",
+    "/vac/waku/lightpush/2.0.0-beta1",
+    "/vac/waku/peer-exchange/2.0.0-alpha1",
+    "/vac/waku/metadata/1.0.0",
+];
+
+/// Versions of protocol `name` libwaku speaks, from the `/vac/waku/{name}/{version}` ids of
+/// [`WAKU_PROTOCOLS`]. The store and filter ids are grouped under `store` and `filter`
+pub(crate) fn supported_protocol_versions(name: &str) -> impl Iterator<Item = &'static str> + '_ {
+    WAKU_PROTOCOLS.iter().filter_map(move |id| {
+        let (protocol, version) = id.strip_prefix("/vac/waku/")?.split_once('/')?;
+        let protocol = match protocol {
+            "store-query" => "store",
+            "filter-subscribe" => "filter",
+            protocol => protocol,
+        };
+        (protocol == name).then_some(version)
+    })
+}
+
+/// Waku protocols a peer can be looked up by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Relay,
+    Store,
+    Lightpush,
+    Filter,
+    PeerExchange,
+}
+
+impl Protocol {
+    /// Ids, among [`WAKU_PROTOCOLS`], a peer serving this protocol advertises
+    pub fn protocol_ids(&self) -> &'static [&'static str] {
+        match self {
+            Self::Relay => &["/vac/waku/relay/2.0.0"],
+            Self::Store => &["/vac/waku/store-query/3.0.0", "/vac/waku/store/2.0.0-beta4"],
+            Self::Lightpush => &["/vac/waku/lightpush/2.0.0-beta1"],
+            Self::Filter => &["/vac/waku/filter-subscribe/2.0.0-beta1"],
+            Self::PeerExchange => &["/vac/waku/peer-exchange/2.0.0-alpha1"],
+        }
+    }
+}
+
+/// Peer known to the node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WakuPeerData {
+    pub peer_id: String,
+    /// Addresses the peer is reachable at, empty when libwaku doesn't report them
+    pub addresses: Vec<Multiaddr>,
+    /// Protocol ids the peer is known to support
+    pub protocols: Vec<String>,
+    pub connected: bool,
+}
+
+/// Ids of the peers in the peer store known to support `protocol`
+pub async fn waku_get_peerids_by_protocol(
+    ctx: &WakuNodeContext,
+    protocol: &str,
+) -> Result<Vec<String>> {
+    let protocol = CString::new(protocol).expect("CString should build properly from protocol");
+
+    handle_ffi_call!(
+        waku_sys::waku_get_peerids_by_protocol,
+        handle_response,
+        ctx.ptr_guard()?,
+        protocol.as_ptr()
+    )
+}
+
+/// Waku protocols supported by the peer at `peer_addr`, as learnt by the identify exchange
+/// run when connecting to it. The node connects to the peer if needed and stays connected.
+/// libwaku only exposes the peers of a given protocol, so protocols other than
+/// [`WAKU_PROTOCOLS`] are not reported
+pub async fn waku_peer_protocols(ctx: &WakuNodeContext, peer_addr: &str) -> Result<Vec<String>> {
+    let address: Multiaddr = peer_addr
+        .parse()
+        .map_err(|e| format!("invalid peer address {peer_addr}: {e}"))?;
+    let peer_id = address_peer_id(&address)
+        .ok_or_else(|| format!("peer address {peer_addr} is missing its /p2p/ peer id"))?;
+
+    waku_connect(ctx, &address, None).await?;
+
+    let mut protocols = Vec::new();
+    for protocol in WAKU_PROTOCOLS {
+        if waku_get_peerids_by_protocol(ctx, protocol)
+            .await?
+            .contains(&peer_id)
+        {
+            protocols.push(protocol.to_string());
+        }
+    }
+    Ok(protocols)
+}
+
+/// Ids of the peers in the peer store, connected or not
+pub async fn waku_get_peerids_from_peerstore(ctx: &WakuNodeContext) -> Result<Vec<String>> {
+    handle_ffi_call!(
+        waku_sys::waku_get_peerids_from_peerstore,
+        handle_response,
+        ctx.ptr_guard()?
+    )
+}
+
+/// Ids of the peers the node is currently connected to
+pub async fn waku_get_connected_peers(ctx: &WakuNodeContext) -> Result<Vec<String>> {
+    handle_ffi_call!(
+        waku_sys::waku_get_connected_peers,
+        handle_response,
+        ctx.ptr_guard()?
+    )
+}
+
+/// Number of peers the node is currently connected to.
+/// libwaku has no call returning the count alone, so the connected peer ids are counted
+pub async fn waku_connected_peer_count(ctx: &WakuNodeContext) -> Result<usize> {
+    Ok(waku_get_connected_peers(ctx).await?.len())
+}
+
+/// Peers the node is currently connected to, with the ids among [`WAKU_PROTOCOLS`] they
+/// support. libwaku has no call describing the connected peers, only one listing their ids
+/// and one listing the peers of a protocol, queried for each of [`WAKU_PROTOCOLS`].
+/// Their addresses aren't reported at all, so [`WakuPeerData::addresses`] is left empty
+pub async fn waku_connected_peers(ctx: &WakuNodeContext) -> Result<Vec<WakuPeerData>> {
+    let mut peers: Vec<WakuPeerData> = waku_get_connected_peers(ctx)
+        .await?
+        .into_iter()
+        .map(|peer_id| WakuPeerData {
+            peer_id,
+            addresses: Vec::new(),
+            protocols: Vec::new(),
+            connected: true,
+        })
+        .collect();
+    for protocol_id in WAKU_PROTOCOLS {
+        let peer_ids = waku_get_peerids_by_protocol(ctx, protocol_id).await?;
+        for peer in peers
+            .iter_mut()
+            .filter(|peer| peer_ids.contains(&peer.peer_id))
+        {
+            peer.protocols.push(protocol_id.to_string());
+        }
+    }
+    Ok(peers)
+}
+
+/// Peers in the peer store known to support `protocol`, under any of its protocol ids.
+/// Only the ids of `protocol` are listed in [`WakuPeerData::protocols`]
+pub async fn waku_peers_by_protocol(
+    ctx: &WakuNodeContext,
+    protocol: Protocol,
+) -> Result<Vec<WakuPeerData>> {
+    let connected = waku_get_connected_peers(ctx).await?;
+    let mut peers: Vec<WakuPeerData> = Vec::new();
+    for protocol_id in protocol.protocol_ids() {
+        for peer_id in waku_get_peerids_by_protocol(ctx, protocol_id).await? {
+            match peers.iter_mut().find(|peer| peer.peer_id == peer_id) {
+                Some(peer) => peer.protocols.push(protocol_id.to_string()),
+                None => peers.push(WakuPeerData {
+                    connected: connected.contains(&peer_id),
+                    peer_id,
+                    addresses: Vec::new(),
+                    protocols: vec![protocol_id.to_string()],
+                }),
+            }
+        }
+    }
+    Ok(peers)
+}
+
+/// Peers whose ENR advertises `shard` of cluster `cluster_id`.
+/// libwaku doesn't expose the ENRs of the peers it discovers, so only the peers whose ENR the
+/// bindings learnt are considered: the ones found by DNS discovery with a
+/// [`DnsResolver`](crate::node::DnsResolver) and the ones given to [`waku_remember_peer_enr`].
+/// Their protocols are the ones listed in the ENR
+pub async fn waku_peers_in_shard(
+    ctx: &WakuNodeContext,
+    cluster_id: u16,
+    shard: u16,
+) -> Result<Vec<WakuPeerData>> {
+    let connected = waku_get_connected_peers(ctx).await?;
+    Ok(ctx
+        .peer_enrs()
+        .iter()
+        .filter(|enr| enr_in_shard(enr, cluster_id, shard))
+        .map(|enr| {
+            let peer_id = enr_peer_id(enr);
+            WakuPeerData {
+                connected: connected.contains(&peer_id),
+                peer_id,
+                addresses: enr_multiaddrs(enr),
+                protocols: enr_protocols(enr),
+            }
+        })
+        .collect())
+}
+
+/// Keep the `enr:` encoded ENR of a peer, so its shards are known
+pub fn waku_remember_peer_enr(ctx: &WakuNodeContext, enr: &str) -> Result<()> {
+    let enr: Enr = enr.parse().map_err(|e| format!("invalid enr {enr}: {e}"))?;
+    ctx.remember_peer_enr(enr);
+    Ok(())
+}
+
+/// Value of the `key` entry of `enr`, decoded from its rlp encoding
+fn enr_value<'a>(enr: &'a Enr, key: &str) -> Option<&'a [u8]> {
+    rlp::Rlp::new(enr.get_raw_rlp(key)?).data().ok()
+}
+
+/// Whether `enr` advertises `shard` of cluster `cluster_id`, either through the shard list of
+/// its `rs` entry or the bit vector of its `rsv` entry, as per
+/// [RFC 51](https://rfc.vac.dev/waku/standards/core/51/waku2-relay-sharding)
+pub(crate) fn enr_in_shard(enr: &Enr, cluster_id: u16, shard: u16) -> bool {
+    if let Some(rs) = enr_value(enr, "rs") {
+        // cluster id, number of shards, then each shard, as big endian u16
+        if let [c0, c1, count, shards @ ..] = rs {
+            return u16::from_be_bytes([*c0, *c1]) == cluster_id
+                && shards
+                    .chunks_exact(2)
+                    .take(usize::from(*count))
+                    .any(|s| u16::from_be_bytes([s[0], s[1]]) == shard);
+        }
+        return false;
+    }
+    if let Some([c0, c1, bits @ ..]) = enr_value(enr, "rsv") {
+        // cluster id, then a bit per shard, least significant bit first within each byte
+        return u16::from_be_bytes([*c0, *c1]) == cluster_id
+            && bits
+                .get(usize::from(shard / 8))
+                .is_some_and(|byte| byte & (1 << (shard % 8)) != 0);
+    }
+    false
+}
+
+/// Protocol ids of the capabilities set in the `waku2` entry of `enr`, as per
+/// [RFC 31](https://rfc.vac.dev/waku/standards/core/31/enr)
+fn enr_protocols(enr: &Enr) -> Vec<String> {
+    let Some(&[flags]) = enr_value(enr, "waku2") else {
+        return Vec::new();
+    };
+    [
+        Protocol::Relay,
+        Protocol::Store,
+        Protocol::Filter,
+        Protocol::Lightpush,
+    ]
+    .iter()
+    .enumerate()
+    .filter(|(bit, _)| flags & (1 << bit) != 0)
+    .map(|(_, protocol)| protocol.protocol_ids()[0].to_string())
+    .collect()
+}
+
+/// Allow and deny lists of peer ids, enforced by disconnecting any disallowed peer
+/// as soon as its connection is reported by a `connection_change` event.
+/// libwaku has no connection gater, so a disallowed peer stays connected for the
+/// short time it takes to process the event.
+#[derive(Clone, Debug, Default)]
+pub struct PeerGate {
+    allowed: Option<HashSet<String>>,
+    denied: HashSet<String>,
+    /// Only the peers dialed by the node are allowed, see `auto_connect_discovered`
+    manual_only: bool,
+    dialed: HashSet<String>,
+}
+
+impl PeerGate {
+    /// Gate matching the lists set in `config`, `None` if there is nothing to enforce
+    pub fn from_config(config: &WakuNodeConfig) -> Option<Self> {
+        let manual_only = config.auto_connect_discovered == Some(false);
+        if config.allowed_peers.is_none() && config.denied_peers.is_none() && !manual_only {
+            return None;
+        }
+
+        Some(Self {
+            allowed: config
+                .allowed_peers
+                .as_ref()
+                .map(|peers| peers.iter().cloned().collect()),
+            denied: config.denied_peers.iter().flatten().cloned().collect(),
+            manual_only,
+            dialed: HashSet::new(),
+        })
+    }
+
+    /// Whether only the peers dialed by the node are allowed
+    pub fn manual_only(&self) -> bool {
+        self.manual_only
+    }
+
+    /// Allow `peer_id` when only dialed peers are
+    pub fn dialed(&mut self, peer_id: &str) {
+        if self.manual_only {
+            self.dialed.insert(peer_id.to_string());
+        }
+    }
+
+    /// A peer is allowed when it isn't denied, if an allow list exists it is part of it,
+    /// and if only dialed peers are allowed it was dialed
+    pub fn is_allowed(&self, peer_id: &str) -> bool {
+        !self.denied.contains(peer_id)
+            && !matches!(&self.allowed, Some(allowed) if !allowed.contains(peer_id))
+            && (!self.manual_only || self.dialed.contains(peer_id))
+    }
+
+    /// Peer to disconnect if `event` reports that a disallowed peer joined
+    pub fn rejected<'e>(&self, event: &'e WakuEvent) -> Option<&'e str> {
+        match event {
+            WakuEvent::ConnectionChange(evt)
+                if evt.peer_event == PeerConnectionEvent::Joined
+                    && !self.is_allowed(&evt.peer_id) =>
+            {
+                Some(&evt.peer_id)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Disconnect from the peer `peer_id`
+pub(crate) async fn waku_disconnect_peer_by_id(node: NodeGuard<'_>, peer_id: &str) -> Result<()> {
+    let peer_id = CString::new(peer_id).expect("CString should build properly from peer id");
+    handle_ffi_call!(
+        waku_sys::waku_disconnect_peer_by_id,
+        handle_no_response,
+        node,
+        peer_id.as_ptr()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        enr_in_shard, enr_protocols, supported_protocol_versions, PeerGate, Protocol,
+        WAKU_PROTOCOLS,
+    };
+    use crate::node::dns::{enr_peer_id, Enr};
+    use crate::WakuNodeConfig;
+
+    /// Peer of cluster 1 in shards 2 and 5 through `rs`, with relay and store capabilities
+    const SHARD_LIST_ENR: &str = "enr:-Ja4QHMgOFLhm1PRJdZSoqR5YgTUqkgThhQNmR-fcKknqGbWbvnrF4ScYlNcd5F3Gg5pykksV6CgFkiXl5HXGsREn-wBgmlkgnY0gmlwhAoAAAeCcnOHAAECAAIABYlzZWNwMjU2azGhApicC3bLVjlx_cm-8x7AbDVg8ySdbunl2DxXYlWW4F9vg3RjcILqYIV3YWt1MgM";
+    /// Peer of cluster 1 in shard 3 through the `rsv` bit vector
+    const SHARD_BITS_ENR: &str = "enr:-QEMuEAXqX4rAxwPfM-z7K5DUBREkr4hCu3O_EqGVmxvmchlxVSq0Q2Cc2F6q67L2Jqk4Ys54WORTMxEvObXULvenk6RAYJpZIJ2NIJpcIQKAAAIg3JzdriCAAEIAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAIlzZWNwMjU2azGhA_mR-UTR4ZVKf8i5v2Lg148BX0wHdi1QXiDmxFJgo2Ybg3RjcILqYA";
+
+    #[test]
+    fn shard_list_membership() {
+        let enr: Enr = SHARD_LIST_ENR.parse().unwrap();
+        assert!(enr_in_shard(&enr, 1, 2));
+        assert!(enr_in_shard(&enr, 1, 5));
+        assert!(!enr_in_shard(&enr, 1, 3));
+        assert!(!enr_in_shard(&enr, 2, 2));
+        assert_eq!(
+            enr_peer_id(&enr),
+            "16Uiu2HAm5hPxT7s3TA2f4b4YjAPzS7yLRSgdj87ysDZ6NAXMiRuY"
+        );
+        assert_eq!(
+            enr_protocols(&enr),
+            vec!["/vac/waku/relay/2.0.0", "/vac/waku/store-query/3.0.0"]
+        );
+    }
+
+    #[test]
+    fn shard_bit_vector_membership() {
+        let enr: Enr = SHARD_BITS_ENR.parse().unwrap();
+        assert!(enr_in_shard(&enr, 1, 3));
+        assert!(!enr_in_shard(&enr, 1, 2));
+        assert!(!enr_in_shard(&enr, 1, 1000));
+        assert!(!enr_in_shard(&enr, 0, 3));
+        assert!(enr_protocols(&enr).is_empty());
+    }
+
+    #[test]
+    fn protocol_ids_are_known_waku_protocols() {
+        for protocol in [
+            Protocol::Relay,
+            Protocol::Store,
+            Protocol::Lightpush,
+            Protocol::Filter,
+            Protocol::PeerExchange,
+        ] {
+            assert!(!protocol.protocol_ids().is_empty());
+            assert!(protocol
+                .protocol_ids()
+                .iter()
+                .all(|id| WAKU_PROTOCOLS.contains(id)));
+        }
+        assert!(Protocol::Store
+            .protocol_ids()
+            .contains(&"/vac/waku/store-query/3.0.0"));
+    }
+
+    #[test]
+    fn protocol_versions_grouped_by_name() {
+        let store: Vec<_> = supported_protocol_versions("store").collect();
+        assert_eq!(store, vec!["3.0.0", "2.0.0-beta4"]);
+        let filter: Vec<_> = supported_protocol_versions("filter").collect();
+        assert_eq!(filter, vec!["2.0.0-beta1"]);
+        assert_eq!(supported_protocol_versions("unknown").count(), 0);
+    }
+
+    #[test]
+    fn no_gate_without_lists() {
+        assert!(PeerGate::from_config(&WakuNodeConfig::default()).is_none());
+    }
+
+    #[test]
+    fn gate_honours_allow_and_deny_lists() {
+        let gate = PeerGate::from_config(&WakuNodeConfig {
+            allowed_peers: Some(vec!["peerA".to_string(), "peerB".to_string()]),
+            denied_peers: Some(vec!["peerB".to_string()]),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(gate.is_allowed("peerA"));
+        assert!(!gate.is_allowed("peerB"));
+        assert!(!gate.is_allowed("peerC"));
+
+        let gate = PeerGate::from_config(&WakuNodeConfig {
+            denied_peers: Some(vec!["peerB".to_string()]),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(gate.is_allowed("peerA"));
+        assert!(!gate.is_allowed("peerB"));
+    }
+
+    #[test]
+    fn gate_allows_only_dialed_peers_without_auto_connect() {
+        assert!(PeerGate::from_config(&WakuNodeConfig {
+            auto_connect_discovered: Some(true),
+            ..Default::default()
+        })
+        .is_none());
+
+        let mut gate = PeerGate::from_config(&WakuNodeConfig {
+            auto_connect_discovered: Some(false),
+            denied_peers: Some(vec!["peerB".to_string()]),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!gate.is_allowed("peerA"));
+
+        gate.dialed("peerA");
+        gate.dialed("peerB");
+        assert!(gate.is_allowed("peerA"));
+        assert!(!gate.is_allowed("peerB"));
+        assert!(!gate.is_allowed("peerC"));
+    }
+}
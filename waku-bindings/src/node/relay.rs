@@ -2,14 +2,18 @@
 
 // std
 use std::ffi::CString;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 // internal
-use crate::general::contenttopic::{Encoding, WakuContentTopic};
+use crate::general::contenttopic::{
+    Encoding, WakuContentTopic, WAKU_NETWORK_CLUSTER_ID, WAKU_NETWORK_SHARD_COUNT,
+};
 use crate::general::libwaku_response::{handle_no_response, handle_response, LibwakuResponse};
 use crate::general::pubsubtopic::PubsubTopic;
-use crate::general::{messagehash::MessageHash, Result, WakuMessage};
+use crate::general::time::Timestamp;
+use crate::general::{messagehash::MessageHash, ErrorCategory, Result, WakuError, WakuMessage};
 use crate::handle_ffi_call;
 use crate::node::context::WakuNodeContext;
+use crate::node::events::{waku_message_waiter, TopicHealth, WakuMessageEvent};
 
 /// Create a content topic according to [RFC 23](https://rfc.vac.dev/spec/23/)
 /// As per the [specification](https://rfc.vac.dev/spec/36/#extern-char-waku_content_topicchar-applicationname-unsigned-int-applicationversion-char-contenttopicname-char-encoding)
@@ -31,7 +35,7 @@ pub async fn waku_create_content_topic(
     handle_ffi_call!(
         waku_sys::waku_content_topic,
         handle_response,
-        ctx.get_ptr(),
+        ctx.ptr_guard()?,
         application_name.as_ptr(),
         application_version,
         content_topic_name.as_ptr(),
@@ -47,11 +51,66 @@ pub async fn waku_relay_publish_message(
     pubsub_topic: &PubsubTopic,
     timeout: Option<Duration>,
 ) -> Result<MessageHash> {
-    let message = CString::new(
-        serde_json::to_string(&message)
-            .expect("WakuMessages should always be able to success serializing"),
-    )
-    .expect("CString should build properly from the serialized waku message");
+    let json = serde_json::to_string(&message)
+        .expect("WakuMessages should always be able to success serializing");
+    let hash = publish_json(ctx, &json, pubsub_topic, timeout).await?;
+    ctx.message_published(message, &hash);
+    Ok(hash)
+}
+
+/// Publish a message using Waku Relay on the shard its content topic maps to under generation
+/// `0` of RFC 51 autosharding, with the [`WAKU_NETWORK_SHARD_COUNT`] shards of The Waku Network.
+/// The shard count of other clusters isn't known to the bindings, so nodes of another cluster
+/// get [`WakuError::InvalidConfig`]
+pub async fn waku_relay_publish_autoshard(
+    ctx: &WakuNodeContext,
+    message: &WakuMessage,
+    timeout: Option<Duration>,
+) -> Result<MessageHash> {
+    if ctx.cluster_id() != usize::from(WAKU_NETWORK_CLUSTER_ID) {
+        return Err(WakuError::InvalidConfig(format!(
+            "autosharding is only supported on cluster {WAKU_NETWORK_CLUSTER_ID}, the node runs in cluster {}",
+            ctx.cluster_id()
+        )));
+    }
+    let pubsub_topic = message
+        .content_topic
+        .pubsub_topic(WAKU_NETWORK_CLUSTER_ID, WAKU_NETWORK_SHARD_COUNT);
+    waku_relay_publish_message(ctx, message, &pubsub_topic, timeout).await
+}
+
+/// Publish a message using Waku Relay, also returning the json handed over to libwaku.
+/// A missing timestamp is set to the current time beforehand, so the json is the one relayed
+pub async fn waku_relay_publish_message_logged(
+    ctx: &WakuNodeContext,
+    message: &WakuMessage,
+    pubsub_topic: &PubsubTopic,
+    timeout: Option<Duration>,
+) -> Result<(MessageHash, String)> {
+    let json = wire_json(message);
+    let hash = publish_json(ctx, &json, pubsub_topic, timeout).await?;
+    ctx.message_published(message, &hash);
+    Ok((hash, json))
+}
+
+/// Json of `message` as relayed, with a missing timestamp set to the current time
+fn wire_json(message: &WakuMessage) -> String {
+    let mut message = message.clone();
+    if message.timestamp == 0 {
+        message.timestamp = Timestamp::now().as_nanos();
+    }
+    serde_json::to_string(&message)
+        .expect("WakuMessages should always be able to success serializing")
+}
+
+async fn publish_json(
+    ctx: &WakuNodeContext,
+    message: &str,
+    pubsub_topic: &PubsubTopic,
+    timeout: Option<Duration>,
+) -> Result<MessageHash> {
+    let message = CString::new(message)
+        .expect("CString should build properly from the serialized waku message");
 
     let pubsub_topic = CString::new(String::from(pubsub_topic))
         .expect("CString should build properly from pubsub topic");
@@ -59,7 +118,7 @@ pub async fn waku_relay_publish_message(
     handle_ffi_call!(
         waku_sys::waku_relay_publish,
         handle_response,
-        ctx.get_ptr(),
+        ctx.ptr_guard()?,
         pubsub_topic.as_ptr(),
         message.as_ptr(),
         timeout
@@ -71,31 +130,180 @@ pub async fn waku_relay_publish_message(
             })
             .unwrap_or(0)
     )
+    .map_err(publish_error)
+}
+
+/// Publish `request` on `pubsub_topic` and wait for the first received message matching
+/// `reply_predicate`, all within `timeout`.
+/// The node subscribes to `pubsub_topic` for the exchange if it isn't subscribed already,
+/// and unsubscribes afterwards. Replies received before the request is published count
+pub async fn waku_request_reply<F>(
+    ctx: &WakuNodeContext,
+    request: &WakuMessage,
+    reply_predicate: F,
+    pubsub_topic: &PubsubTopic,
+    timeout: Duration,
+) -> Result<WakuMessageEvent>
+where
+    F: Fn(&WakuMessageEvent) -> bool + Send + 'static,
+{
+    let deadline = Instant::now() + timeout;
+    let subscribed = ctx.relay_topics().contains(pubsub_topic);
+    if !subscribed {
+        waku_relay_subscribe(ctx, pubsub_topic).await?;
+    }
+
+    let reply = async {
+        let reply = waku_message_waiter(
+            ctx,
+            reply_predicate,
+            deadline.saturating_duration_since(Instant::now()),
+        );
+        waku_relay_publish_message(
+            ctx,
+            request,
+            pubsub_topic,
+            Some(deadline.saturating_duration_since(Instant::now())),
+        )
+        .await?;
+        reply.await
+    }
+    .await;
+
+    if !subscribed {
+        // The outcome of the exchange matters more than a failed clean up
+        let _ = waku_relay_unsubscribe(ctx, pubsub_topic).await;
+    }
+    reply
+}
+
+/// Type the publication failures libwaku only reports as text
+fn publish_error(err: WakuError) -> WakuError {
+    let WakuError::LibwakuFailure(msg) = err else {
+        return err;
+    };
+    match ErrorCategory::of_message(&msg) {
+        ErrorCategory::NoPeers => WakuError::NoPeersToPublish(msg),
+        ErrorCategory::MessageTooLarge => WakuError::MessageTooLarge(msg),
+        _ => WakuError::LibwakuFailure(msg),
+    }
 }
 
 pub async fn waku_relay_subscribe(ctx: &WakuNodeContext, pubsub_topic: &PubsubTopic) -> Result<()> {
-    let pubsub_topic = CString::new(String::from(pubsub_topic))
+    let _reservation = ctx.reserve_relay_subscription(pubsub_topic)?;
+    let topic = CString::new(String::from(pubsub_topic))
         .expect("CString should build properly from pubsub topic");
 
     handle_ffi_call!(
         waku_sys::waku_relay_subscribe,
         handle_no_response,
-        ctx.get_ptr(),
-        pubsub_topic.as_ptr()
-    )
+        ctx.ptr_guard()?,
+        topic.as_ptr()
+    )?;
+    ctx.relay_subscribed(pubsub_topic);
+    Ok(())
 }
 
 pub async fn waku_relay_unsubscribe(
     ctx: &WakuNodeContext,
     pubsub_topic: &PubsubTopic,
 ) -> Result<()> {
-    let pubsub_topic = CString::new(String::from(pubsub_topic))
+    let topic = CString::new(String::from(pubsub_topic))
         .expect("CString should build properly from pubsub topic");
 
     handle_ffi_call!(
         waku_sys::waku_relay_unsubscribe,
         handle_no_response,
-        ctx.get_ptr(),
-        pubsub_topic.as_ptr()
+        ctx.ptr_guard()?,
+        topic.as_ptr()
+    )?;
+    ctx.relay_unsubscribed(pubsub_topic);
+    Ok(())
+}
+
+/// Minimum number of mesh peers for a relay publication to reach the network
+pub const RELAY_MIN_PEERS_TO_PUBLISH: usize = 1;
+
+/// Number of peers in the gossipsub mesh of `pubsub_topic`
+pub async fn waku_relay_get_num_peers_in_mesh(
+    ctx: &WakuNodeContext,
+    pubsub_topic: &PubsubTopic,
+) -> Result<usize> {
+    let topic = CString::new(String::from(pubsub_topic))
+        .expect("CString should build properly from pubsub topic");
+
+    handle_ffi_call!(
+        waku_sys::waku_relay_get_num_peers_in_mesh,
+        handle_response,
+        ctx.ptr_guard()?,
+        topic.as_ptr()
     )
 }
+
+/// Whether the mesh of `pubsub_topic` has at least [`RELAY_MIN_PEERS_TO_PUBLISH`] peers
+pub async fn waku_relay_enough_peers(
+    ctx: &WakuNodeContext,
+    pubsub_topic: &PubsubTopic,
+) -> Result<bool> {
+    Ok(waku_relay_get_num_peers_in_mesh(ctx, pubsub_topic).await? >= RELAY_MIN_PEERS_TO_PUBLISH)
+}
+
+/// Health of a subscribed relay topic, as last reported by nwaku.
+/// Topics nwaku didn't report on yet are [`TopicHealth::UnHealthy`]
+pub fn waku_relay_topic_health(
+    ctx: &WakuNodeContext,
+    pubsub_topic: &PubsubTopic,
+) -> Result<TopicHealth> {
+    if !ctx.relay_topics().contains(pubsub_topic) {
+        return Err(format!("not subscribed to relay topic {pubsub_topic:?}").into());
+    }
+    Ok(ctx
+        .topic_health(pubsub_topic)
+        .unwrap_or(TopicHealth::UnHealthy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{publish_error, wire_json};
+    use crate::general::contenttopic::{Encoding, WakuContentTopic};
+    use crate::general::{WakuError, WakuMessage};
+
+    #[test]
+    fn wire_json_roundtrips() {
+        let content_topic = WakuContentTopic::new("toychat", "2", "huilong", Encoding::Proto);
+        let message = WakuMessage::new("Hi!", content_topic, 0, b"meta", false);
+        let json = wire_json(&message);
+        assert_eq!(serde_json::from_str::<WakuMessage>(&json).unwrap(), message);
+
+        let mut untimed = message.clone();
+        untimed.timestamp = 0;
+        let relayed: WakuMessage = serde_json::from_str(&wire_json(&untimed)).unwrap();
+        assert_ne!(relayed.timestamp, 0);
+        assert_eq!(relayed.payload, message.payload);
+    }
+
+    #[test]
+    fn publish_failures_are_typed() {
+        let err = publish_error(WakuError::LibwakuFailure(
+            "Failed to publish: NoPeersToPublish".to_string(),
+        ));
+        assert!(matches!(err, WakuError::NoPeersToPublish(_)));
+        assert!(err.is_retryable());
+
+        let err = publish_error(WakuError::LibwakuFailure(
+            "Message size exceeded maximum of 153600 bytes".to_string(),
+        ));
+        assert!(matches!(err, WakuError::MessageTooLarge(_)));
+        assert!(!err.is_retryable());
+
+        let err = publish_error(WakuError::LibwakuFailure(
+            "invalid pubsub topic".to_string(),
+        ));
+        assert_eq!(
+            err,
+            WakuError::LibwakuFailure("invalid pubsub topic".to_string())
+        );
+        assert!(!err.is_retryable());
+        assert!(!publish_error(WakuError::NodeDestroyed).is_retryable());
+    }
+}
@@ -0,0 +1,171 @@
+//! Minimal HTTP bridge to poke a running node while debugging, enabled by the `rest` feature
+//!
+//! Endpoints:
+//! - `GET /health`: `{"status": "ok", "version": <nwaku version>}`
+//! - `GET /peers`: array with the ids of the connected peers
+//! - `POST /relay/publish`: publish `{"pubsubTopic": <topic>, "message": <WakuMessage>}` using relay,
+//!   where the message uses the same JSON as [`WakuMessage`]. Replies with `{"messageHash": <hash>}`
+//!
+//! The endpoints are not authenticated, so the server listens on localhost unless
+//! [`RestConfig::bind_addr`] says otherwise.
+
+// std
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+// crates
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::AbortHandle;
+// internal
+use crate::general::{messagehash::MessageHash, pubsubtopic::PubsubTopic, Result, WakuMessage};
+use crate::node::{Running, WakuNodeHandle};
+
+/// Address used by default by the REST bridge, same port as the nwaku REST API
+pub const DEFAULT_REST_ADDRESS: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8645);
+
+/// Node calls waiting to be executed, HTTP requests beyond it wait for a free slot
+const PENDING_REQUESTS: usize = 32;
+
+/// REST bridge configuration
+#[derive(Clone, Debug)]
+pub struct RestConfig {
+    /// Address the HTTP server binds to, [`DEFAULT_REST_ADDRESS`] by default
+    pub bind_addr: SocketAddr,
+}
+
+impl Default for RestConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: DEFAULT_REST_ADDRESS,
+        }
+    }
+}
+
+/// Node call requested by an HTTP handler, answered through `reply`
+enum RestRequest {
+    Health {
+        reply: oneshot::Sender<Result<String>>,
+    },
+    Peers {
+        reply: oneshot::Sender<Result<Vec<String>>>,
+    },
+    Publish {
+        message: Box<WakuMessage>,
+        pubsub_topic: PubsubTopic,
+        reply: oneshot::Sender<Result<MessageHash>>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PublishRequest {
+    pubsub_topic: PubsubTopic,
+    message: WakuMessage,
+}
+
+type RestResponse = std::result::Result<Json<Value>, (StatusCode, String)>;
+
+/// Stops the HTTP server when the future serving the node calls is dropped
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Serve the REST bridge for `node` until the HTTP server fails
+pub async fn serve(node: &WakuNodeHandle<Running>, config: RestConfig) -> Result<()> {
+    let listener = TcpListener::bind(config.bind_addr)
+        .await
+        .map_err(|e| format!("could not bind REST server to {}: {e}", config.bind_addr))?;
+
+    let (requests, mut pending) = mpsc::channel(PENDING_REQUESTS);
+    let router = Router::new()
+        .route("/health", get(health))
+        .route("/peers", get(peers))
+        .route("/relay/publish", post(relay_publish))
+        .with_state(requests);
+
+    let server = tokio::spawn(async move { axum::serve(listener, router).await });
+    let _stop_server = AbortOnDrop(server.abort_handle());
+
+    // The node handle can't be sent to the server tasks, so they forward every call here
+    while let Some(request) = pending.recv().await {
+        match request {
+            RestRequest::Health { reply } => {
+                let _ = reply.send(node.version().await);
+            }
+            RestRequest::Peers { reply } => {
+                let _ = reply.send(node.connected_peer_ids().await);
+            }
+            RestRequest::Publish {
+                message,
+                pubsub_topic,
+                reply,
+            } => {
+                let _ = reply.send(
+                    node.relay_publish_message(&message, &pubsub_topic, None)
+                        .await,
+                );
+            }
+        }
+    }
+
+    // Requests only stop coming once the server is gone
+    match server.await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(format!("REST server failed: {e}").into()),
+        Err(e) => Err(format!("REST server task failed: {e}").into()),
+    }
+}
+
+/// Forward a request to the node and wait for its reply
+async fn call<T>(
+    requests: &mpsc::Sender<RestRequest>,
+    request: impl FnOnce(oneshot::Sender<Result<T>>) -> RestRequest,
+) -> std::result::Result<T, (StatusCode, String)> {
+    let unavailable = || {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "node is not serving requests".to_string(),
+        )
+    };
+    let (reply, response) = oneshot::channel();
+    requests
+        .send(request(reply))
+        .await
+        .map_err(|_| unavailable())?;
+    response
+        .await
+        .map_err(|_| unavailable())?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+async fn health(State(requests): State<mpsc::Sender<RestRequest>>) -> RestResponse {
+    let version = call(&requests, |reply| RestRequest::Health { reply }).await?;
+    Ok(Json(json!({ "status": "ok", "version": version })))
+}
+
+async fn peers(State(requests): State<mpsc::Sender<RestRequest>>) -> RestResponse {
+    let peers = call(&requests, |reply| RestRequest::Peers { reply }).await?;
+    Ok(Json(json!(peers)))
+}
+
+async fn relay_publish(
+    State(requests): State<mpsc::Sender<RestRequest>>,
+    Json(body): Json<PublishRequest>,
+) -> RestResponse {
+    let message_hash = call(&requests, |reply| RestRequest::Publish {
+        message: Box::new(body.message),
+        pubsub_topic: body.pubsub_topic,
+        reply,
+    })
+    .await?;
+    Ok(Json(json!({ "messageHash": message_hash.to_string() })))
+}
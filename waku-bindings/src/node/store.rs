@@ -1,23 +1,32 @@
 //! Waku store protocol related methods
 
 // std
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::future::Future;
 use uuid::Uuid;
 // crates
 use tokio::time::Duration;
 // internal
+use crate::general::contenttopic::{WAKU_NETWORK_CLUSTER_ID, WAKU_NETWORK_SHARD_COUNT};
 use crate::general::libwaku_response::{handle_response, LibwakuResponse};
-use crate::general::time::get_now_in_nanosecs;
 use crate::general::waku_decode::WakuDecode;
 use crate::general::{
-    contenttopic::WakuContentTopic, messagehash::MessageHash, pubsubtopic::PubsubTopic, Result,
-    WakuStoreRespMessage,
+    contenttopic::WakuContentTopic, messagehash::MessageHash, pubsubtopic::PubsubTopic,
+    ErrorCategory, Result, WakuError, WakuStoreRespMessage,
 };
 use crate::handle_ffi_call;
 use crate::node::context::WakuNodeContext;
+use crate::node::peers::Protocol;
 use multiaddr::Multiaddr;
 use serde::{Deserialize, Serialize};
 
+/// Largest page a store node serves for a single request
+pub const STORE_MAX_PAGE_SIZE: usize = 100;
+
+/// Largest number of store queries kept in flight by `store_query_parallel`
+pub const STORE_MAX_PARALLEL_QUERIES: usize = 4;
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct PagingOptions {
@@ -50,14 +59,16 @@ pub struct StoreQueryRequest {
 }
 
 impl StoreQueryRequest {
+    /// Request for the whole history: both time bounds are optional and left unset,
+    /// see [`with_time_start`](Self::with_time_start) and [`with_time_end`](Self::with_time_end)
     pub fn new() -> Self {
         StoreQueryRequest {
             request_id: Uuid::new_v4().to_string(),
             include_data: true,
             pubsub_topic: None,
             content_topics: Vec::new(),
-            time_start: Some(get_now_in_nanosecs()),
-            time_end: Some(get_now_in_nanosecs()),
+            time_start: None,
+            time_end: None,
             message_hashes: None,
             pagination_cursor: None,
             pagination_forward: true,
@@ -80,11 +91,13 @@ impl StoreQueryRequest {
         self
     }
 
+    /// Oldest timestamp of the messages to retrieve, in unix nanoseconds, `None` for no bound
     pub fn with_time_start(mut self, time_start: Option<u64>) -> Self {
         self.time_start = time_start;
         self
     }
 
+    /// Newest timestamp of the messages to retrieve, in unix nanoseconds, `None` for no bound
     pub fn with_time_end(mut self, time_end: Option<u64>) -> Self {
         self.time_end = time_end;
         self
@@ -105,6 +118,26 @@ impl StoreQueryRequest {
         self.pagination_forward = pagination_forward;
         self
     }
+
+    pub fn with_pagination_limit(mut self, pagination_limit: Option<u64>) -> Self {
+        self.pagination_limit = pagination_limit;
+        self
+    }
+
+    /// Same query resuming from `cursor`, under a new request id
+    pub(crate) fn next_page(self, cursor: MessageHash) -> Self {
+        Self {
+            request_id: Uuid::new_v4().to_string(),
+            pagination_cursor: Some(cursor),
+            ..self
+        }
+    }
+}
+
+impl Default for StoreQueryRequest {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[derive(Clone, Deserialize, Debug)]
@@ -115,6 +148,86 @@ pub struct StoreWakuMessageResponse {
     pub pubsub_topic: String,
 }
 
+/// Destination of the messages retrieved by `store_query_into`.
+/// Messages are handed over one at a time, so a slow sink holds back the next page request.
+pub trait MessageSink {
+    /// Take a retrieved message. Returning an error aborts the query
+    fn accept(&mut self, msg: StoreWakuMessageResponse) -> impl Future<Output = Result<()>>;
+}
+
+impl<S: MessageSink> MessageSink for &mut S {
+    fn accept(&mut self, msg: StoreWakuMessageResponse) -> impl Future<Output = Result<()>> {
+        (**self).accept(msg)
+    }
+}
+
+/// Bucket `messages` by content topic. Every topic of `content_topics` gets an entry, even
+/// when no message matched it; messages of any other topic are left out.
+/// Messages keep their relative order within each bucket
+pub(crate) fn group_by_content_topic(
+    content_topics: &[WakuContentTopic],
+    messages: Vec<StoreWakuMessageResponse>,
+) -> HashMap<WakuContentTopic, Vec<StoreWakuMessageResponse>> {
+    let mut groups: HashMap<WakuContentTopic, Vec<StoreWakuMessageResponse>> = content_topics
+        .iter()
+        .map(|topic| (topic.clone(), Vec::new()))
+        .collect();
+    let by_name: HashMap<String, &WakuContentTopic> = content_topics
+        .iter()
+        .map(|topic| (topic.to_string(), topic))
+        .collect();
+
+    for msg in messages {
+        let topic = msg
+            .message
+            .as_ref()
+            .and_then(|message| by_name.get(&message.content_topic));
+        if let Some(&topic) = topic {
+            groups
+                .get_mut(topic)
+                .expect("every topic has a bucket")
+                .push(msg);
+        }
+    }
+
+    groups
+}
+
+/// Category of the `status_code` of a store response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreStatus {
+    Success,
+    PartialContent,
+    TooManyRequests,
+    BadRequest,
+    ServiceUnavailable,
+    Unknown(u32),
+}
+
+impl StoreStatus {
+    /// Whether the query was served. Unlisted 2xx codes count as served too
+    pub fn is_success(&self) -> bool {
+        match self {
+            Self::Success | Self::PartialContent => true,
+            Self::Unknown(code) => (200..300).contains(code),
+            _ => false,
+        }
+    }
+}
+
+impl From<u32> for StoreStatus {
+    fn from(code: u32) -> Self {
+        match code {
+            200 => Self::Success,
+            206 => Self::PartialContent,
+            400 => Self::BadRequest,
+            429 => Self::TooManyRequests,
+            503 => Self::ServiceUnavailable,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
 #[derive(Clone, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct StoreResponse {
@@ -136,10 +249,18 @@ pub struct StoreResponse {
     pub pagination_cursor: Option<MessageHash>,
 }
 
+impl StoreResponse {
+    /// Category of [`StoreResponse::status_code`]
+    pub fn status(&self) -> StoreStatus {
+        self.status_code.into()
+    }
+}
+
 // Implement WakuDecode for Vec<Multiaddr>
 impl WakuDecode for StoreResponse {
     fn decode(input: &str) -> Result<Self> {
-        Ok(serde_json::from_str(input).expect("could not parse store resp"))
+        serde_json::from_str(input)
+            .map_err(|e| WakuError::Decode(format!("could not parse store response: {e}")))
     }
 }
 
@@ -149,6 +270,8 @@ pub async fn waku_store_query(
     peer_addr: &str,
     timeout_millis: Option<Duration>,
 ) -> Result<StoreResponse> {
+    check_autosharding(query.pubsub_topic.as_ref(), &query.content_topics)?;
+
     let json_query = CString::new(
         serde_json::to_string(&query).expect("StoreQuery should always be able to be serialized"),
     )
@@ -161,12 +284,275 @@ pub async fn waku_store_query(
 
     let timeout_millis = timeout_millis.unwrap_or(Duration::from_secs(10));
 
-    handle_ffi_call!(
+    let response = handle_ffi_call!(
         waku_sys::waku_store_query,
         handle_response,
-        ctx.get_ptr(),
+        ctx.ptr_guard()?,
         json_query.as_ptr(),
         peer_addr.as_ptr(),
         timeout_millis.as_millis() as i32
-    )
+    );
+    store_outcome(response)
+}
+
+/// Timestamps of the oldest and newest messages held by the store node at `peer_addr`,
+/// in unix nanoseconds, `None` if it holds none.
+/// Found with two single message queries, one from each end of the store
+pub async fn waku_store_time_range(
+    ctx: &WakuNodeContext,
+    peer_addr: &str,
+) -> Result<Option<(u64, u64)>> {
+    let mut edges = Vec::with_capacity(2);
+    for forward in [true, false] {
+        let query = StoreQueryRequest::new()
+            .with_pagination_forward(forward)
+            .with_pagination_limit(Some(1));
+        let response = waku_store_query(ctx, query, peer_addr, None).await?;
+        let timestamp = response
+            .messages
+            .into_iter()
+            .next()
+            .and_then(|msg| msg.message)
+            .map(|msg| msg.timestamp as u64);
+        match timestamp {
+            Some(timestamp) => edges.push(timestamp),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some((edges[0], edges[1])))
+}
+
+/// Under the autosharding of The Waku Network a content topic only travels on the shard it
+/// maps to, so a query pairing a pubsub topic of that cluster with content topics of other
+/// shards could only come back empty. Such queries are refused instead
+fn check_autosharding(
+    pubsub_topic: Option<&PubsubTopic>,
+    content_topics: &[WakuContentTopic],
+) -> Result<()> {
+    let Some(pubsub_topic) = pubsub_topic else {
+        return Ok(());
+    };
+    if !matches!(pubsub_topic.shard(), Some((cluster_id, _)) if cluster_id == WAKU_NETWORK_CLUSTER_ID)
+    {
+        return Ok(());
+    }
+    let mismatches: Vec<String> = content_topics
+        .iter()
+        .map(|topic| {
+            (
+                topic,
+                topic.pubsub_topic(WAKU_NETWORK_CLUSTER_ID, WAKU_NETWORK_SHARD_COUNT),
+            )
+        })
+        .filter(|(_, shard)| shard != pubsub_topic)
+        .map(|(topic, shard)| format!("{topic} is on {}", String::from(&shard)))
+        .collect();
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(WakuError::Other(format!(
+            "content topics not on pubsub topic {}: {}",
+            String::from(pubsub_topic),
+            mismatches.join(", ")
+        )))
+    }
+}
+
+/// Tell an unreachable store node and a rejected query apart from an empty result,
+/// which is a successful response without messages
+fn store_outcome(response: Result<StoreResponse>) -> Result<StoreResponse> {
+    match response {
+        Ok(response) if !response.status().is_success() => Err(WakuError::StorePeerRejected {
+            status_code: response.status_code,
+            status_desc: response.status_desc,
+        }),
+        Err(WakuError::LibwakuFailure(msg))
+            if matches!(
+                ErrorCategory::of_message(&msg),
+                ErrorCategory::DialBackoff | ErrorCategory::PeerUnreachable
+            ) =>
+        {
+            Err(WakuError::StorePeerUnreachable(msg))
+        }
+        response => response,
+    }
+}
+
+/// Delete the messages of the node's own store older than `older_than`.
+/// libwaku exposes no store maintenance call, so this fails with
+/// [`WakuError::InvalidConfig`]: retention is enforced by the node itself, periodically,
+/// according to [`store_message_retention_policy`](crate::WakuNodeConfig::store_message_retention_policy)
+pub async fn waku_store_prune(ctx: &WakuNodeContext, older_than: Duration) -> Result<u64> {
+    if !ctx.capabilities().contains(&Protocol::Store) {
+        return Err(WakuError::InvalidConfig(
+            "the store protocol is not mounted on this node".to_string(),
+        ));
+    }
+    Err(WakuError::InvalidConfig(format!(
+        "libwaku can't prune the store on demand, set a store message retention policy such as `time:{}` instead",
+        older_than.as_secs()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_autosharding, group_by_content_topic, store_outcome, StoreQueryRequest,
+        StoreResponse, StoreStatus, StoreWakuMessageResponse,
+    };
+    use crate::general::contenttopic::{Encoding, WakuContentTopic};
+    use crate::general::messagehash::MessageHash;
+    use crate::general::pubsubtopic::PubsubTopic;
+    use crate::general::waku_decode::WakuDecode;
+    use crate::general::{WakuError, WakuStoreRespMessage};
+
+    fn response(json: &str) -> StoreResponse {
+        StoreResponse::decode(json).unwrap()
+    }
+
+    #[test]
+    fn autosharded_topics_checked_against_pubsub_topic() {
+        // On shard 3 and 0 of The Waku Network respectively
+        let chat: WakuContentTopic = "/toychat/2/huilong/proto".parse().unwrap();
+        let app: WakuContentTopic = "/myapp/1/latest/proto".parse().unwrap();
+
+        let shard_3 = PubsubTopic::new("/waku/2/rs/1/3");
+        assert!(check_autosharding(Some(&shard_3), std::slice::from_ref(&chat)).is_ok());
+        assert!(check_autosharding(Some(&shard_3), &[]).is_ok());
+        let err = check_autosharding(Some(&shard_3), &[chat.clone(), app.clone()]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("/myapp/1/latest/proto is on /waku/2/rs/1/0"));
+
+        // Static sharding elsewhere, or no pubsub topic, is left alone
+        let other_cluster = PubsubTopic::new("/waku/2/rs/16/32");
+        assert!(check_autosharding(Some(&other_cluster), &[chat.clone(), app.clone()]).is_ok());
+        assert!(check_autosharding(
+            Some(&PubsubTopic::new("/waku/2/default-waku/proto")),
+            std::slice::from_ref(&app)
+        )
+        .is_ok());
+        assert!(check_autosharding(None, &[chat, app]).is_ok());
+    }
+
+    #[test]
+    fn new_request_has_no_time_bounds() {
+        let json = serde_json::to_value(StoreQueryRequest::new()).unwrap();
+        assert!(json.get("time_start").is_none());
+        assert!(json.get("time_end").is_none());
+
+        let json = serde_json::to_value(
+            StoreQueryRequest::new()
+                .with_time_start(Some(1))
+                .with_time_end(Some(2)),
+        )
+        .unwrap();
+        assert_eq!(json["time_start"], 1);
+        assert_eq!(json["time_end"], 2);
+    }
+
+    #[test]
+    fn empty_result_is_ok() {
+        let outcome = store_outcome(Ok(response(
+            "{\"requestId\":\"3d4e4b4a\",\"statusCode\":200,\"statusDesc\":\"OK\",\"messages\":[]}",
+        )))
+        .unwrap();
+        assert!(outcome.messages.is_empty());
+        assert!(outcome.pagination_cursor.is_none());
+    }
+
+    #[test]
+    fn rejected_query() {
+        let outcome = store_outcome(Ok(response(
+            "{\"requestId\":\"3d4e4b4a\",\"statusCode\":429,\"statusDesc\":\"TOO_MANY_REQUESTS\",\"messages\":[]}",
+        )));
+        let err = outcome.unwrap_err();
+        assert_eq!(err.store_status(), Some(StoreStatus::TooManyRequests));
+        assert_eq!(
+            err,
+            WakuError::StorePeerRejected {
+                status_code: 429,
+                status_desc: "TOO_MANY_REQUESTS".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn status_categories() {
+        assert_eq!(StoreStatus::from(200), StoreStatus::Success);
+        assert_eq!(StoreStatus::from(206), StoreStatus::PartialContent);
+        assert_eq!(StoreStatus::from(400), StoreStatus::BadRequest);
+        assert_eq!(StoreStatus::from(429), StoreStatus::TooManyRequests);
+        assert_eq!(StoreStatus::from(503), StoreStatus::ServiceUnavailable);
+        assert_eq!(StoreStatus::from(300), StoreStatus::Unknown(300));
+
+        assert!(StoreStatus::Success.is_success());
+        assert!(StoreStatus::PartialContent.is_success());
+        assert!(StoreStatus::Unknown(204).is_success());
+        assert!(!StoreStatus::TooManyRequests.is_success());
+        assert!(!StoreStatus::Unknown(300).is_success());
+
+        let served = response(
+            "{\"requestId\":\"3d4e4b4a\",\"statusCode\":503,\"statusDesc\":\"SERVICE_UNAVAILABLE\",\"messages\":[]}",
+        );
+        assert_eq!(served.status(), StoreStatus::ServiceUnavailable);
+    }
+
+    #[test]
+    fn unreachable_peer() {
+        let outcome = store_outcome(Err(WakuError::LibwakuFailure(
+            "PEER_DIAL_FAILURE: /ip4/127.0.0.1/tcp/60010/p2p/16Uiu2HAmVFXtAfSj4EiR7mL2KvL4EE2wztuQgUSBoj2Jx2KeXFLN".to_string(),
+        )));
+        assert!(matches!(outcome, Err(WakuError::StorePeerUnreachable(_))));
+
+        let outcome = store_outcome(Err(WakuError::LibwakuFailure(
+            "BAD_RESPONSE: could not decode".to_string(),
+        )));
+        assert!(matches!(outcome, Err(WakuError::LibwakuFailure(_))));
+    }
+
+    fn hash(byte: u8) -> MessageHash {
+        format!("{byte:02x}").repeat(32).parse().unwrap()
+    }
+
+    fn stored(hash_byte: u8, content_topic: &WakuContentTopic) -> StoreWakuMessageResponse {
+        StoreWakuMessageResponse {
+            message_hash: hash(hash_byte),
+            message: Some(WakuStoreRespMessage {
+                content_topic: content_topic.to_string(),
+                ..Default::default()
+            }),
+            pubsub_topic: "/waku/2/rs/0/1".to_string(),
+        }
+    }
+
+    #[test]
+    fn grouped_by_content_topic() {
+        let chat = WakuContentTopic::new("toychat", "2", "chat", Encoding::Proto);
+        let presence = WakuContentTopic::new("toychat", "2", "presence", Encoding::Proto);
+        let typing = WakuContentTopic::new("toychat", "2", "typing", Encoding::Proto);
+        let other = WakuContentTopic::new("toychat", "2", "other", Encoding::Proto);
+
+        let messages = vec![
+            stored(1, &chat),
+            stored(2, &presence),
+            stored(3, &chat),
+            stored(4, &other),
+            stored(5, &chat),
+        ];
+        let groups =
+            group_by_content_topic(&[chat.clone(), presence.clone(), typing.clone()], messages);
+
+        assert_eq!(groups.len(), 3);
+        let hashes = |topic| -> Vec<MessageHash> {
+            groups[topic]
+                .iter()
+                .map(|msg: &StoreWakuMessageResponse| msg.message_hash.clone())
+                .collect()
+        };
+        assert_eq!(hashes(&chat), vec![hash(1), hash(3), hash(5)]);
+        assert_eq!(hashes(&presence), vec![hash(2)]);
+        assert!(hashes(&typing).is_empty());
+    }
 }
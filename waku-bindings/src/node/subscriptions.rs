@@ -0,0 +1,187 @@
+//! Snapshot of the subscriptions of a node, to carry them over a restart
+
+// crates
+use serde::{Deserialize, Serialize};
+// internal
+use crate::general::contenttopic::WakuContentTopic;
+use crate::general::pubsubtopic::PubsubTopic;
+use crate::general::Result;
+use crate::node::context::WakuNodeContext;
+use crate::node::events::WakuMessageEvent;
+use crate::node::peers::Protocol;
+use crate::node::{filter, relay};
+
+/// Content topics subscribed to through filter on a pubsub topic
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterSubscription {
+    pub pubsub_topic: PubsubTopic,
+    pub content_topics: Vec<WakuContentTopic>,
+}
+
+/// Relay topics and filter subscriptions of a node, as tracked by the bindings.
+/// Serializable, so it can be kept while the node is recreated
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionState {
+    pub relay_topics: Vec<PubsubTopic>,
+    pub filter_subscriptions: Vec<FilterSubscription>,
+}
+
+impl SubscriptionState {
+    /// Whether there is nothing to subscribe to
+    pub fn is_empty(&self) -> bool {
+        self.relay_topics.is_empty() && self.filter_subscriptions.is_empty()
+    }
+}
+
+/// Subscription made by [`subscribe_auto`](crate::WakuNodeHandle::subscribe_auto),
+/// through relay or filter depending on the node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subscription {
+    pub pubsub_topic: PubsubTopic,
+    pub content_topics: Vec<WakuContentTopic>,
+    /// Protocol used for the subscription, [`Protocol::Relay`] or [`Protocol::Filter`]
+    pub protocol: Protocol,
+}
+
+impl Subscription {
+    /// Whether `event` belongs to this subscription. A relay subscription receives every
+    /// message of its pubsub topic, the ones of other content topics are told apart here
+    pub fn matches(&self, event: &WakuMessageEvent) -> bool {
+        event.pubsub_topic == String::from(&self.pubsub_topic)
+            && self
+                .content_topics
+                .contains(&event.waku_message.content_topic)
+    }
+}
+
+/// Subscribe to `content_topics` on `pubsub_topic` with relay if the node mounts it,
+/// with filter otherwise
+pub async fn waku_subscribe_auto(
+    ctx: &WakuNodeContext,
+    pubsub_topic: &PubsubTopic,
+    content_topics: Vec<WakuContentTopic>,
+) -> Result<Subscription> {
+    let protocol = if ctx.capabilities().contains(&Protocol::Relay) {
+        relay::waku_relay_subscribe(ctx, pubsub_topic).await?;
+        Protocol::Relay
+    } else {
+        filter::waku_filter_subscribe(ctx, pubsub_topic, content_topics.clone(), None).await?;
+        Protocol::Filter
+    };
+    Ok(Subscription {
+        pubsub_topic: pubsub_topic.clone(),
+        content_topics,
+        protocol,
+    })
+}
+
+/// Undo `subscription`. A relay subscription is dropped for its whole pubsub topic
+pub async fn waku_unsubscribe(ctx: &WakuNodeContext, subscription: Subscription) -> Result<()> {
+    match subscription.protocol {
+        Protocol::Relay => relay::waku_relay_unsubscribe(ctx, &subscription.pubsub_topic).await,
+        _ => {
+            filter::waku_filter_unsubscribe(
+                ctx,
+                &subscription.pubsub_topic,
+                subscription.content_topics,
+            )
+            .await
+        }
+    }
+}
+
+/// Current subscriptions of the node, topics sorted so equal states compare equal
+pub fn waku_subscription_state(ctx: &WakuNodeContext) -> SubscriptionState {
+    let mut relay_topics = ctx.relay_topics();
+    relay_topics.sort_by_key(|topic| String::from(topic));
+
+    let mut filter_subscriptions: Vec<FilterSubscription> = ctx
+        .filter_subscriptions()
+        .into_iter()
+        .map(|(pubsub_topic, mut content_topics)| {
+            content_topics.sort_by_key(|topic| topic.to_string());
+            FilterSubscription {
+                pubsub_topic,
+                content_topics,
+            }
+        })
+        .collect();
+    filter_subscriptions.sort_by_key(|subscription| String::from(&subscription.pubsub_topic));
+
+    SubscriptionState {
+        relay_topics,
+        filter_subscriptions,
+    }
+}
+
+/// Subscribe to everything in `state` the node isn't subscribed to yet.
+/// Stops at the first failing subscription, keeping the ones already made
+pub async fn waku_apply_subscription_state(
+    ctx: &WakuNodeContext,
+    state: &SubscriptionState,
+) -> Result<()> {
+    let current = waku_subscription_state(ctx);
+
+    for pubsub_topic in &state.relay_topics {
+        if !current.relay_topics.contains(pubsub_topic) {
+            relay::waku_relay_subscribe(ctx, pubsub_topic).await?;
+        }
+    }
+
+    for subscription in &state.filter_subscriptions {
+        let subscribed = current
+            .filter_subscriptions
+            .iter()
+            .find(|current| current.pubsub_topic == subscription.pubsub_topic)
+            .map(|current| current.content_topics.as_slice())
+            .unwrap_or_default();
+        let missing: Vec<WakuContentTopic> = subscription
+            .content_topics
+            .iter()
+            .filter(|topic| !subscribed.contains(topic))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            filter::waku_filter_subscribe(ctx, &subscription.pubsub_topic, missing, None).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilterSubscription, SubscriptionState};
+    use crate::general::contenttopic::{Encoding, WakuContentTopic};
+    use crate::general::pubsubtopic::PubsubTopic;
+
+    #[test]
+    fn state_roundtrips_through_json() {
+        let state = SubscriptionState {
+            relay_topics: vec![PubsubTopic::new("/waku/2/rs/16/32")],
+            filter_subscriptions: vec![FilterSubscription {
+                pubsub_topic: PubsubTopic::new("/waku/2/rs/16/64"),
+                content_topics: vec![WakuContentTopic::new(
+                    "toychat",
+                    "2",
+                    "huilong",
+                    Encoding::Proto,
+                )],
+            }],
+        };
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(
+            json,
+            "{\"relayTopics\":[\"/waku/2/rs/16/32\"],\"filterSubscriptions\":[{\"pubsubTopic\":\"/waku/2/rs/16/64\",\"contentTopics\":[\"/toychat/2/huilong/proto\"]}]}"
+        );
+        assert_eq!(
+            serde_json::from_str::<SubscriptionState>(&json).unwrap(),
+            state
+        );
+        assert!(!state.is_empty());
+        assert!(SubscriptionState::default().is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! Parsing of the nwaku version strings
+
+// std
+use std::fmt;
+// internal
+use crate::general::waku_decode::WakuDecode;
+use crate::general::{Result, WakuError};
+
+/// nwaku version, as reported by [`version`](crate::WakuNodeHandle::version).
+/// Release versions (`v0.33.0`), release candidates (`v0.33.0-rc.1`) and `git describe`
+/// outputs of builds between tags (`v0.33.0-rc.1-12-gdeadbeef`, `-dirty` included) are understood
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NwakuVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// Pre-release tag without its leading `-`, i.e. `rc.1`
+    pub pre_release: Option<String>,
+    /// Commits made since the tag, for builds between tags
+    pub commits_since_tag: Option<u32>,
+    /// Abbreviated hash of the commit built, for builds between tags
+    pub commit: Option<String>,
+    /// Version string as reported by nwaku
+    pub raw: String,
+}
+
+impl NwakuVersion {
+    /// `(major, minor, patch)`, leaving out pre-release tags and build details
+    pub fn release(&self) -> (u32, u32, u32) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
+/// Split a `git describe` suffix, `-{commits}-g{hash}`, off `version`
+fn split_git_describe(version: &str) -> (&str, Option<(u32, String)>) {
+    let Some((rest, hash)) = version.rsplit_once("-g") else {
+        return (version, None);
+    };
+    let Some((tag, commits)) = rest.rsplit_once('-') else {
+        return (version, None);
+    };
+    match commits.parse() {
+        Ok(commits) if !hash.is_empty() && hash.chars().all(|c| c.is_ascii_hexdigit()) => {
+            (tag, Some((commits, hash.to_string())))
+        }
+        _ => (version, None),
+    }
+}
+
+impl WakuDecode for NwakuVersion {
+    fn decode(input: &str) -> Result<Self> {
+        let error = || WakuError::Decode(format!("could not parse nwaku version {input}"));
+
+        // Anything after the version itself, like a build note, is left out
+        let version = input.split_whitespace().next().ok_or_else(error)?;
+        let version = version.strip_prefix('v').unwrap_or(version);
+        let version = version.strip_suffix("-dirty").unwrap_or(version);
+        let (version, build) = split_git_describe(version);
+        let (release, pre_release) = match version.split_once('-') {
+            Some((release, pre_release)) => (release, Some(pre_release.to_string())),
+            None => (version, None),
+        };
+
+        let mut numbers = release.split('.').map(str::parse::<u32>);
+        let (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) = (
+            numbers.next(),
+            numbers.next(),
+            numbers.next(),
+            numbers.next(),
+        ) else {
+            return Err(error());
+        };
+
+        let (commits_since_tag, commit) = build.unzip();
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            pre_release,
+            commits_since_tag,
+            commit,
+            raw: input.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for NwakuVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NwakuVersion;
+    use crate::general::waku_decode::WakuDecode;
+
+    fn parse(version: &str) -> NwakuVersion {
+        NwakuVersion::decode(version).unwrap()
+    }
+
+    #[test]
+    fn release_versions() {
+        let version = parse("v0.33.1");
+        assert_eq!(version.release(), (0, 33, 1));
+        assert_eq!(version.pre_release, None);
+        assert_eq!(version.commit, None);
+        assert_eq!(version.raw, "v0.33.1");
+
+        assert_eq!(parse("0.34.0").release(), (0, 34, 0));
+    }
+
+    #[test]
+    fn release_candidates() {
+        let version = parse("v0.33.0-rc.1");
+        assert_eq!(version.release(), (0, 33, 0));
+        assert_eq!(version.pre_release.as_deref(), Some("rc.1"));
+        assert_eq!(version.commits_since_tag, None);
+    }
+
+    #[test]
+    fn git_describes() {
+        let version = parse("v0.33.0-rc.1-12-gdeadbeef");
+        assert_eq!(version.release(), (0, 33, 0));
+        assert_eq!(version.pre_release.as_deref(), Some("rc.1"));
+        assert_eq!(version.commits_since_tag, Some(12));
+        assert_eq!(version.commit.as_deref(), Some("deadbeef"));
+        assert_eq!(version.raw, "v0.33.0-rc.1-12-gdeadbeef");
+
+        let version = parse("v0.34.0-3-g4b1e9a5c-dirty");
+        assert_eq!(version.release(), (0, 34, 0));
+        assert_eq!(version.pre_release, None);
+        assert_eq!(version.commits_since_tag, Some(3));
+        assert_eq!(version.commit.as_deref(), Some("4b1e9a5c"));
+    }
+
+    #[test]
+    fn invalid_versions() {
+        assert!(NwakuVersion::decode("").is_err());
+        assert!(NwakuVersion::decode("v0.33").is_err());
+        assert!(NwakuVersion::decode("master").is_err());
+        assert!(NwakuVersion::decode("v0.33.0.1").is_err());
+    }
+}
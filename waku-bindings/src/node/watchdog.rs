@@ -0,0 +1,158 @@
+//! Detection of a libwaku that stopped calling back
+
+// std
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+// crates
+use tokio::time::sleep_until;
+
+/// Tracks the callbacks of a node to tell when it stalls during an operation
+pub(crate) struct Watchdog {
+    /// Longest silence allowed during an operation, `None` when disabled
+    window: Mutex<Option<Duration>>,
+    last_callback: Mutex<Instant>,
+    /// Set once a stall is reported, cleared by the next callback
+    stalled: AtomicBool,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self {
+            window: Mutex::new(None),
+            last_callback: Mutex::new(Instant::now()),
+            stalled: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Watchdog {
+    pub(crate) fn set_window(&self, window: Option<Duration>) {
+        *self.window.lock().expect("watchdog window lock") = window;
+    }
+
+    fn window(&self) -> Option<Duration> {
+        *self.window.lock().expect("watchdog window lock")
+    }
+
+    /// Record that libwaku called back, so it is alive
+    pub(crate) fn callback_fired(&self) {
+        *self.last_callback.lock().expect("watchdog lock") = Instant::now();
+        self.stalled.store(false, Ordering::Release);
+    }
+
+    /// Silence since the last callback, or since `started` if later
+    fn silence_since(&self, started: Instant) -> Duration {
+        started
+            .max(*self.last_callback.lock().expect("watchdog lock"))
+            .elapsed()
+    }
+
+    /// Await `response`, calling `on_stall` with the silence duration once libwaku made no
+    /// callback for longer than the window. A stall is reported once until the next callback.
+    /// The operation keeps waiting afterwards: its callback may still fire, so it can't be abandoned
+    pub(crate) async fn watch<F: Future>(
+        &self,
+        response: F,
+        on_stall: impl Fn(Duration),
+    ) -> F::Output {
+        let started = Instant::now();
+        tokio::pin!(response);
+        loop {
+            let Some(window) = self.window() else {
+                return response.await;
+            };
+            let deadline = if self.stalled.load(Ordering::Acquire) {
+                Instant::now() + window
+            } else {
+                Instant::now() + window.saturating_sub(self.silence_since(started))
+            };
+
+            tokio::select! {
+                output = &mut response => {
+                    self.callback_fired();
+                    return output;
+                }
+                _ = sleep_until(deadline.into()) => {
+                    let silence = self.silence_since(started);
+                    if silence >= window && !self.stalled.swap(true, Ordering::AcqRel) {
+                        on_stall(silence);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Watchdog;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tokio::time::sleep;
+
+    /// Stand-in for an FFI call whose callback fires after `delay`
+    async fn slow_ffi_call(delay: Duration) -> &'static str {
+        sleep(delay).await;
+        "done"
+    }
+
+    #[tokio::test]
+    async fn stall_detected_within_window() {
+        let watchdog = Watchdog::default();
+        watchdog.set_window(Some(Duration::from_millis(50)));
+        let stalls = Mutex::new(Vec::new());
+        let started = Instant::now();
+
+        let output = watchdog
+            .watch(slow_ffi_call(Duration::from_millis(300)), |silence| {
+                stalls.lock().unwrap().push((silence, started.elapsed()))
+            })
+            .await;
+
+        assert_eq!(output, "done");
+        let stalls = stalls.into_inner().unwrap();
+        assert_eq!(stalls.len(), 1);
+        let (silence, detected_after) = stalls[0];
+        assert!(silence >= Duration::from_millis(50));
+        assert!(detected_after < Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn callbacks_keep_node_alive() {
+        let watchdog = Arc::new(Watchdog::default());
+        watchdog.set_window(Some(Duration::from_millis(50)));
+        let heartbeat = {
+            let watchdog = watchdog.clone();
+            tokio::spawn(async move {
+                loop {
+                    sleep(Duration::from_millis(10)).await;
+                    watchdog.callback_fired();
+                }
+            })
+        };
+
+        let stalls = Mutex::new(0);
+        watchdog
+            .watch(slow_ffi_call(Duration::from_millis(200)), |_| {
+                *stalls.lock().unwrap() += 1
+            })
+            .await;
+        heartbeat.abort();
+
+        assert_eq!(stalls.into_inner().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn disabled_by_default() {
+        let watchdog = Watchdog::default();
+        let stalls = Mutex::new(0);
+        watchdog
+            .watch(slow_ffi_call(Duration::from_millis(100)), |_| {
+                *stalls.lock().unwrap() += 1
+            })
+            .await;
+        assert_eq!(stalls.into_inner().unwrap(), 0);
+    }
+}
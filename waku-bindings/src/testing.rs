@@ -0,0 +1,86 @@
+//! Helpers to write integration tests against real nodes, enabled by the `testing` feature
+
+// std
+use std::time::Duration;
+// crates
+use regex::Regex;
+use tokio::time::sleep;
+// internal
+use crate::general::Result;
+use crate::node::{
+    waku_new, Initialized, Multiaddr, PubsubTopic, Running, WakuNodeConfig, WakuNodeHandle,
+};
+
+/// Relay topic the nodes made by this module are subscribed to
+pub const TESTING_PUBSUB_TOPIC: &str = "/waku/2/rs/0/1";
+
+/// Time given to the relay mesh to form once the nodes are connected
+pub const MESH_FORMATION_DELAY: Duration = Duration::from_secs(3);
+
+/// Create a node from `config`, listening on a random port unless `config` sets one, with a
+/// no-op event callback, and subscribed to [`TESTING_PUBSUB_TOPIC`] once started
+pub async fn new_node(config: WakuNodeConfig) -> Result<WakuNodeHandle<Initialized>> {
+    let node = waku_new(Some(WakuNodeConfig {
+        tcp_port: config.tcp_port.or(Some(0)),
+        ..config
+    }))
+    .await?;
+    node.set_event_callback(|_| {})?;
+    node.queue_relay_subscribe(&PubsubTopic::new(TESTING_PUBSUB_TOPIC));
+    Ok(node)
+}
+
+/// Create and start a node as per [`new_node`]
+pub async fn start_node(config: WakuNodeConfig) -> Result<WakuNodeHandle<Running>> {
+    new_node(config).await?.start().await
+}
+
+/// Dial `node` from `dialer` through localhost, to avoid issues with NAT or firewall.
+/// Returns the dialed address, which includes the peer id of `node`
+pub async fn connect_to(
+    dialer: &WakuNodeHandle<Running>,
+    node: &WakuNodeHandle<Running>,
+) -> Result<Multiaddr> {
+    let addresses = node.listen_addresses().await?;
+    let address = addresses
+        .first()
+        .ok_or("node listens on no address")?
+        .to_string();
+    let re = Regex::new(r"\b(?:\d{1,3}\.){3}\d{1,3}\b").expect("valid ip regex");
+    let address: Multiaddr = re
+        .replace_all(&address, "127.0.0.1")
+        .parse()
+        .map_err(|e| format!("invalid listen address {address}: {e}"))?;
+    dialer.connect(&address, None).await?;
+    Ok(address)
+}
+
+/// [`connect_to`], then give the relay mesh of the two nodes [`MESH_FORMATION_DELAY`] to form
+pub async fn form_mesh(
+    dialer: &WakuNodeHandle<Running>,
+    node: &WakuNodeHandle<Running>,
+) -> Result<Multiaddr> {
+    let address = connect_to(dialer, node).await?;
+    sleep(MESH_FORMATION_DELAY).await;
+    Ok(address)
+}
+
+/// Two running nodes on random localhost ports, connected to each other and with their
+/// relay mesh formed on [`TESTING_PUBSUB_TOPIC`].
+/// Their event callback ignores every event, use
+/// [`await_message`](WakuNodeHandle::await_message) to check what they receive
+pub async fn two_connected_nodes() -> Result<(WakuNodeHandle<Running>, WakuNodeHandle<Running>)> {
+    two_connected_nodes_with(WakuNodeConfig::default(), WakuNodeConfig::default()).await
+}
+
+/// Like [`two_connected_nodes`], with the nodes created from `config1` and `config2`,
+/// the second one dialing the first one
+pub async fn two_connected_nodes_with(
+    config1: WakuNodeConfig,
+    config2: WakuNodeConfig,
+) -> Result<(WakuNodeHandle<Running>, WakuNodeHandle<Running>)> {
+    let node1 = start_node(config1).await?;
+    let node2 = start_node(config2).await?;
+    form_mesh(&node2, &node1).await?;
+    Ok((node1, node2))
+}
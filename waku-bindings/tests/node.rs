@@ -8,10 +8,18 @@ use std::time::Duration;
 use std::{collections::HashSet, str::from_utf8};
 use tokio::time;
 use tokio::time::sleep;
-use waku_bindings::node::PubsubTopic;
+use waku_bindings::general::signing::recover_signer;
+use waku_bindings::node::{
+    MessageSink, PeerConnectionEvent, Protocol, PubsubTopic, StoreQueryRequest,
+    StoreWakuMessageResponse, SubscriptionState,
+};
+use waku_bindings::testing::{
+    connect_to, form_mesh, new_node, start_node, two_connected_nodes, two_connected_nodes_with,
+    MESH_FORMATION_DELAY, TESTING_PUBSUB_TOPIC,
+};
 use waku_bindings::{
-    waku_new, Encoding, Initialized, MessageHash, WakuContentTopic, WakuEvent, WakuMessage,
-    WakuNodeConfig, WakuNodeHandle,
+    waku_new, Encoding, Initialized, MessageHash, NodeHealth, WakuContentTopic, WakuEvent,
+    WakuMessage, WakuNodeConfig, WakuNodeHandle,
 };
 use waku_bindings::{LibwakuResponse, Running};
 const ECHO_TIMEOUT: u64 = 1000;
@@ -186,3 +194,1951 @@ async fn node_restart() {
         node.waku_destroy().await.expect("free resources");
     }
 }
+
+#[tokio::test]
+#[serial]
+async fn store_latest_returns_newest_messages() {
+    let store_node = start_node(WakuNodeConfig {
+        store: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let client = start_node(Default::default()).await.unwrap();
+    let store_addr = form_mesh(&client, &store_node).await.unwrap();
+
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "latest", Encoding::Proto);
+    for i in 0..5 {
+        let message = WakuMessage::new(
+            format!("message {i}"),
+            content_topic.clone(),
+            0,
+            Vec::new(),
+            false,
+        );
+        client
+            .relay_publish_message(&message, &pubsub_topic, None)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(100)).await;
+    }
+    sleep(Duration::from_secs(1)).await;
+
+    let messages = client
+        .store_latest(
+            vec![content_topic],
+            Some(pubsub_topic),
+            3,
+            &store_addr.to_string(),
+        )
+        .await
+        .unwrap();
+
+    let payloads: Vec<String> = messages
+        .iter()
+        .map(|msg| {
+            from_utf8(msg.message.as_ref().unwrap().payload())
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(payloads, vec!["message 4", "message 3", "message 2"]);
+
+    let store_node = store_node.stop().await.unwrap();
+    let client = client.stop().await.unwrap();
+    store_node.waku_destroy().await.unwrap();
+    client.waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn denied_peer_is_disconnected() {
+    let peer = start_node(Default::default()).await.unwrap();
+
+    let peer_address = peer.listen_addresses().await.unwrap()[0].to_string();
+    let peer_id = peer_address.rsplit("/p2p/").next().unwrap().to_string();
+
+    let node = new_node(WakuNodeConfig {
+        denied_peers: Some(vec![peer_id.clone()]),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let peer_events = Arc::new(Mutex::new(Vec::new()));
+    let peer_events_cloned = peer_events.clone();
+    node.set_event_callback(move |response| {
+        if let LibwakuResponse::Success(Some(v)) = response {
+            if let Ok(WakuEvent::ConnectionChange(evt)) = serde_json::from_str(&v) {
+                peer_events_cloned
+                    .lock()
+                    .unwrap()
+                    .push((evt.peer_id, evt.peer_event));
+            }
+        }
+    })
+    .unwrap();
+    let node = node.start().await.unwrap();
+    form_mesh(&node, &peer).await.unwrap();
+
+    let disconnected = peer_events
+        .lock()
+        .unwrap()
+        .contains(&(peer_id, PeerConnectionEvent::Left));
+    assert!(disconnected);
+
+    let node = node.stop().await.unwrap();
+    let peer = peer.stop().await.unwrap();
+    node.waku_destroy().await.unwrap();
+    peer.waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn signed_message_is_verified_by_receiver() {
+    let node_key =
+        SecretKey::from_str("05f381866cc21f6c1e2e80e07fa732008e36d942dce3206ad6dcd6793c98d609")
+            .unwrap();
+    let sender = start_node(WakuNodeConfig {
+        node_key: Some(node_key),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let receiver = new_node(Default::default()).await.unwrap();
+
+    let rx_waku_message: Arc<Mutex<Option<WakuMessage>>> = Arc::new(Mutex::new(None));
+    let rx_waku_message_cloned = rx_waku_message.clone();
+    receiver
+        .set_event_callback(move |response| {
+            if let LibwakuResponse::Success(Some(v)) = response {
+                if let Ok(WakuEvent::WakuMessage(evt)) = serde_json::from_str(&v) {
+                    *rx_waku_message_cloned.lock().unwrap() = Some(evt.waku_message);
+                }
+            }
+        })
+        .unwrap();
+
+    let receiver = receiver.start().await.unwrap();
+    form_mesh(&receiver, &sender).await.unwrap();
+
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "signed", Encoding::Proto);
+    sender
+        .relay_publish_signed(ECHO_MESSAGE, content_topic, &pubsub_topic)
+        .await
+        .unwrap();
+    sleep(Duration::from_secs(1)).await;
+
+    let message = rx_waku_message
+        .lock()
+        .unwrap()
+        .take()
+        .expect("signed message should be received");
+    assert_eq!(message.version, 0);
+    let (payload, signer) = recover_signer(&message).unwrap();
+    assert_eq!(payload, ECHO_MESSAGE.as_bytes());
+    assert_eq!(signer, node_key.public_key(&secp256k1::Secp256k1::new()));
+
+    let sender = sender.stop().await.unwrap();
+    let receiver = receiver.stop().await.unwrap();
+    sender.waku_destroy().await.unwrap();
+    receiver.waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn await_message_request_response() {
+    let (responder, requester) = two_connected_nodes().await.unwrap();
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "rpc", Encoding::Proto);
+    let request = WakuMessage::new("ping 42", content_topic.clone(), 0, Vec::new(), false);
+    let reply = WakuMessage::new("pong 42", content_topic, 0, Vec::new(), false);
+
+    let respond = async {
+        responder
+            .await_message(
+                |evt| evt.waku_message.payload() == b"ping 42",
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap();
+        responder
+            .relay_publish_message(&reply, &pubsub_topic, None)
+            .await
+            .unwrap();
+    };
+    let request = async {
+        // let the responder start waiting before publishing the request
+        sleep(Duration::from_millis(500)).await;
+        requester
+            .relay_publish_message(&request, &pubsub_topic, None)
+            .await
+            .unwrap();
+        requester
+            .await_message(
+                |evt| evt.waku_message.payload() == b"pong 42",
+                Duration::from_secs(10),
+            )
+            .await
+    };
+    let (_, response) = tokio::join!(respond, request);
+    assert_eq!(response.unwrap().waku_message.payload(), b"pong 42");
+
+    let timed_out = requester
+        .await_message(|_| false, Duration::from_millis(200))
+        .await;
+    assert!(timed_out.is_err());
+
+    let requester = requester.stop().await.unwrap();
+    let responder = responder.stop().await.unwrap();
+    requester.waku_destroy().await.unwrap();
+    responder.waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn store_query_parallel_merges_topic_groups() {
+    let store_node = start_node(WakuNodeConfig {
+        store: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let client = start_node(Default::default()).await.unwrap();
+    let store_addr = form_mesh(&client, &store_node).await.unwrap();
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let topic_a = WakuContentTopic::new("toychat", "2", "group-a", Encoding::Proto);
+    let topic_b = WakuContentTopic::new("toychat", "2", "group-b", Encoding::Proto);
+    for content_topic in [&topic_a, &topic_b] {
+        let message = WakuMessage::new(
+            content_topic.content_topic_name.to_string(),
+            content_topic.clone(),
+            0,
+            Vec::new(),
+            false,
+        );
+        client
+            .relay_publish_message(&message, &pubsub_topic, None)
+            .await
+            .unwrap();
+    }
+    sleep(Duration::from_secs(1)).await;
+
+    let messages = client
+        .store_query_parallel(
+            vec![
+                (pubsub_topic.clone(), vec![topic_a]),
+                (pubsub_topic, vec![topic_b]),
+            ],
+            &store_addr.to_string(),
+        )
+        .await
+        .unwrap();
+
+    let payloads: HashSet<&str> = messages
+        .iter()
+        .map(|msg| from_utf8(msg.message.as_ref().unwrap().payload()).unwrap())
+        .collect();
+    assert_eq!(payloads, HashSet::from(["group-a", "group-b"]));
+
+    let store_node = store_node.stop().await.unwrap();
+    let client = client.stop().await.unwrap();
+    store_node.waku_destroy().await.unwrap();
+    client.waku_destroy().await.unwrap();
+}
+
+/// Plain HTTP/1.1 GET returning the response body, enough for the REST bridge tests
+#[cfg(feature = "rest")]
+async fn http_get(addr: std::net::SocketAddr, path: &str) -> serde_json::Value {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    serde_json::from_str(body).unwrap()
+}
+
+#[cfg(feature = "rest")]
+#[tokio::test]
+#[serial]
+async fn rest_health_and_peers() {
+    use waku_bindings::node::RestConfig;
+
+    let node = start_node(Default::default()).await.unwrap();
+    let peer = start_node(Default::default()).await.unwrap();
+    let peer_addr = connect_to(&node, &peer).await.unwrap().to_string();
+    let (_, peer_id) = peer_addr.rsplit_once("/p2p/").unwrap();
+    sleep(Duration::from_secs(1)).await;
+
+    let config = RestConfig {
+        bind_addr: "127.0.0.1:60030".parse().unwrap(),
+    };
+    let bind_addr = config.bind_addr;
+    tokio::select! {
+        res = node.serve_rest(config) => panic!("REST server stopped: {res:?}"),
+        _ = async {
+            // give the server time to start listening
+            sleep(Duration::from_millis(500)).await;
+
+            let health = http_get(bind_addr, "/health").await;
+            assert_eq!(health["status"], "ok");
+            assert!(health["version"].is_string());
+
+            let peers = http_get(bind_addr, "/peers").await;
+            assert!(peers.as_array().unwrap().iter().any(|id| id == peer_id));
+        } => {}
+    }
+
+    let node = node.stop().await.unwrap();
+    let peer = peer.stop().await.unwrap();
+    node.waku_destroy().await.unwrap();
+    peer.waku_destroy().await.unwrap();
+}
+
+/// Sink counting the messages it accepts
+#[derive(Default)]
+struct CountingSink {
+    count: usize,
+}
+
+impl MessageSink for CountingSink {
+    async fn accept(&mut self, _msg: StoreWakuMessageResponse) -> waku_bindings::Result<()> {
+        self.count += 1;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn store_query_into_feeds_sink() {
+    let store_node = start_node(WakuNodeConfig {
+        store: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let client = start_node(Default::default()).await.unwrap();
+    let store_addr = form_mesh(&client, &store_node).await.unwrap();
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "sink", Encoding::Proto);
+    for i in 0..7 {
+        let message = WakuMessage::new(
+            format!("message {i}"),
+            content_topic.clone(),
+            0,
+            Vec::new(),
+            false,
+        );
+        client
+            .relay_publish_message(&message, &pubsub_topic, None)
+            .await
+            .unwrap();
+    }
+    sleep(Duration::from_secs(1)).await;
+
+    // small pages so the sink is fed across several of them
+    let query = StoreQueryRequest::new()
+        .with_pubsub_topic(Some(pubsub_topic))
+        .with_content_topics(vec![content_topic])
+        .with_time_start(None)
+        .with_time_end(None)
+        .with_pagination_limit(Some(3));
+    let mut sink = CountingSink::default();
+    let count = client
+        .store_query_into(query, &store_addr.to_string(), &mut sink)
+        .await
+        .unwrap();
+    assert_eq!(count, 7);
+    assert_eq!(sink.count, 7);
+
+    let store_node = store_node.stop().await.unwrap();
+    let client = client.stop().await.unwrap();
+    store_node.waku_destroy().await.unwrap();
+    client.waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn rendezvous_discovery_yields_peers() {
+    let point = start_node(WakuNodeConfig {
+        rendezvous: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let point_addr: Multiaddr = point.store_peer_address().await.unwrap().parse().unwrap();
+
+    let mut nodes = Vec::new();
+    for _ in 0..2 {
+        let node = start_node(WakuNodeConfig {
+            rendezvous: Some(true),
+            rendezvous_nodes: Some(vec![point_addr.clone()]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        nodes.push(node);
+    }
+
+    // Both nodes registered with the rendezvous point, so they should find each other
+    let registered_addr = nodes[1].listen_addresses().await.unwrap()[0].to_string();
+    let (_, registered_peer) = registered_addr.rsplit_once("/p2p/").unwrap();
+    let registered_peer = registered_peer.to_string();
+    let discovered = time::timeout(Duration::from_secs(30), async {
+        while !nodes[0]
+            .connected_peer_ids()
+            .await
+            .unwrap()
+            .contains(&registered_peer)
+        {
+            sleep(Duration::from_secs(1)).await;
+        }
+    })
+    .await;
+    assert!(discovered.is_ok(), "peer not discovered via rendezvous");
+
+    for node in nodes {
+        node.stop().await.unwrap().waku_destroy().await.unwrap();
+    }
+    point.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn all_topic_healths_lists_subscribed_topics() {
+    let node = start_node(Default::default()).await.unwrap();
+
+    let topic_a = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let topic_b = PubsubTopic::new("/waku/2/rs/0/2");
+    node.relay_subscribe(&topic_b).await.unwrap();
+
+    let healths = node.all_topic_healths().await.unwrap();
+    assert_eq!(healths.len(), 2);
+    assert!(healths.contains_key(&topic_a));
+    assert!(healths.contains_key(&topic_b));
+
+    node.relay_unsubscribe(&topic_b).await.unwrap();
+    let healths = node.all_topic_healths().await.unwrap();
+    assert!(healths.contains_key(&topic_a));
+    assert!(!healths.contains_key(&topic_b));
+    assert!(node.topic_health(&topic_b).await.is_err());
+
+    node.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn peer_protocols_of_store_node() {
+    let store_node = start_node(WakuNodeConfig {
+        store: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let client = start_node(Default::default()).await.unwrap();
+    let store_addr = connect_to(&client, &store_node).await.unwrap().to_string();
+
+    let protocols = client.peer_protocols(&store_addr).await.unwrap();
+    assert!(protocols.iter().any(|p| p == "/vac/waku/store-query/3.0.0"));
+    assert!(protocols.iter().any(|p| p == "/vac/waku/relay/2.0.0"));
+
+    store_node
+        .stop()
+        .await
+        .unwrap()
+        .waku_destroy()
+        .await
+        .unwrap();
+    client.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn queued_relay_subscription_applied_on_start() {
+    let sender = new_node(Default::default()).await.unwrap();
+    let receiver = new_node(Default::default()).await.unwrap();
+
+    let pubsub_topic = PubsubTopic::new(TEST_PUBSUBTOPIC);
+    sender.queue_relay_subscribe(&pubsub_topic);
+    receiver.queue_relay_subscribe(&pubsub_topic);
+
+    let sender = sender.start().await.unwrap();
+    let receiver = receiver.start().await.unwrap();
+    form_mesh(&receiver, &sender).await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "queued", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+    let publish = async {
+        // let the receiver start waiting before publishing
+        sleep(Duration::from_millis(500)).await;
+        sender
+            .relay_publish_message(&message, &pubsub_topic, None)
+            .await
+            .unwrap();
+    };
+    let receive = receiver.await_message(
+        |evt| evt.waku_message.payload() == ECHO_MESSAGE.as_bytes(),
+        Duration::from_secs(5),
+    );
+    let (_, received) = tokio::join!(publish, receive);
+    assert_eq!(received.unwrap().pubsub_topic, TEST_PUBSUBTOPIC);
+
+    sender.stop().await.unwrap().waku_destroy().await.unwrap();
+    receiver.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn two_connected_nodes_exchange_messages() {
+    let (node1, node2) = two_connected_nodes().await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "harness", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+    let publish = async {
+        // let node2 start waiting before publishing
+        sleep(Duration::from_millis(500)).await;
+        node1
+            .relay_publish_message(&message, &PubsubTopic::new(TESTING_PUBSUB_TOPIC), None)
+            .await
+            .unwrap();
+    };
+    let receive = node2.await_message(
+        |evt| evt.waku_message.payload() == ECHO_MESSAGE.as_bytes(),
+        Duration::from_secs(5),
+    );
+    let (_, received) = tokio::join!(publish, receive);
+    assert_eq!(received.unwrap().pubsub_topic, TESTING_PUBSUB_TOPIC);
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn relay_enough_peers_once_mesh_formed() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let node1 = start_node(Default::default()).await.unwrap();
+    let node2 = start_node(Default::default()).await.unwrap();
+
+    // isolated node
+    assert!(!node1.relay_enough_peers(&pubsub_topic).await.unwrap());
+
+    form_mesh(&node2, &node1).await.unwrap();
+    assert!(node1.relay_enough_peers(&pubsub_topic).await.unwrap());
+    assert!(node2.relay_enough_peers(&pubsub_topic).await.unwrap());
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn publish_if_absent_skips_stored_message() {
+    let store_node = start_node(WakuNodeConfig {
+        store: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let client = start_node(Default::default()).await.unwrap();
+    let store_addr = form_mesh(&client, &store_node).await.unwrap().to_string();
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "idempotent", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+
+    let first = client
+        .publish_if_absent(&message, &pubsub_topic, &store_addr)
+        .await
+        .unwrap();
+    assert!(first.is_some());
+    // Let the store node archive the message
+    sleep(Duration::from_secs(1)).await;
+
+    let second = client
+        .publish_if_absent(&message, &pubsub_topic, &store_addr)
+        .await
+        .unwrap();
+    assert_eq!(second, None);
+
+    store_node
+        .stop()
+        .await
+        .unwrap()
+        .waku_destroy()
+        .await
+        .unwrap();
+    client.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn store_query_through_store_peer_address() {
+    let node_a = start_node(WakuNodeConfig {
+        store: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let node_b = start_node(Default::default()).await.unwrap();
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let store_addr = node_a.store_peer_address().await.unwrap();
+    assert!(store_addr.contains("/p2p/"));
+    node_b
+        .connect(&store_addr.parse().unwrap(), None)
+        .await
+        .unwrap();
+    sleep(MESH_FORMATION_DELAY).await;
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "storepeer", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic.clone(), 0, Vec::new(), false);
+    node_b
+        .relay_publish_message(&message, &pubsub_topic, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_secs(1)).await;
+
+    let messages = node_b
+        .store_query(
+            Some(pubsub_topic),
+            vec![content_topic],
+            &store_addr,
+            true,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(
+        messages[0].message.as_ref().unwrap().payload(),
+        ECHO_MESSAGE.as_bytes()
+    );
+
+    node_a.stop().await.unwrap().waku_destroy().await.unwrap();
+    node_b.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn relay_publish_with_retry_waits_for_mesh() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let node1 = start_node(Default::default()).await.unwrap();
+    let node2 = start_node(Default::default()).await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "retry", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+
+    // The isolated node has no peer to publish to
+    let err = node1
+        .relay_publish_message(&message, &pubsub_topic, None)
+        .await
+        .unwrap_err();
+    assert!(err.is_retryable());
+    let err = node1
+        .relay_publish_with_retry(&message, &pubsub_topic, 0, Duration::ZERO)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, waku_bindings::WakuError::InvalidConfig(_)));
+    assert!(!err.is_retryable());
+
+    let form_mesh = async {
+        sleep(Duration::from_secs(1)).await;
+        connect_to(&node2, &node1).await.unwrap();
+    };
+    let publish =
+        node1.relay_publish_with_retry(&message, &pubsub_topic, 8, Duration::from_millis(250));
+    let (_, published) = tokio::join!(form_mesh, publish);
+    assert!(published.is_ok());
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn connection_events_report_joined_peer() {
+    let node1 = start_node(Default::default()).await.unwrap();
+    let node2 = start_node(Default::default()).await.unwrap();
+
+    let mut events = node1.connection_events().await.unwrap();
+    connect_to(&node2, &node1).await.unwrap();
+
+    let evt = time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .expect("connection event within timeout")
+        .expect("connection events stream open");
+    assert_eq!(evt.peer_event, PeerConnectionEvent::Joined);
+    assert!(!evt.peer_id.is_empty());
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn paused_events_delivered_on_resume() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let node1 = start_node(Default::default()).await.unwrap();
+    let node2 = new_node(Default::default()).await.unwrap();
+
+    let received: Arc<Mutex<Vec<Vec<u8>>>> = Arc::new(Mutex::new(Vec::new()));
+    let received_cloned = received.clone();
+    node2
+        .set_event_callback(move |response| {
+            if let LibwakuResponse::Success(Some(v)) = response {
+                if let Ok(WakuEvent::WakuMessage(evt)) = serde_json::from_str(&v) {
+                    received_cloned
+                        .lock()
+                        .unwrap()
+                        .push(evt.waku_message.payload().to_vec());
+                }
+            }
+        })
+        .unwrap();
+    let node2 = node2.start().await.unwrap();
+    form_mesh(&node2, &node1).await.unwrap();
+
+    node2.pause_events();
+    let content_topic = WakuContentTopic::new("toychat", "2", "paused", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+    node1
+        .relay_publish_message(&message, &pubsub_topic, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_secs(1)).await;
+    assert!(received.lock().unwrap().is_empty());
+
+    node2.resume_events();
+    assert_eq!(
+        *received.lock().unwrap(),
+        vec![ECHO_MESSAGE.as_bytes().to_vec()]
+    );
+    assert_eq!(node2.events_dropped_while_paused(), 0);
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn event_stream_drops_oldest_when_flooded() {
+    use waku_bindings::node::OverflowPolicy;
+
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let (node1, node2) = two_connected_nodes().await.unwrap();
+
+    let mut events = node2.event_stream(2, OverflowPolicy::DropOldest);
+    let content_topic = WakuContentTopic::new("toychat", "2", "flood", Encoding::Proto);
+    for i in 0..5 {
+        let message = WakuMessage::new(
+            format!("message {i}"),
+            content_topic.clone(),
+            0,
+            Vec::new(),
+            false,
+        );
+        node1
+            .relay_publish_message(&message, &pubsub_topic, None)
+            .await
+            .unwrap();
+    }
+    // Nothing is consumed until every message arrived
+    sleep(Duration::from_secs(1)).await;
+
+    assert_eq!(events.queue_depth(), 2);
+    assert!(events.dropped() >= 3);
+    let mut payloads = Vec::new();
+    while events.queue_depth() > 0 {
+        if let Some(WakuEvent::WakuMessage(evt)) = events.recv().await {
+            payloads.push(from_utf8(evt.waku_message.payload()).unwrap().to_string());
+        }
+    }
+    assert_eq!(payloads.last().map(String::as_str), Some("message 4"));
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn event_stream_block_keeps_every_event() {
+    use waku_bindings::node::OverflowPolicy;
+
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let (node1, node2) = two_connected_nodes().await.unwrap();
+
+    let mut events = node2.event_stream(2, OverflowPolicy::Block);
+    let content_topic = WakuContentTopic::new("toychat", "2", "block", Encoding::Proto);
+    for i in 0..5 {
+        let message = WakuMessage::new(
+            format!("message {i}"),
+            content_topic.clone(),
+            0,
+            Vec::new(),
+            false,
+        );
+        node1
+            .relay_publish_message(&message, &pubsub_topic, None)
+            .await
+            .unwrap();
+    }
+    // Nothing is consumed until every message arrived
+    sleep(Duration::from_secs(1)).await;
+
+    assert_eq!(events.queue_depth(), 2);
+    let mut payloads = Vec::new();
+    while payloads.len() < 5 {
+        if let Some(WakuEvent::WakuMessage(evt)) = events.recv().await {
+            payloads.push(from_utf8(evt.waku_message.payload()).unwrap().to_string());
+        }
+    }
+    assert_eq!(payloads[0], "message 0");
+    assert_eq!(payloads[4], "message 4");
+    assert_eq!(events.dropped(), 0);
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn store_query_grouped_by_content_topic() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let config = || WakuNodeConfig {
+        store: Some(true),
+        ..Default::default()
+    };
+    let (node1, node2) = two_connected_nodes_with(config(), config()).await.unwrap();
+
+    let topics: Vec<WakuContentTopic> = ["chat", "presence", "typing"]
+        .into_iter()
+        .map(|name| WakuContentTopic::new("toychat", "2", name, Encoding::Proto))
+        .collect();
+    // Three messages on chat, two on presence, one on typing
+    for (i, topic) in [0, 0, 1, 0, 1, 2].into_iter().enumerate() {
+        let message = WakuMessage::new(
+            format!("message {i}"),
+            topics[topic].clone(),
+            0,
+            Vec::new(),
+            false,
+        );
+        node1
+            .relay_publish_message(&message, &pubsub_topic, None)
+            .await
+            .unwrap();
+    }
+    sleep(Duration::from_secs(1)).await;
+
+    let store_peer = node2.store_peer_address().await.unwrap();
+    let groups = node1
+        .store_query_grouped(topics.clone(), Some(pubsub_topic.clone()), &store_peer)
+        .await
+        .unwrap();
+
+    assert_eq!(groups.len(), 3);
+    for (topic, expected) in topics.iter().zip([3, 2, 1]) {
+        assert_eq!(groups[topic].len(), expected);
+        assert!(groups[topic].iter().all(|msg| msg
+            .message
+            .as_ref()
+            .is_some_and(|message| message.content_topic == topic.to_string())));
+    }
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn store_time_range_brackets_stored_messages() {
+    use waku_bindings::Timestamp;
+
+    let (store_node, client) = two_connected_nodes_with(
+        WakuNodeConfig {
+            store: Some(true),
+            ..Default::default()
+        },
+        Default::default(),
+    )
+    .await
+    .unwrap();
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let store_addr = store_node.store_peer_address().await.unwrap();
+    assert_eq!(client.store_time_range(&store_addr).await.unwrap(), None);
+    // The client doesn't mount the store protocol
+    let client_addr = client.store_peer_address().await.unwrap();
+    assert!(store_node.store_time_range(&client_addr).await.is_err());
+
+    // Within the time variance accepted by the store
+    let now = Timestamp::now();
+    let timestamps = [
+        now - Duration::from_secs(10),
+        now - Duration::from_secs(5),
+        now - Duration::from_secs(8),
+    ];
+    let content_topic = WakuContentTopic::new("toychat", "2", "timerange", Encoding::Proto);
+    for timestamp in timestamps {
+        let message = WakuMessage::new(ECHO_MESSAGE, content_topic.clone(), 0, Vec::new(), false)
+            .with_timestamp(timestamp);
+        client
+            .relay_publish_message(&message, &pubsub_topic, None)
+            .await
+            .unwrap();
+    }
+    sleep(Duration::from_secs(1)).await;
+
+    let (oldest, newest) = client.store_time_range(&store_addr).await.unwrap().unwrap();
+    assert_eq!(oldest, timestamps[0].as_nanos());
+    assert_eq!(newest, timestamps[1].as_nanos());
+
+    store_node
+        .stop()
+        .await
+        .unwrap()
+        .waku_destroy()
+        .await
+        .unwrap();
+    client.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn store_prune_unsupported_by_libwaku() {
+    let (store_node, client) = two_connected_nodes_with(
+        WakuNodeConfig {
+            store: Some(true),
+            store_message_retention_policy: Some("time:86400".to_string()),
+            ..Default::default()
+        },
+        Default::default(),
+    )
+    .await
+    .unwrap();
+
+    let err = client
+        .store_prune(Duration::from_secs(86400))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, waku_bindings::WakuError::InvalidConfig(ref msg) if msg.contains("not mounted"))
+    );
+
+    // libwaku enforces retention on its own schedule only, pruning on demand isn't available
+    let err = store_node
+        .store_prune(Duration::from_secs(86400))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, waku_bindings::WakuError::InvalidConfig(ref msg) if msg.contains("retention policy"))
+    );
+
+    store_node
+        .stop()
+        .await
+        .unwrap()
+        .waku_destroy()
+        .await
+        .unwrap();
+    client.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn subscription_state_carried_to_another_node() {
+    // Not made by the testing helpers, which subscribe every node to a relay topic
+    let pubsub_topic = PubsubTopic::new("/waku/2/rs/0/1");
+    let mut nodes = Vec::new();
+    for _ in 0..3 {
+        let node = waku_new(Some(WakuNodeConfig {
+            tcp_port: Some(0),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+        node.set_event_callback(|_| {}).unwrap();
+        nodes.push(node.start().await.unwrap());
+    }
+    let [original, restored, publisher] = <[_; 3]>::try_from(nodes).ok().unwrap();
+
+    original.relay_subscribe(&pubsub_topic).await.unwrap();
+    let state = original.subscription_state();
+    assert_eq!(state.relay_topics, vec![pubsub_topic.clone()]);
+
+    // The state survives serialization, as when kept across a restart
+    let state: SubscriptionState =
+        serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+    restored.apply_subscription_state(&state).await.unwrap();
+    assert_eq!(restored.subscription_state(), state);
+
+    publisher.relay_subscribe(&pubsub_topic).await.unwrap();
+    connect_to(&publisher, &original).await.unwrap();
+    form_mesh(&publisher, &restored).await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "restart", Encoding::Proto);
+    async fn received_by(
+        node: &WakuNodeHandle<Running>,
+        content_topic: WakuContentTopic,
+    ) -> waku_bindings::Result<waku_bindings::WakuMessageEvent> {
+        node.await_message(
+            move |evt| evt.waku_message.content_topic == content_topic,
+            Duration::from_secs(5),
+        )
+        .await
+    }
+    let (original_received, restored_received, published) = tokio::join!(
+        received_by(&original, content_topic.clone()),
+        received_by(&restored, content_topic.clone()),
+        async {
+            // Let both listeners register before publishing
+            sleep(Duration::from_millis(200)).await;
+            let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+            publisher
+                .relay_publish_message(&message, &pubsub_topic, None)
+                .await
+        }
+    );
+    published.unwrap();
+    original_received.unwrap();
+    restored_received.unwrap();
+
+    for node in [original, restored, publisher] {
+        node.stop().await.unwrap().waku_destroy().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn lightpush_through_explicit_peer() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let (service, receiver) = two_connected_nodes_with(
+        WakuNodeConfig {
+            lightpush: Some(true),
+            ..Default::default()
+        },
+        Default::default(),
+    )
+    .await
+    .unwrap();
+    // Without relay, so not made by the testing helpers
+    let client = waku_new(Some(WakuNodeConfig {
+        tcp_port: Some(0),
+        relay: Some(false),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+    client.set_event_callback(|_| {}).unwrap();
+    let client = client.start().await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "pinned", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+    let err = client
+        .lightpush_publish_message_to(&message, &pubsub_topic, Some("not an address"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, waku_bindings::WakuError::Parse(_)));
+
+    let service_addr = service.store_peer_address().await.unwrap();
+    client
+        .lightpush_publish_message_to(&message, &pubsub_topic, Some(&service_addr))
+        .await
+        .unwrap();
+
+    for node in [service, receiver, client] {
+        node.stop().await.unwrap().waku_destroy().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn lightpush_failover_skips_unreachable_peer() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let (service, receiver) = two_connected_nodes_with(
+        WakuNodeConfig {
+            lightpush: Some(true),
+            ..Default::default()
+        },
+        Default::default(),
+    )
+    .await
+    .unwrap();
+    // Without relay, so not made by the testing helpers
+    let client = waku_new(Some(WakuNodeConfig {
+        tcp_port: Some(0),
+        relay: Some(false),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+    client.set_event_callback(|_| {}).unwrap();
+    let client = client.start().await.unwrap();
+
+    // Nothing listens on this port
+    let unreachable =
+        "/ip4/127.0.0.1/tcp/60099/p2p/16Uiu2HAmVFXtAfSj4EiR7mL2KvL4EE2wztuQgUSBoj2Jx2KeXFLN";
+    let working = service.store_peer_address().await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "failover", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+    let result = client
+        .lightpush_publish_failover(&message, &pubsub_topic, &[unreachable, &working])
+        .await
+        .unwrap();
+    assert_eq!(result.peer, working);
+    assert_eq!(result.message_hash, message.hash(&pubsub_topic));
+    assert_eq!(result.relay_peer_count, None);
+
+    let err = client
+        .lightpush_publish_failover(&message, &pubsub_topic, &[unreachable])
+        .await;
+    assert!(err.is_err());
+
+    for node in [service, receiver, client] {
+        node.stop().await.unwrap().waku_destroy().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn peers_by_protocol_lists_store_node() {
+    let store_node = start_node(WakuNodeConfig {
+        store: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let client = start_node(Default::default()).await.unwrap();
+    connect_to(&client, &store_node).await.unwrap();
+    let store_addr = store_node.store_peer_address().await.unwrap();
+    let store_peer_id = store_addr.rsplit("/p2p/").next().unwrap();
+
+    let store_peers = client.peers_by_protocol(Protocol::Store).await.unwrap();
+    let peer = store_peers
+        .iter()
+        .find(|peer| peer.peer_id == store_peer_id)
+        .expect("store node listed as a store peer");
+    assert!(peer.connected);
+    assert!(peer
+        .protocols
+        .iter()
+        .any(|p| p == "/vac/waku/store-query/3.0.0"));
+
+    store_node
+        .stop()
+        .await
+        .unwrap()
+        .waku_destroy()
+        .await
+        .unwrap();
+    client.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn relay_publish_autoshard_outside_waku_network() {
+    let node = start_node(Default::default()).await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "autoshard", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+    // The testing nodes don't run in The Waku Network cluster, whose shard count is the only
+    // one known
+    let err = node
+        .relay_publish_autoshard(&message, None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, waku_bindings::WakuError::InvalidConfig(_)));
+
+    node.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn relay_publish_logged_returns_published_json() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let (node1, node2) = two_connected_nodes().await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "audit", Encoding::Proto);
+    let mut message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+    message.timestamp = 0;
+    let publish = async {
+        sleep(Duration::from_millis(500)).await;
+        node1
+            .relay_publish_message_logged(&message, &pubsub_topic, None)
+            .await
+            .unwrap()
+    };
+    let receive = node2.await_message(
+        |evt| evt.waku_message.payload() == ECHO_MESSAGE.as_bytes(),
+        Duration::from_secs(5),
+    );
+    let ((hash, json), received) = tokio::join!(publish, receive);
+    let received = received.unwrap();
+
+    let published: WakuMessage = serde_json::from_str(&json).unwrap();
+    assert_ne!(published.timestamp, 0);
+    assert_eq!(published.payload(), message.payload());
+    assert_eq!(published.timestamp, received.waku_message.timestamp);
+    assert_eq!(hash, received.message_hash);
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn request_reply_with_echo_service() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let (service, client) = two_connected_nodes().await.unwrap();
+
+    let requests = WakuContentTopic::new("echo", "1", "request", Encoding::Proto);
+    let replies = WakuContentTopic::new("echo", "1", "reply", Encoding::Proto);
+
+    // Echo service: answers a request with its payload on the reply topic
+    let echo = async {
+        let requests = requests.clone();
+        let request = service
+            .await_message(
+                move |evt| evt.waku_message.content_topic == requests,
+                Duration::from_secs(15),
+            )
+            .await
+            .unwrap();
+        let reply = WakuMessage::new(
+            request.waku_message.payload(),
+            replies.clone(),
+            0,
+            Vec::new(),
+            false,
+        );
+        service
+            .relay_publish_message(&reply, &pubsub_topic, None)
+            .await
+            .unwrap();
+    };
+    let request = WakuMessage::new(ECHO_MESSAGE, requests.clone(), 0, Vec::new(), false);
+    let exchange = async {
+        let replies = replies.clone();
+        // let the service start waiting before publishing the request
+        sleep(Duration::from_millis(500)).await;
+        client
+            .request_reply(
+                &request,
+                move |evt| evt.waku_message.content_topic == replies,
+                &pubsub_topic,
+                Duration::from_secs(10),
+            )
+            .await
+    };
+    let (_, reply) = tokio::join!(echo, exchange);
+    assert_eq!(
+        reply.unwrap().waku_message.payload(),
+        ECHO_MESSAGE.as_bytes()
+    );
+
+    client.stop().await.unwrap().waku_destroy().await.unwrap();
+    service.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn discovered_peers_not_kept_connected_without_auto_connect() {
+    let node = start_node(WakuNodeConfig {
+        auto_connect_discovered: Some(false),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let dialed = start_node(Default::default()).await.unwrap();
+    let stranger = start_node(Default::default()).await.unwrap();
+
+    let peer_id = |address: Multiaddr| {
+        address
+            .to_string()
+            .rsplit("/p2p/")
+            .next()
+            .unwrap()
+            .to_string()
+    };
+    let dialed_id = peer_id(connect_to(&node, &dialed).await.unwrap());
+    // A peer the node learns about without dialing it
+    connect_to(&stranger, &node).await.unwrap();
+    let stranger_id = peer_id(stranger.listen_addresses().await.unwrap()[0].clone());
+    sleep(Duration::from_secs(3)).await;
+
+    let known_peers = node.known_peers().await.unwrap();
+    assert!(known_peers.contains(&dialed_id));
+    assert!(known_peers.contains(&stranger_id));
+    assert_eq!(node.connected_peer_ids().await.unwrap(), vec![dialed_id]);
+
+    node.stop().await.unwrap().waku_destroy().await.unwrap();
+    dialed.stop().await.unwrap().waku_destroy().await.unwrap();
+    stranger.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn connected_peer_count_after_connecting() {
+    let node1 = start_node(Default::default()).await.unwrap();
+    let node2 = start_node(Default::default()).await.unwrap();
+
+    assert_eq!(node1.connected_peer_count().await.unwrap(), 0);
+    connect_to(&node1, &node2).await.unwrap();
+    sleep(Duration::from_secs(1)).await;
+    assert!(node1.connected_peer_count().await.unwrap() >= 1);
+    assert!(node2.connected_peer_count().await.unwrap() >= 1);
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn connected_peers_list_protocols() {
+    let node1 = start_node(Default::default()).await.unwrap();
+    let node2 = start_node(Default::default()).await.unwrap();
+
+    connect_to(&node1, &node2).await.unwrap();
+    sleep(Duration::from_secs(1)).await;
+
+    let node2_addr = node2.store_peer_address().await.unwrap();
+    let node2_id = node2_addr.rsplit("/p2p/").next().unwrap();
+    let peers = node1.connected_peers().await.unwrap();
+    let peer = peers
+        .iter()
+        .find(|peer| peer.peer_id == node2_id)
+        .expect("node2 listed");
+    assert!(peer.connected);
+    assert!(peer
+        .protocols
+        .iter()
+        .any(|protocol| protocol == "/vac/waku/relay/2.0.0"));
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn subscribe_auto_picks_relay_or_filter() {
+    let pubsub_topic = PubsubTopic::new("/waku/2/rs/0/1");
+    let content_topics = vec![WakuContentTopic::new(
+        "toychat",
+        "2",
+        "auto",
+        Encoding::Proto,
+    )];
+
+    // Not made by the testing helpers, which subscribe every node to a relay topic
+    let full_node = waku_new(Some(WakuNodeConfig {
+        tcp_port: Some(0),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+    let edge_node = waku_new(Some(WakuNodeConfig {
+        tcp_port: Some(0),
+        relay: Some(false),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+    full_node.set_event_callback(|_| {}).unwrap();
+    edge_node.set_event_callback(|_| {}).unwrap();
+    let full_node = full_node.start().await.unwrap();
+    let edge_node = edge_node.start().await.unwrap();
+    connect_to(&edge_node, &full_node).await.unwrap();
+
+    assert!(full_node.capabilities().contains(&Protocol::Relay));
+    let subscription = full_node
+        .subscribe_auto(&pubsub_topic, content_topics.clone())
+        .await
+        .unwrap();
+    assert_eq!(subscription.protocol, Protocol::Relay);
+    assert!(full_node
+        .subscription_state()
+        .relay_topics
+        .contains(&pubsub_topic));
+
+    assert!(!edge_node.capabilities().contains(&Protocol::Relay));
+    // Whether a filter service is available or not, relay is never tried on the edge node
+    if let Ok(subscription) = edge_node
+        .subscribe_auto(&pubsub_topic, content_topics)
+        .await
+    {
+        assert_eq!(subscription.protocol, Protocol::Filter);
+    }
+    assert!(edge_node.subscription_state().relay_topics.is_empty());
+
+    full_node.unsubscribe(subscription).await.unwrap();
+    assert!(full_node.subscription_state().relay_topics.is_empty());
+
+    for node in [full_node, edge_node] {
+        node.stop().await.unwrap().waku_destroy().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn store_query_page_leaves_pagination_to_caller() {
+    let store_node = start_node(WakuNodeConfig {
+        store: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let client = start_node(Default::default()).await.unwrap();
+    let store_addr = form_mesh(&client, &store_node).await.unwrap().to_string();
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "paging", Encoding::Proto);
+    for i in 0..5 {
+        let message = WakuMessage::new(
+            format!("message {i}"),
+            content_topic.clone(),
+            0,
+            Vec::new(),
+            false,
+        );
+        client
+            .relay_publish_message(&message, &pubsub_topic, None)
+            .await
+            .unwrap();
+    }
+    sleep(Duration::from_secs(1)).await;
+
+    let query = StoreQueryRequest::new()
+        .with_pubsub_topic(Some(pubsub_topic))
+        .with_content_topics(vec![content_topic])
+        .with_time_start(None)
+        .with_time_end(None)
+        .with_pagination_limit(Some(2));
+    let first = client
+        .store_query_page(query.clone(), &store_addr, None)
+        .await
+        .unwrap();
+    assert_eq!(first.messages.len(), 2);
+    assert!(first.pagination_cursor.is_some());
+
+    let mut pages = vec![first.messages.len()];
+    let mut cursor = first.pagination_cursor;
+    while cursor.is_some() {
+        let page = client
+            .store_query_page(
+                query.clone().with_pagination_cursor(cursor),
+                &store_addr,
+                None,
+            )
+            .await
+            .unwrap();
+        pages.push(page.messages.len());
+        cursor = page.pagination_cursor;
+    }
+    assert_eq!(pages.iter().sum::<usize>(), 5);
+
+    for node in [store_node, client] {
+        node.stop().await.unwrap().waku_destroy().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn publish_stats_count_published_messages() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let (node1, node2) = two_connected_nodes().await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "stats", Encoding::Proto);
+    for _ in 0..3 {
+        let message = WakuMessage::new(ECHO_MESSAGE, content_topic.clone(), 0, Vec::new(), false);
+        node1
+            .relay_publish_message(&message, &pubsub_topic, None)
+            .await
+            .unwrap();
+    }
+
+    let stats = node1.publish_stats();
+    let topic_stats = stats.by_content_topic[&content_topic];
+    assert_eq!(topic_stats.messages, 3);
+    assert_eq!(topic_stats.bytes, 3 * ECHO_MESSAGE.len() as u64);
+    assert_eq!(stats.total(), topic_stats);
+    assert!(node2.publish_stats().by_content_topic.is_empty());
+
+    for node in [node1, node2] {
+        node.stop().await.unwrap().waku_destroy().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn store_query_by_hashes_fetches_content() {
+    let (node_a, node_b) = two_connected_nodes_with(
+        WakuNodeConfig {
+            store: Some(true),
+            ..Default::default()
+        },
+        Default::default(),
+    )
+    .await
+    .unwrap();
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let store_addr = node_a.store_peer_address().await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "byhash", Encoding::Proto);
+    let mut hashes = Vec::new();
+    for i in 0..3 {
+        let message = WakuMessage::new(
+            format!("message {i}"),
+            content_topic.clone(),
+            0,
+            Vec::new(),
+            false,
+        );
+        hashes.push(
+            node_b
+                .relay_publish_message(&message, &pubsub_topic, None)
+                .await
+                .unwrap(),
+        );
+    }
+    sleep(Duration::from_secs(1)).await;
+
+    let wanted = vec![hashes[0].clone(), hashes[2].clone()];
+    let messages = node_b
+        .store_query_by_hashes(wanted.clone(), &store_addr, true, None)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 2);
+    for message in &messages {
+        assert!(wanted.contains(&message.message_hash));
+        assert!(message.message.is_some());
+    }
+
+    let messages = node_b
+        .store_query_by_hashes(wanted, &store_addr, false, None)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 2);
+    assert!(messages.iter().all(|message| message.message.is_none()));
+
+    node_a.stop().await.unwrap().waku_destroy().await.unwrap();
+    node_b.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn static_bootstrap_peers_dialed_on_start() {
+    let bootstrap = start_node(Default::default()).await.unwrap();
+    let bootstrap_addr: Multiaddr = bootstrap
+        .store_peer_address()
+        .await
+        .unwrap()
+        .parse()
+        .unwrap();
+    let bootstrap_id = bootstrap_addr
+        .to_string()
+        .rsplit("/p2p/")
+        .next()
+        .unwrap()
+        .to_string();
+
+    let node = start_node(WakuNodeConfig {
+        static_bootstrap_peers: Some(vec![bootstrap_addr]),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    assert!(node
+        .connected_peer_ids()
+        .await
+        .unwrap()
+        .contains(&bootstrap_id));
+
+    for node in [bootstrap, node] {
+        node.stop().await.unwrap().waku_destroy().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn filter_toggle_requires_restart() {
+    let node = start_node(Default::default()).await.unwrap();
+
+    for enabled in [false, true] {
+        assert!(matches!(
+            node.filter_set_enabled(enabled).await,
+            Err(waku_bindings::WakuError::InvalidConfig(_))
+        ));
+    }
+
+    node.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn filter_subscriptions_are_reported() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let content_topics = vec![
+        WakuContentTopic::new("toychat", "2", "filter-a", Encoding::Proto),
+        WakuContentTopic::new("toychat", "2", "filter-b", Encoding::Proto),
+    ];
+
+    let full_node = start_node(Default::default()).await.unwrap();
+    // Without relay, so not made by the testing helpers
+    let edge_node = waku_new(Some(WakuNodeConfig {
+        tcp_port: Some(0),
+        relay: Some(false),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+    edge_node.set_event_callback(|_| {}).unwrap();
+    let edge_node = edge_node.start().await.unwrap();
+    let full_node_addr = connect_to(&edge_node, &full_node)
+        .await
+        .unwrap()
+        .to_string();
+
+    assert!(edge_node.filter_subscriptions().await.unwrap().is_empty());
+    edge_node
+        .filter_subscribe(&pubsub_topic, content_topics.clone(), Some(&full_node_addr))
+        .await
+        .unwrap();
+
+    let subscriptions = edge_node.filter_subscriptions().await.unwrap();
+    assert_eq!(subscriptions.len(), 1);
+    assert_eq!(subscriptions[0].0, pubsub_topic);
+    assert_eq!(subscriptions[0].1, content_topics);
+
+    edge_node.filter_unsubscribe_all().await.unwrap();
+    assert!(edge_node.filter_subscriptions().await.unwrap().is_empty());
+
+    for node in [full_node, edge_node] {
+        node.stop().await.unwrap().waku_destroy().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn discovery_status_reports_dns_resolution() {
+    let enrtree_url = waku_bindings::Fleet::TheWakuNetwork.dns_discovery_url();
+    let node = start_node(WakuNodeConfig {
+        dns_discovery: Some(true),
+        dns_discovery_url: Some(enrtree_url),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let status = node.discovery_status();
+    assert!(status.dns.enabled);
+    assert!(!status.discv5.enabled);
+
+    // Resolution needs network access, only its outcome is checked without it
+    let enrtree_url = enrtree_url.parse().unwrap();
+    match node
+        .dns_discovery(&enrtree_url, None, None, Some(Duration::from_secs(10)))
+        .await
+    {
+        Ok(peers) => {
+            let status = node.discovery_status();
+            assert!(status.dns.last_success.is_some());
+            assert_eq!(status.dns.peers_found, peers.len() as u64);
+        }
+        Err(_) => {
+            let status = node.discovery_status();
+            assert_eq!(status.dns.last_success, None);
+        }
+    }
+
+    node.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn store_keeps_message_meta() {
+    let store_node = start_node(WakuNodeConfig {
+        store: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let client = start_node(Default::default()).await.unwrap();
+    let store_addr = form_mesh(&client, &store_node).await.unwrap();
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+
+    let meta = vec![0x00, 0xde, 0xad, 0xbe, 0xef, 0xff];
+    let content_topic = WakuContentTopic::new("toychat", "2", "meta", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic.clone(), 0, &meta, false);
+    client
+        .relay_publish_message(&message, &pubsub_topic, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_secs(1)).await;
+
+    let messages = client
+        .store_latest(
+            vec![content_topic],
+            Some(pubsub_topic),
+            1,
+            &store_addr.to_string(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].message.as_ref().unwrap().meta, meta);
+
+    store_node
+        .stop()
+        .await
+        .unwrap()
+        .waku_destroy()
+        .await
+        .unwrap();
+    client.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn try_next_event_polls_buffered_events() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let node1 = start_node(Default::default()).await.unwrap();
+    let node2 = new_node(Default::default()).await.unwrap();
+    node2.buffer_events(16).unwrap();
+    assert!(node2.try_next_event().is_none());
+
+    let node2 = node2.start().await.unwrap();
+    form_mesh(&node1, &node2).await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "poll", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+    node1
+        .relay_publish_message(&message, &pubsub_topic, None)
+        .await
+        .unwrap();
+
+    let mut received = false;
+    for _ in 0..50 {
+        while let Some(event) = node2.try_next_event() {
+            if let WakuEvent::WakuMessage(evt) = event {
+                received |= evt.waku_message.payload == ECHO_MESSAGE.as_bytes();
+            }
+        }
+        if received {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert!(received);
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn discv5_started_and_stopped() {
+    let node = start_node(WakuNodeConfig {
+        discv5_discovery: Some(true),
+        discv5_udp_port: Some(9010),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    node.discv5_stop().await.unwrap();
+    assert!(!node.discovery_status().discv5.enabled);
+    node.discv5_start().await.unwrap();
+    assert!(node.discovery_status().discv5.enabled);
+
+    node.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn peer_exchange_request_from_local_node() {
+    let server = start_node(Default::default()).await.unwrap();
+    // Without relay, so not made by the testing helpers
+    let client = waku_new(Some(WakuNodeConfig {
+        tcp_port: Some(0),
+        relay: Some(false),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+    client.set_event_callback(|_| {}).unwrap();
+    let client = client.start().await.unwrap();
+
+    let server_addr: Multiaddr = server.store_peer_address().await.unwrap().parse().unwrap();
+
+    // The server only shares the peers it discovered through discv5, possibly none
+    if let Ok(peers) = client
+        .peer_exchange_request(&server_addr, 5, Some(Duration::from_secs(5)))
+        .await
+    {
+        assert!(peers.iter().all(|peer| peer.to_string().contains("/p2p/")));
+        let status = client.discovery_status();
+        assert!(status.peer_exchange.last_success.is_some());
+    }
+    assert!(client
+        .connected_peer_ids()
+        .await
+        .unwrap()
+        .iter()
+        .any(|peer_id| server_addr.to_string().ends_with(peer_id.as_str())));
+
+    server.stop().await.unwrap().waku_destroy().await.unwrap();
+    client.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn published_message_seen() {
+    let pubsub_topic = PubsubTopic::new(TESTING_PUBSUB_TOPIC);
+    let (node1, node2) = two_connected_nodes().await.unwrap();
+
+    let content_topic = WakuContentTopic::new("toychat", "2", "seen", Encoding::Proto);
+    let message = WakuMessage::new(ECHO_MESSAGE, content_topic, 0, Vec::new(), false);
+    assert!(!node1.has_seen(&message.hash(&pubsub_topic)));
+    let hash = node1
+        .relay_publish_message(&message, &pubsub_topic, None)
+        .await
+        .unwrap();
+    assert!(node1.has_seen(&hash));
+
+    sleep(Duration::from_secs(1)).await;
+    assert!(node2.has_seen(&hash));
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn wait_closed_resolves_on_stop() {
+    let node = start_node(Default::default()).await.unwrap();
+
+    let waiter = tokio::spawn(node.wait_closed());
+    sleep(Duration::from_millis(100)).await;
+    assert!(!waiter.is_finished());
+
+    let node = node.stop().await.unwrap();
+    time::timeout(Duration::from_secs(5), waiter)
+        .await
+        .expect("waiter resolves once the node stops")
+        .unwrap()
+        .unwrap();
+
+    node.waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn local_enr_matches_node_key() {
+    let node_key =
+        SecretKey::from_str("05f381866cc21f6c1e2e80e07fa732008e36d942dce3206ad6dcd6793c98d609")
+            .unwrap();
+    let node = start_node(WakuNodeConfig {
+        node_key: Some(node_key),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let enr = node.local_enr().await.unwrap();
+    assert!(enr.starts_with("enr:-"));
+    let parsed = node.local_enr_parsed().await.unwrap();
+    assert_eq!(parsed.to_base64(), enr);
+    assert_eq!(
+        parsed.public_key().serialize(),
+        node_key
+            .public_key(&secp256k1::Secp256k1::new())
+            .serialize()
+    );
+
+    node.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn ready_once_relay_mesh_formed() {
+    let node1 = start_node(Default::default()).await.unwrap();
+    let node2 = start_node(Default::default()).await.unwrap();
+
+    // isolated node
+    assert_ne!(node1.health().await.unwrap(), NodeHealth::Ready);
+    assert!(node1
+        .wait_until_ready(Duration::from_millis(500))
+        .await
+        .is_err());
+
+    connect_to(&node2, &node1).await.unwrap();
+    node1
+        .wait_until_ready(Duration::from_secs(10))
+        .await
+        .unwrap();
+    assert_eq!(node1.health().await.unwrap(), NodeHealth::Ready);
+
+    node1.stop().await.unwrap().waku_destroy().await.unwrap();
+    node2.stop().await.unwrap().waku_destroy().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn relay_subscriptions_limited() {
+    // Not made by the testing helpers, whose relay subscription would take a slot
+    let node = waku_new(Some(WakuNodeConfig {
+        tcp_port: Some(0),
+        max_relay_subscriptions: Some(2),
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+    node.set_event_callback(|_| {}).unwrap();
+    let node = node.start().await.unwrap();
+
+    let topic_a = PubsubTopic::new("/waku/2/rs/0/1");
+    let topic_b = PubsubTopic::new("/waku/2/rs/0/2");
+    let topic_c = PubsubTopic::new("/waku/2/rs/0/3");
+    node.relay_subscribe(&topic_a).await.unwrap();
+    node.relay_subscribe(&topic_b).await.unwrap();
+    let err = node.relay_subscribe(&topic_c).await.unwrap_err();
+    assert_eq!(
+        err,
+        waku_bindings::WakuError::SubscriptionLimitReached { limit: 2 }
+    );
+
+    // Resubscribing doesn't count, unsubscribing frees a slot
+    node.relay_subscribe(&topic_a).await.unwrap();
+    node.relay_unsubscribe(&topic_b).await.unwrap();
+    node.relay_subscribe(&topic_c).await.unwrap();
+
+    // Concurrent subscriptions can't both take the last slot
+    node.relay_unsubscribe(&topic_c).await.unwrap();
+    let topic_d = PubsubTopic::new("/waku/2/rs/0/4");
+    let (c, d) = tokio::join!(
+        node.relay_subscribe(&topic_c),
+        node.relay_subscribe(&topic_d)
+    );
+    assert!(c.is_ok() != d.is_ok());
+
+    node.stop().await.unwrap().waku_destroy().await.unwrap();
+}